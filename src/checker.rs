@@ -0,0 +1,391 @@
+use super::parser::Instruction;
+use super::parser::MatchArm;
+use super::lexer::TokenType;
+use super::lexer::boolean_literal;
+use super::operators::top_level_operators;
+use super::operators::operator_symbol;
+use super::variables::Variable;
+use super::variables::VariableType;
+use super::engine::split_call;
+use super::common::ParseError;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+// What's known about a variable's type ahead of time, by name (e.g. "int" or "Point")
+struct KnownType
+{
+    type_name: Option<String>, // None if it can't be known until it runs
+    inferred_on: Option<usize> // The line of the "let" that declared it, if any
+}
+
+// Checks for mistakes that can be spotted before anything runs, returning a message for each one found
+pub fn check(instructions: &[Instruction]) -> Vec<ParseError>
+{
+    let mut problems = Vec::<ParseError>::new();
+
+    // Structs and enums may be declared anywhere, so find them all first. Each variant is noted along with its enum
+    // and the number of values it carries.
+    let mut structs = HashSet::<String>::new();
+    let mut enums = HashMap::<String, Vec<String>>::new();
+    let mut variants = HashMap::<String, (String, usize)>::new();
+    for instruction in instructions
+    {
+        if let Instruction::StructDeclaration { name, .. } = instruction {
+            structs.insert(name.clone());
+        }
+
+        if let Instruction::EnumDeclaration { name, variants: declared } = instruction
+        {
+            enums.insert(name.clone(), declared.iter().map(|(variant, _)| variant.clone()).collect());
+            for (variant, fields) in declared {
+                variants.insert(variant.clone(), (name.clone(), fields.len()));
+            }
+        }
+    }
+
+    for (i, instruction) in instructions.iter().enumerate()
+    {
+        if let Instruction::Match { arms, .. } = instruction {
+            check_match(arms, &enums, &variants, i, &mut problems);
+        }
+    }
+
+    let everything = Scope { last_line: instructions.len(), known: HashMap::new() };
+    let types = TypeContext { structs, variants, scopes: vec![everything] };
+    check_assignments(instructions, types, &mut problems);
+
+    problems
+}
+
+struct TypeContext
+{
+    structs: HashSet<String>,
+    variants: HashMap<String, (String, usize)>,
+    scopes: Vec<Scope> // The innermost last
+}
+
+// The variables declared within a block (e.g. a function or loop), which are forgotten once it ends
+struct Scope
+{
+    last_line: usize,
+    known: HashMap<String, KnownType>
+}
+
+impl TypeContext
+{
+    fn enter(&mut self, last_line: usize)
+    {
+        self.scopes.push(Scope { last_line, known: HashMap::new() });
+    }
+
+    // Leaves every block that ended before the line
+    fn leave_before(&mut self, line: usize)
+    {
+        while self.scopes.len() > 1 && self.scopes.last().is_some_and(|scope| scope.last_line < line) {
+            self.scopes.pop();
+        }
+    }
+
+    fn declare(&mut self, name: String, known: KnownType)
+    {
+        self.scopes.last_mut().expect("there is always a scope").known.insert(name, known);
+    }
+
+    fn known(&self, name: &str) -> Option<&KnownType>
+    {
+        self.scopes.iter().rev().find_map(|scope| scope.known.get(name))
+    }
+}
+
+/*
+    Variables declared with "let" take the type of whatever they're first given, and must keep to it, so anything
+    later assigned to them has to be of a compatible type. Other variables convert what they're given as they run.
+    A variable is assumed to be the one declared most recently with its name in the innermost block that has one,
+    just as when running.
+*/
+fn check_assignments(instructions: &[Instruction], mut types: TypeContext, problems: &mut Vec<ParseError>)
+{
+    // Cases and catches begin blocks of their own, found from the match or try they're part of. Each is kept by the
+    // line it begins on, along with its last line and any values it binds.
+    let mut blocks = HashMap::<usize, (usize, Vec<String>)>::new();
+
+    for (i, instruction) in instructions.iter().enumerate()
+    {
+        types.leave_before(i);
+        if let Some((last_line, bindings)) = blocks.remove(&i)
+        {
+            types.enter(last_line);
+            for name in bindings {
+                types.declare(name, KnownType { type_name: None, inferred_on: None });
+            }
+        }
+
+        let declared = |name: &String, variable_type: &VariableType| {
+            (name.clone(), Some(Variable { variable_type: variable_type.clone() }.type_name()))
+        };
+
+        let (name, type_name) = match instruction
+        {
+            Instruction::LetDeclaration { name, value } =>
+            {
+                let type_name = infer_type(value, &types);
+                if type_name.as_deref() == Some("none")
+                {
+                    problems.push(ParseError::new(format!(
+                        "cannot infer the type of \"{}\" from none on line {}, so give it a type (e.g. \"int?\")", name, i + 1
+                    ), i));
+                }
+
+                types.declare(name.clone(), KnownType { type_name, inferred_on: Some(i) });
+                continue
+            },
+
+            Instruction::Assignment { name, value } =>
+            {
+                let Some(KnownType { type_name: Some(expected), inferred_on: Some(line) }) = types.known(name) else {
+                    continue
+                };
+
+                if let Some(actual) = infer_type(value, &types)
+                {
+                    if !is_assignable(expected, &actual)
+                    {
+                        problems.push(ParseError::new(format!(
+                            "cannot assign {} to \"{}\" on line {}, as it was inferred to be {} on line {}",
+                            actual, name, i + 1, expected, line + 1
+                        ), i));
+                    }
+                }
+                continue
+            },
+
+            Instruction::IntDeclaration { name, optional, .. } => (name.clone(), Some(optional_name("int", *optional))),
+            Instruction::BoolDeclaration { name, optional, .. } => (name.clone(), Some(optional_name("bool", *optional))),
+            Instruction::StringDeclaration { name, optional, .. } => (name.clone(), Some(optional_name("string", *optional))),
+            Instruction::StructInstanceDeclaration { struct_name, name, optional, .. } =>
+                (name.clone(), Some(optional_name(struct_name, *optional))),
+            Instruction::ConstDeclaration { name, variable_type, .. } => declared(name, variable_type),
+            Instruction::ArrayDeclaration { name, .. } => (name.clone(), Some("array".to_string())),
+            Instruction::MapDeclaration { name, .. } => (name.clone(), Some("map".to_string())),
+
+            Instruction::FunctionDeclaration { arguments, last_line, .. } =>
+            {
+                types.enter(*last_line);
+                for (name, variable_type) in arguments
+                {
+                    let (name, type_name) = declared(name, variable_type);
+                    types.declare(name, KnownType { type_name, inferred_on: None });
+                }
+                continue
+            },
+
+            Instruction::IfValue { last_line, .. } | Instruction::IfValueIsValue { last_line, .. } |
+            Instruction::IfValueIsNotValue { last_line, .. } | Instruction::Test { last_line, .. } =>
+            {
+                types.enter(*last_line);
+                continue
+            },
+
+            Instruction::Match { arms, .. } =>
+            {
+                for arm in arms {
+                    blocks.insert(arm.first_line, (arm.last_line, arm.bindings.clone()));
+                }
+                continue
+            },
+
+            Instruction::Try { catch_line, last_line } =>
+            {
+                types.enter(catch_line - 1);
+                blocks.insert(*catch_line, (*last_line, Vec::new()));
+                continue
+            },
+
+            // Loop variables belong to the loop
+            Instruction::FromValueToValue { value, last_line, .. } =>
+            {
+                types.enter(*last_line);
+                (value.clone(), Some("int".to_string()))
+            },
+
+            // Anything else that creates a variable hides any earlier one of the same name
+            Instruction::FunctionCall { target_variable: Some(name), .. } => (name.clone(), None),
+            Instruction::Catch { name: Some(name) } => (name.clone(), Some("Error".to_string())),
+            Instruction::ForEach { key, value, last_line, .. } =>
+            {
+                types.enter(*last_line);
+                if let Some(value) = value {
+                    types.declare(value.clone(), KnownType { type_name: None, inferred_on: None });
+                }
+                (key.clone(), None)
+            },
+
+            _ => continue
+        };
+
+        types.declare(name, KnownType { type_name, inferred_on: None });
+    }
+}
+
+// Works out the type of a value ahead of time, where possible. Operators are applied left to right, as they are when
+// running.
+fn infer_type(value: &str, types: &TypeContext) -> Option<String>
+{
+    let chars = value.chars().collect::<Vec<char>>();
+    let operators = top_level_operators(value).collect::<Vec<(usize, TokenType)>>();
+
+    let first_end = operators.first().map(|(position, _)| *position).unwrap_or(chars.len());
+    let mut inferred = infer_operand(&chars[..first_end].iter().collect::<String>(), types);
+
+    for j in 0..operators.len()
+    {
+        let (position, operator) = &operators[j];
+        let start = position + operator_symbol(operator).chars().count();
+        let end = operators.get(j + 1).map(|(position, _)| *position).unwrap_or(chars.len());
+        let operand = chars[start..end].iter().collect::<String>();
+
+        inferred = match operator
+        {
+            TokenType::LessThan | TokenType::GreaterThan => Some("bool".to_string()),
+            TokenType::DefaultTo => match inferred.as_deref()
+            {
+                Some("none") => infer_operand(&operand, types),
+                Some(type_name) => Some(type_name.trim_end_matches('?').to_string()),
+                None => None
+            },
+            _ => inferred // Arithmetic keeps the type of the left-hand side
+        };
+    }
+
+    inferred
+}
+
+fn infer_operand(operand: &str, types: &TypeContext) -> Option<String>
+{
+    let operand = operand.trim();
+
+    let type_name = if !operand.is_empty() && operand.chars().all(|c| c.is_ascii_digit()) { "int" }
+        else if operand.len() >= 2 && operand.starts_with('\"') && operand.ends_with('\"') { "string" }
+        else if boolean_literal(operand).is_some() { "bool" }
+        else if operand == "none" { "none" }
+        else if operand.starts_with('[') { "array" }
+        else if operand.starts_with('{') { "map" }
+
+        else if let Some(function) = whole_call(operand)
+        {
+            if types.structs.contains(function) { return Some(function.to_string()) }
+            if let Some((enum_name, _)) = types.variants.get(function) { return Some(enum_name.clone()) }
+            stdlib_return_type(function)?
+        }
+
+        else if let Some(known) = types.known(operand) { return known.type_name.clone() }
+        else if let Some((enum_name, 0)) = types.variants.get(operand) { return Some(enum_name.clone()) }
+        else { return None };
+
+    Some(type_name.to_string())
+}
+
+// Gives the name of the function called if the whole operand is a single call (e.g. "foo(1 2)", but not "foo(1).bar")
+fn whole_call(operand: &str) -> Option<&str>
+{
+    let (function, arguments) = split_call(operand)?;
+    let mut depth = 0;
+
+    for c in arguments.chars()
+    {
+        match c
+        {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    if function.contains(['.', '[']) { None } else { Some(function) }
+}
+
+fn stdlib_return_type(function: &str) -> Option<&'static str>
+{
+    match function
+    {
+        "input" | "upper" | "lower" | "trim" | "join" | "replace" | "repeat" => Some("string"),
+        "find" => Some("int"),
+        "starts_with" | "ends_with" | "has_key" => Some("bool"),
+        "split" | "keys" | "values" | "slice" => Some("array"),
+        _ => None // Either unknown, or (like "len") dependent on what's passed
+    }
+}
+
+fn optional_name(type_name: &str, optional: bool) -> String
+{
+    if optional { format!("{}?", type_name) } else { type_name.to_string() }
+}
+
+// Integers and booleans convert freely between each other, as both do to strings, but strings can't be relied upon to
+// hold numbers. Something that may be none can only be given to something else that may be.
+fn is_assignable(expected: &str, actual: &str) -> bool
+{
+    if expected == actual { return true }
+
+    if let Some(inner) = expected.strip_suffix('?') {
+        return actual == "none" || is_assignable(inner, actual)
+    }
+
+    matches!((expected, actual), ("int" | "bool" | "string", "int" | "bool"))
+}
+
+// Every case of a match must name a variant of the same enum, and between them cover all of its variants (unless
+// there's a wildcard)
+fn check_match(arms: &[MatchArm], enums: &HashMap<String, Vec<String>>, variants: &HashMap<String, (String, usize)>,
+               line: usize, problems: &mut Vec<ParseError>)
+{
+    let mut matched_enum = Option::<&String>::None;
+    let mut matched = Vec::<&String>::new();
+
+    for arm in arms
+    {
+        if arm.variant == "_" { continue }
+
+        let Some((enum_name, field_count)) = variants.get(&arm.variant) else
+        {
+            let message = format!("unknown variant \"{}\" in match on line {}", arm.variant, arm.first_line + 1);
+            problems.push(ParseError::new(message, arm.first_line));
+            continue
+        };
+
+        if arm.bindings.len() != *field_count
+        {
+            problems.push(ParseError::new(format!(
+                "{} carries {} value(s) but {} were bound on line {}",
+                arm.variant, field_count, arm.bindings.len(), arm.first_line + 1
+            ), arm.first_line));
+        }
+
+        if matched.contains(&&arm.variant) {
+            let message = format!("variant {} matched more than once on line {}", arm.variant, arm.first_line + 1);
+            problems.push(ParseError::new(message, arm.first_line));
+        }
+        matched.push(&arm.variant);
+
+        match matched_enum
+        {
+            Some(matched_enum) if matched_enum != enum_name => problems.push(ParseError::new(format!(
+                "match on line {} mixes variants of {} and {}", line + 1, matched_enum, enum_name
+            ), line)),
+            _ => matched_enum = Some(enum_name)
+        }
+    }
+
+    if arms.iter().any(|arm| arm.variant == "_") { return }
+
+    if let Some(matched_enum) = matched_enum
+    {
+        let missing = enums[matched_enum].iter().filter(|v| !matched.contains(v)).cloned().collect::<Vec<String>>();
+        if !missing.is_empty() {
+            let message = format!("non-exhaustive match on line {}, missing {}", line + 1, missing.join(", "));
+            problems.push(ParseError::new(message, line));
+        }
+    }
+}
@@ -1,5 +1,208 @@
+use std::fmt;
+
 pub fn error(message: String) -> !
 {
-    println!("Error: {}", message);
+    eprintln!("Error: {}", message);
     std::process::exit(1);
-}
\ No newline at end of file
+}
+
+// Reports several errors at once, such as those found before running anything
+pub fn errors(errors: Vec<ParseError>) -> !
+{
+    for error in errors {
+        eprintln!("Error: {}", error);
+    }
+    std::process::exit(1);
+}
+
+// A problem found in a script before running it (i.e. while parsing or checking it). The message says where it is,
+// but the (0-based) line is kept too, for anything that needs to point it out itself (e.g. an editor).
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError
+{
+    pub message: String,
+    pub line: usize
+}
+
+impl ParseError
+{
+    pub fn new(message: String, line: usize) -> ParseError
+    {
+        ParseError { message, line }
+    }
+}
+
+impl fmt::Display for ParseError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+// The kinds of error that can happen while running, which scripts can tell apart when catching them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ErrorKind
+{
+    Type, // Something of the wrong type (e.g. "attempt to cast none to int")
+    Value, // Something of the right type that still can't be used (e.g. "abc" as a number)
+    Index, // An index out of range
+    Key, // A key missing from a map
+    Name, // Something that doesn't exist, or already does
+    Argument, // The wrong number of arguments
+    Runtime, // Anything else (e.g. returning outside of a function)
+    EndOfInput, // Nothing left to read
+    Assertion, // An assert that didn't hold
+    Thrown, // Raised by the script itself
+
+    // Limits placed on the script, which can't be caught
+    DepthLimit,
+    InstructionLimit,
+    SizeLimit,
+    Timeout,
+
+    Stopped // By whatever is running the script (e.g. a debugger told to quit), which can't be caught either
+}
+
+impl ErrorKind
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            ErrorKind::Type => "type",
+            ErrorKind::Value => "value",
+            ErrorKind::Index => "index",
+            ErrorKind::Key => "key",
+            ErrorKind::Name => "name",
+            ErrorKind::Argument => "argument",
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::EndOfInput => "eof",
+            ErrorKind::Assertion => "assertion",
+            ErrorKind::Thrown => "thrown",
+            ErrorKind::DepthLimit => "depth",
+            ErrorKind::InstructionLimit => "instructions",
+            ErrorKind::SizeLimit => "size",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Stopped => "stopped"
+        }
+    }
+
+    pub fn is_limit(&self) -> bool
+    {
+        matches!(self, ErrorKind::DepthLimit | ErrorKind::InstructionLimit | ErrorKind::SizeLimit | ErrorKind::Timeout)
+    }
+
+    // Whether a try block (or assert_error) may carry on after it, which would let the script ignore it
+    pub fn can_be_caught(&self) -> bool
+    {
+        !self.is_limit() && *self != ErrorKind::Stopped
+    }
+
+    // Anything unrecognised (or a limit) is taken to have been thrown by the script
+    pub fn from_name(name: &str) -> ErrorKind
+    {
+        let kinds = [
+            ErrorKind::Type, ErrorKind::Value, ErrorKind::Index, ErrorKind::Key,
+            ErrorKind::Name, ErrorKind::Argument, ErrorKind::Runtime, ErrorKind::EndOfInput,
+            ErrorKind::Assertion
+        ];
+        kinds.into_iter().find(|kind| kind.name() == name).unwrap_or(ErrorKind::Thrown)
+    }
+}
+
+// An error raised while running, which ends the script unless it's caught. The line and trace are filled in by the
+// engine once the error reaches it, as nothing below it knows what's running.
+#[derive(Clone, Debug)]
+pub struct RuntimeError
+{
+    pub kind: ErrorKind,
+    pub message: String,
+    pub line: Option<usize>,
+    pub trace: Vec<Call> // The functions being run when it was raised, innermost first
+}
+
+// A function that was running, along with the values of its arguments
+#[derive(Clone, Debug)]
+pub struct Call
+{
+    pub function: String,
+    pub arguments: Vec<(String, String)>,
+    pub line: usize // The line it was called from
+}
+
+// How many calls in a row to the same function are shown before the rest are summarised
+const REPEATED_CALLS_SHOWN: usize = 3;
+
+impl RuntimeError
+{
+    pub fn new(kind: ErrorKind, message: String) -> RuntimeError
+    {
+        RuntimeError { kind, message, line: None, trace: Vec::new() }
+    }
+
+    // Keeps the line the error was first raised on, if it already has one
+    pub fn on_line(mut self, line: usize) -> RuntimeError
+    {
+        self.line.get_or_insert(line);
+        self
+    }
+}
+
+pub fn fail<T>(kind: ErrorKind, message: String) -> Result<T, RuntimeError>
+{
+    Err(RuntimeError::new(kind, message))
+}
+
+// Checks the size of an array or string (e.g. one about to be made) against the maximum allowed, if any
+pub fn check_size(description: &str, size: usize, max_size: Option<usize>) -> Result<(), RuntimeError>
+{
+    match max_size
+    {
+        Some(max_size) if size > max_size =>
+            fail(ErrorKind::SizeLimit, format!("{} of size {} exceeds the maximum size of {}", description, size, max_size)),
+        _ => Ok(())
+    }
+}
+
+impl fmt::Display for RuntimeError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.line
+        {
+            Some(line) => write!(f, "{} - line {}", self.message, line + 1)?,
+            None => write!(f, "{}", self.message)?
+        }
+
+        // Deep recursion would otherwise bury everything else, so repeated calls are cut short
+        let mut i = 0;
+        while i < self.trace.len()
+        {
+            let function = &self.trace[i].function;
+            let repeats = self.trace[i..].iter().take_while(|call| call.function == *function).count();
+
+            for call in &self.trace[i..i + repeats.min(REPEATED_CALLS_SHOWN)] {
+                write!(f, "\n    in {} called on line {}", call, call.line + 1)?;
+            }
+            if repeats > REPEATED_CALLS_SHOWN {
+                write!(f, "\n    ... {} called {} more times", function, repeats - REPEATED_CALLS_SHOWN)?;
+            }
+
+            i += repeats;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Call
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let arguments = self.arguments.iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<String>>();
+        write!(f, "{}({})", self.function, arguments.join(", "))
+    }
+}
@@ -0,0 +1,486 @@
+use super::lexer::tokenise_lines;
+use super::parser::parse_lines;
+use super::parser::Instruction;
+use super::checker::check;
+use super::engine::Hook;
+use super::engine::Limits;
+use super::engine::State;
+use super::debugger::Mode;
+use super::streams::Io;
+use super::common::ErrorKind;
+use super::common::ParseError;
+use super::variables::Variable;
+use super::variables::VariableType;
+use super::lsp::read_message;
+use super::lsp::write_message;
+use super::json::Json;
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+/*
+    A debug adapter, speaking the Debug Adapter Protocol over whatever it's given to read from and write to (framed
+    the same way as the language server's messages). It waits to be launched and configured, then runs the program,
+    pausing at breakpoints and steps just as the terminal debugger does. There's only ever one thread. What the
+    program prints is sent as output events, and as messages come in where input would, it has no input to read.
+*/
+
+const THREAD_ID: usize = 1;
+
+struct Connection
+{
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: usize // Of the last message sent
+}
+
+impl Connection
+{
+    // Gives None once there's nothing left to read, skipping anything that isn't valid
+    fn receive(&mut self) -> Option<Json>
+    {
+        loop
+        {
+            if let Ok(message) = read_message(self.input.as_mut())? {
+                return Some(message)
+            }
+        }
+    }
+
+    fn send(&mut self, message_type: &str, fields: Vec<(&str, Json)>)
+    {
+        self.seq += 1;
+        let mut message = vec![("seq", self.seq.into()), ("type", message_type.into())];
+        message.extend(fields);
+
+        // Whoever was listening has gone, so there's no one to tell
+        let _ = write_message(self.output.as_mut(), &Json::object(message));
+    }
+
+    fn event(&mut self, event: &str, body: Json)
+    {
+        self.send("event", vec![("event", event.into()), ("body", body)]);
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>)
+    {
+        let mut fields = vec![
+            ("request_seq", request.get("seq").cloned().unwrap_or(Json::Null)),
+            ("success", result.is_ok().into()),
+            ("command", request.get("command").cloned().unwrap_or(Json::Null))
+        ];
+        match result
+        {
+            Ok(body) => fields.push(("body", body)),
+            Err(message) => fields.push(("message", message.into()))
+        }
+        self.send("response", fields);
+    }
+}
+
+// Sends what the program prints to the client
+struct AdapterIo
+{
+    connection: Rc<RefCell<Connection>>
+}
+
+impl Io for AdapterIo
+{
+    fn read_line(&mut self) -> Option<String>
+    {
+        None
+    }
+
+    fn write(&mut self, text: &str)
+    {
+        let body = Json::object(vec![("category", "stdout".into()), ("output", text.into())]);
+        self.connection.borrow_mut().event("output", body);
+    }
+
+    fn write_error(&mut self, text: &str)
+    {
+        let body = Json::object(vec![("category", "stderr".into()), ("output", text.into())]);
+        self.connection.borrow_mut().event("output", body);
+    }
+}
+
+struct Program
+{
+    path: String,
+    instructions: Vec<Instruction>,
+    stop_on_entry: bool
+}
+
+// What a variables reference given to the client refers to, which lasts until the program carries on
+enum Reference
+{
+    Scope { call: usize }, // What's declared within a call (see State::call_variables)
+    Value(Variable) // The elements or fields of a value
+}
+
+enum Outcome
+{
+    Stay,
+    Resume,
+    Disconnect
+}
+
+struct Adapter
+{
+    connection: Rc<RefCell<Connection>>,
+    program: Option<Program>,
+    configured: bool,
+    breakpoints: Vec<usize>,
+    mode: Mode,
+    step_reason: &'static str, // Why the next pause from stepping happens
+    references: Vec<Reference> // Each given as its index plus one, as zero means there's nothing to refer to
+}
+
+// Handles messages until told to disconnect (or there's nothing left to read), running the program once launched
+pub fn serve(input: Box<dyn BufRead>, output: Box<dyn Write>, limits: &Limits)
+{
+    let connection = Rc::new(RefCell::new(Connection { input, output, seq: 0 }));
+    let mut adapter = Adapter::new(Rc::clone(&connection));
+
+    // Breakpoints are set once launched, so wait until then before running anything
+    while adapter.program.is_none() || !adapter.configured
+    {
+        let Some(request) = adapter.receive() else { return };
+        if let Outcome::Disconnect = adapter.handle(&request, None) { return }
+    }
+
+    let Some(program) = &adapter.program else { return };
+    let instructions = program.instructions.clone();
+    if program.stop_on_entry {
+        adapter.mode = Mode::StepInto;
+    }
+
+    let mut state = State::default();
+    state.set_limits(limits.clone());
+    state.set_io(Box::new(AdapterIo { connection: Rc::clone(&connection) }));
+    state.set_hook(Some(Box::new(adapter)));
+
+    let exit_code = match state.execute(instructions)
+    {
+        Ok(()) => 0,
+
+        // Told to disconnect while paused, so there's no one left to tell it's finished
+        Err(error) if error.kind == ErrorKind::Stopped => return,
+        Err(error) =>
+        {
+            state.io().write_error(&format!("Error: {}\n", error));
+            1
+        }
+    };
+    state.set_hook(None);
+    connection.borrow_mut().event("exited", Json::object(vec![("exitCode", exit_code.into())]));
+    connection.borrow_mut().event("terminated", Json::object(Vec::new()));
+
+    // The client may still ask things before disconnecting
+    let mut adapter = Adapter::new(connection);
+    while let Some(request) = adapter.receive()
+    {
+        if let Outcome::Disconnect = adapter.handle(&request, None) { return }
+    }
+}
+
+impl Adapter
+{
+    fn new(connection: Rc<RefCell<Connection>>) -> Adapter
+    {
+        Adapter {
+            connection,
+            program: None,
+            configured: false,
+            breakpoints: Vec::new(),
+            mode: Mode::Continue,
+            step_reason: "entry",
+            references: Vec::new()
+        }
+    }
+
+    fn receive(&self) -> Option<Json>
+    {
+        self.connection.borrow_mut().receive()
+    }
+
+    // Responds to a request, given the state if the program is paused
+    fn handle(&mut self, request: &Json, state: Option<&mut State>) -> Outcome
+    {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or_default();
+        let arguments = request.get("arguments").unwrap_or(&Json::Null);
+        let mut outcome = Outcome::Stay;
+
+        let result = match command
+        {
+            "initialize" => Ok(Json::object(vec![
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsEvaluateForHovers", true.into())
+            ])),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "configurationDone" =>
+            {
+                self.configured = true;
+                Ok(Json::Null)
+            },
+            "threads" => Ok(Json::object(vec![("threads", Json::Array(vec![
+                Json::object(vec![("id", THREAD_ID.into()), ("name", "main".into())])
+            ]))])),
+            "disconnect" | "terminate" =>
+            {
+                outcome = Outcome::Disconnect;
+                Ok(Json::Null)
+            },
+
+            "stackTrace" | "scopes" | "variables" | "evaluate" | "continue" | "next" | "stepIn" | "stepOut" =>
+            {
+                match state
+                {
+                    Some(state) => self.handle_paused(command, arguments, state, &mut outcome),
+                    None => Err("the program is not paused".to_string())
+                }
+            },
+
+            _ => Err(format!("unsupported request \"{}\"", command))
+        };
+
+        self.connection.borrow_mut().respond(request, result);
+        if command == "initialize" {
+            self.connection.borrow_mut().event("initialized", Json::object(Vec::new()));
+        }
+        outcome
+    }
+
+    fn handle_paused(&mut self, command: &str, arguments: &Json, state: &mut State, outcome: &mut Outcome)
+        -> Result<Json, String>
+    {
+        let depth = state.call_depth();
+        let mut resume = |mode: Mode, adapter: &mut Adapter| {
+            adapter.mode = mode;
+            adapter.step_reason = "step";
+            *outcome = Outcome::Resume;
+        };
+
+        match command
+        {
+            "stackTrace" => Ok(self.stack_trace(state)),
+            "scopes" =>
+            {
+                let frame = arguments.get("frameId").and_then(Json::as_usize).unwrap_or(1);
+                let call = frame.saturating_sub(1).min(depth);
+                let mut scopes = vec![self.scope("Locals", Reference::Scope { call })];
+                if call < depth {
+                    scopes.push(self.scope("Globals", Reference::Scope { call: depth }));
+                }
+                Ok(Json::object(vec![("scopes", Json::Array(scopes))]))
+            },
+            "variables" =>
+            {
+                let reference = arguments.get("variablesReference").and_then(Json::as_usize).unwrap_or_default();
+                let named = match self.references.get(reference.wrapping_sub(1))
+                {
+                    Some(Reference::Scope { call }) => state.call_variables(*call),
+                    Some(Reference::Value(value)) => children(value),
+                    None => return Err(format!("unknown variables reference {}", reference))
+                };
+
+                let variables = named.into_iter().map(|(name, value)| self.variable(&name, value)).collect();
+                Ok(Json::object(vec![("variables", Json::Array(variables))]))
+            },
+            "evaluate" =>
+            {
+                let expression = arguments.get("expression").and_then(Json::as_str).unwrap_or_default();
+                let value = state.inspect(expression).map_err(|error| error.message)?;
+                let reference = self.reference(&value);
+                Ok(Json::object(vec![("result", shown(&value).into()), ("variablesReference", reference.into())]))
+            },
+            "continue" =>
+            {
+                resume(Mode::Continue, self);
+                Ok(Json::object(vec![("allThreadsContinued", true.into())]))
+            },
+            "next" =>
+            {
+                resume(Mode::StepOver { depth }, self);
+                Ok(Json::Null)
+            },
+            "stepIn" =>
+            {
+                resume(Mode::StepInto, self);
+                Ok(Json::Null)
+            },
+            _ =>
+            {
+                resume(Mode::StepOut { depth }, self);
+                Ok(Json::Null)
+            }
+        }
+    }
+
+    fn launch(&mut self, arguments: &Json) -> Result<Json, String>
+    {
+        let Some(path) = arguments.get("program").and_then(Json::as_str) else {
+            return Err("expected the path of the program to launch".to_string())
+        };
+
+        let source = fs::read_to_string(path).map_err(|error| format!("could not read \"{}\": {}", path, error))?;
+        let lines = source.lines().map(String::from).collect::<Vec<String>>();
+        let instructions = parse_lines(&tokenise_lines(&lines)).map_err(|error| error.to_string())?;
+
+        let problems = check(&instructions);
+        if !problems.is_empty() {
+            return Err(problems.iter().map(ParseError::to_string).collect::<Vec<String>>().join("\n"))
+        }
+
+        let stop_on_entry = arguments.get("stopOnEntry") == Some(&Json::Bool(true));
+        self.program = Some(Program { path: path.to_string(), instructions, stop_on_entry });
+        Ok(Json::Null)
+    }
+
+    // Only lines with code on them can be paused on, so the rest aren't verified
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json
+    {
+        let requested = arguments.get("breakpoints").and_then(Json::as_array).cloned().unwrap_or_default();
+        let mut breakpoints = Vec::<Json>::new();
+        self.breakpoints.clear();
+
+        for breakpoint in requested
+        {
+            let line = breakpoint.get("line").and_then(Json::as_usize).unwrap_or_default();
+            let has_code = |program: &Program| {
+                program.instructions.get(line - 1).is_some_and(|instruction| !matches!(instruction, Instruction::NoOp))
+            };
+            let verified = line >= 1 && self.program.as_ref().is_none_or(has_code);
+
+            if verified {
+                self.breakpoints.push(line - 1);
+            }
+            breakpoints.push(Json::object(vec![("verified", verified.into()), ("line", line.into())]));
+        }
+
+        Json::object(vec![("breakpoints", Json::Array(breakpoints))])
+    }
+
+    // Each call being run is a frame of its own, innermost first, then the top level
+    fn stack_trace(&self, state: &State) -> Json
+    {
+        let calls = state.backtrace();
+        let source = self.program.as_ref().map(|program| {
+            let name = Path::new(&program.path).file_name().map(|name| name.to_string_lossy().to_string());
+            Json::object(vec![("name", name.unwrap_or_default().into()), ("path", program.path.clone().into())])
+        }).unwrap_or(Json::Null);
+
+        let frames = (0..=calls.len()).map(|i| {
+            let name = calls.get(i).map(|call| call.function.clone()).unwrap_or("main".to_string());
+            let line = if i == 0 { state.line() } else { calls[i - 1].line };
+            Json::object(vec![
+                ("id", (i + 1).into()),
+                ("name", name.into()),
+                ("source", source.clone()),
+                ("line", (line + 1).into()),
+                ("column", 1.into())
+            ])
+        }).collect::<Vec<Json>>();
+
+        Json::object(vec![("totalFrames", frames.len().into()), ("stackFrames", Json::Array(frames))])
+    }
+
+    fn scope(&mut self, name: &str, reference: Reference) -> Json
+    {
+        self.references.push(reference);
+        Json::object(vec![
+            ("name", name.into()),
+            ("variablesReference", self.references.len().into()),
+            ("expensive", false.into())
+        ])
+    }
+
+    fn variable(&mut self, name: &str, value: Variable) -> Json
+    {
+        let reference = self.reference(&value);
+        Json::object(vec![
+            ("name", name.into()),
+            ("value", shown(&value).into()),
+            ("type", value.type_name().into()),
+            ("variablesReference", reference.into())
+        ])
+    }
+
+    // Values holding others can be expanded to show them
+    fn reference(&mut self, value: &Variable) -> usize
+    {
+        if children(value).is_empty() { return 0 }
+        self.references.push(Reference::Value(value.clone()));
+        self.references.len()
+    }
+}
+
+impl Hook for Adapter
+{
+    fn before_line(&mut self, state: &mut State)
+    {
+        let reason = if self.mode.reached(state.call_depth()) { self.step_reason }
+            else if self.breakpoints.contains(&state.line()) { "breakpoint" }
+            else { return };
+
+        self.references.clear();
+        let body = Json::object(vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into())
+        ]);
+        self.connection.borrow_mut().event("stopped", body);
+
+        loop
+        {
+            // With no one left to tell, let the program run to the end
+            let Some(request) = self.receive() else
+            {
+                self.mode = Mode::Continue;
+                self.breakpoints.clear();
+                return
+            };
+
+            match self.handle(&request, Some(state))
+            {
+                Outcome::Stay => {},
+                Outcome::Resume => return,
+                Outcome::Disconnect =>
+                {
+                    state.stop();
+                    return
+                }
+            }
+        }
+    }
+}
+
+// Strings are quoted, to tell them apart from anything else
+fn shown(value: &Variable) -> String
+{
+    match &value.variable_type
+    {
+        VariableType::Str(string) => format!("\"{}\"", string),
+        _ => value.printed_string()
+    }
+}
+
+// The elements or fields a value holds, by name
+fn children(value: &Variable) -> Vec<(String, Variable)>
+{
+    match &value.variable_type
+    {
+        VariableType::Array(array) => array.elements.iter().enumerate()
+            .map(|(i, element)| (format!("[{}]", i), element.clone())).collect(),
+        VariableType::Map(map) => map.entries().iter()
+            .map(|(key, value)| (shown(&key.to_variable()), value.clone())).collect(),
+        VariableType::Struct(instance) => instance.fields.clone(),
+        VariableType::Enum(instance) => instance.fields.clone(),
+        _ => Vec::new()
+    }
+}
@@ -0,0 +1,256 @@
+use super::engine::Hook;
+use super::engine::State;
+
+use std::io::BufRead;
+use std::io::Write;
+
+/*
+    A debugger driven from the terminal, pausing the script at breakpoints, after each step, or when a watched
+    variable changes. While paused, commands are read a line at a time (see HELP). Steps are measured in calls, so
+    stepping over a line runs any function it calls to the end, and stepping out runs until the current function
+    returns. It starts paused on the first line.
+*/
+
+const HELP: &str = "\
+break <line>      pause whenever the line is reached (b)
+delete <line>     remove the breakpoint on the line
+continue          run until the next breakpoint or change to a watched variable (c)
+step              run the line, pausing inside any function it calls (s)
+next              run the line, including any functions it calls (n)
+out               run until the current function returns (o)
+backtrace         show the functions being run (bt)
+print <value>     work out a value from where the script is (p)
+variables         show every variable visible from where the script is (v)
+watch <name>      pause whenever the variable changes
+unwatch <name>    stop watching the variable
+list              show the lines around where the script is (l)
+quit              stop running the script (q)";
+
+// How far to run before pausing again, other than at breakpoints
+pub enum Mode
+{
+    Continue,
+    StepInto,
+    StepOver { depth: usize }, // Pause once back at this call depth (or fewer)
+    StepOut { depth: usize } // Pause once at fewer calls than this
+}
+
+impl Mode
+{
+    // Whether a line run at the given call depth is far enough
+    pub fn reached(&self, depth: usize) -> bool
+    {
+        match self
+        {
+            Mode::Continue => false,
+            Mode::StepInto => true,
+            Mode::StepOver { depth: over } => depth <= *over,
+            Mode::StepOut { depth: out } => depth < *out
+        }
+    }
+}
+
+struct Watch
+{
+    name: String,
+    value: Option<String> // As printed, or None if not visible
+}
+
+pub struct Debugger
+{
+    source: Vec<String>,
+    input: Box<dyn BufRead>, // Where commands are read from
+    output: Box<dyn Write>, // Where what the debugger has to say is written, separately from the script's output
+    breakpoints: Vec<usize>,
+    watches: Vec<Watch>,
+    mode: Mode,
+    last_lines: Vec<usize> // The line last run at each call depth, so that changes are put down to the right one
+}
+
+impl Debugger
+{
+    pub fn new(source: Vec<String>, input: Box<dyn BufRead>, output: Box<dyn Write>) -> Debugger
+    {
+        Debugger {
+            source,
+            input,
+            output,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            mode: Mode::StepInto,
+            last_lines: Vec::new()
+        }
+    }
+
+    // What can't be written has no one to read it, and shouldn't stop the script
+    fn show(&mut self, text: &str)
+    {
+        let _ = writeln!(self.output, "{}", text);
+    }
+
+    fn should_pause(&self, state: &State) -> bool
+    {
+        self.mode.reached(state.call_depth()) || self.breakpoints.contains(&state.line())
+    }
+
+    // Shows where the script has paused, with the line about to run
+    fn show_location(&mut self, line: usize)
+    {
+        let text = format!("line {}: {}", line + 1, self.source.get(line).map(|text| text.trim()).unwrap_or_default());
+        self.show(&text);
+    }
+
+    fn list(&mut self, line: usize)
+    {
+        let first = line.saturating_sub(3);
+        let last = (line + 4).min(self.source.len());
+        for i in first..last
+        {
+            let marker = if i == line { "->" } else if self.breakpoints.contains(&i) { " *" } else { "  " };
+            let text = format!("{} {:>4}  {}", marker, i + 1, self.source[i]);
+            self.show(&text);
+        }
+    }
+
+    // Reads and carries out commands until one that resumes the script
+    fn pause(&mut self, state: &mut State)
+    {
+        loop
+        {
+            let _ = write!(self.output, "(debug) ");
+            let _ = self.output.flush();
+
+            // With nothing left to read, let the script run to the end
+            let mut command = String::new();
+            if !matches!(self.input.read_line(&mut command), Ok(read) if read > 0)
+            {
+                self.show("");
+                self.breakpoints.clear();
+                self.watches.clear();
+                self.mode = Mode::Continue;
+                return
+            };
+
+            let (command, argument) = command.trim().split_once(' ').unwrap_or((command.trim(), ""));
+            let argument = argument.trim();
+
+            match command
+            {
+                "continue" | "c" => { self.mode = Mode::Continue; return },
+                "step" | "s" => { self.mode = Mode::StepInto; return },
+                "next" | "n" => { self.mode = Mode::StepOver { depth: state.call_depth() }; return },
+                "out" | "o" =>
+                {
+                    if state.call_depth() == 0 {
+                        self.show("not inside a function");
+                        continue
+                    }
+                    self.mode = Mode::StepOut { depth: state.call_depth() };
+                    return
+                },
+                "quit" | "q" => { state.stop(); return },
+
+                "break" | "b" => match self.line_argument(argument)
+                {
+                    Ok(line) if self.source[line].trim().is_empty() || self.source[line].trim().starts_with("//") =>
+                        self.show(&format!("line {} has no code to pause on", line + 1)),
+                    Ok(line) =>
+                    {
+                        if !self.breakpoints.contains(&line) {
+                            self.breakpoints.push(line);
+                        }
+                        self.show(&format!("breakpoint on line {}", line + 1));
+                    },
+                    Err(message) => self.show(&message)
+                },
+                "delete" => match self.line_argument(argument)
+                {
+                    Ok(line) if self.breakpoints.contains(&line) =>
+                    {
+                        self.breakpoints.retain(|breakpoint| *breakpoint != line);
+                        self.show(&format!("removed the breakpoint on line {}", line + 1));
+                    },
+                    Ok(line) => self.show(&format!("no breakpoint on line {}", line + 1)),
+                    Err(message) => self.show(&message)
+                },
+
+                "backtrace" | "bt" =>
+                {
+                    self.show(&format!("at line {}", state.line() + 1));
+                    for call in state.backtrace() {
+                        self.show(&format!("    in {} called on line {}", call, call.line + 1));
+                    }
+                },
+                "print" | "p" => match state.inspect(argument)
+                {
+                    Ok(value) => self.show(&value.printed_string()),
+                    Err(error) => self.show(&format!("Error: {}", error.message))
+                },
+                "variables" | "v" =>
+                {
+                    for (name, value) in state.visible_variables() {
+                        self.show(&format!("{} = {}", name, value.printed_string()));
+                    }
+                },
+                "watch" if !argument.is_empty() =>
+                {
+                    let value = state.visible(argument).map(|value| value.printed_string());
+                    self.watches.retain(|watch| watch.name != argument);
+                    self.watches.push(Watch { name: argument.to_string(), value });
+                    self.show(&format!("watching {}", argument));
+                },
+                "unwatch" => self.watches.retain(|watch| watch.name != argument),
+                "list" | "l" => self.list(state.line()),
+                "help" | "h" => self.show(HELP),
+                "" => {}
+                _ => self.show(&format!("unknown command \"{}\" (try \"help\")", command))
+            }
+        }
+    }
+
+    // Gives the (0-based) line of a line number
+    fn line_argument(&self, argument: &str) -> Result<usize, String>
+    {
+        match argument.parse::<usize>()
+        {
+            Ok(line) if line >= 1 && line <= self.source.len() => Ok(line - 1),
+            _ => Err(format!("expected a line number from 1 to {}", self.source.len()))
+        }
+    }
+}
+
+impl Hook for Debugger
+{
+    fn before_line(&mut self, state: &mut State)
+    {
+        // A function's arguments are worked out by its caller, and what it returns is used by its caller after it
+        // returns, so anything changed is put down to the last line run at this depth, or else the caller's
+        let depth = state.call_depth();
+        let last_line = self.last_lines.get(depth.min(self.last_lines.len().saturating_sub(1))).copied();
+
+        let mut changes = Vec::<String>::new();
+        for watch in &mut self.watches
+        {
+            let value = state.visible(&watch.name).map(|value| value.printed_string());
+            if value != watch.value
+            {
+                let shown = |value: &Option<String>| value.clone().unwrap_or("(not declared)".to_string());
+                let line = last_line.map(|line| format!(" on line {}", line + 1)).unwrap_or_default();
+                changes.push(format!("{} changed{} from {} to {}", watch.name, line, shown(&watch.value), shown(&value)));
+                watch.value = value;
+            }
+        }
+
+        if !changes.is_empty() || self.should_pause(state)
+        {
+            for change in changes {
+                self.show(&change);
+            }
+            self.show_location(state.line());
+            self.pause(state);
+        }
+
+        self.last_lines.resize(depth + 1, 0);
+        self.last_lines[depth] = state.line();
+    }
+}
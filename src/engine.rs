@@ -1,27 +1,58 @@
+use super::lexer::TokenType;
+use super::lexer::boolean_literal;
 use super::parser::Instruction;
 use super::parser::Instruction::*;
 use super::variables::Variable;
 use super::variables::VariableType;
+use super::variables::Array;
+use super::variables::Map;
+use super::variables::MapKey;
+use super::variables::Struct;
+use super::variables::Enum;
 use crate::variables::is_str_valid_type;
+use crate::variables::value_of_type;
 use super::operators::value_contains_operator;
+use super::operators::top_level_operators;
 use super::operators::is_char_operator;
-use super::operators::operator_char_to_token_type;
+use super::operators::operator_symbol;
 use super::operators::evaluate_operator_expression;
 use super::operators::OperatorExpression;
 use super::stdlib::stdlib_function;
-use super::common::error;
-
+use super::stdlib::Receiver;
+use super::natives::Natives;
+use super::streams::Io;
+use super::streams::StandardIo;
+use super::common::RuntimeError;
+use super::common::Call;
+use super::common::ErrorKind;
+use super::common::fail;
+use super::common::check_size;
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
 
 type FunctionInfo = (usize, Vec<(String, VariableType)>);
+type StructInfo = Vec<(String, VariableType)>;
+type EnumInfo = Vec<(String, Vec<(String, VariableType)>)>;
 
 #[derive(Clone)]
 enum Frame
 {
     Root,
     ForLoop { variable: String, start_line: usize, end_value: String },
-    Function { caller_line: usize, target_variable: Option<String> },
-    IfStatement
+    ForEachLoop { key: String, value: Option<String>, items: Rc<Vec<(Variable, Variable)>>, index: usize, start_line: usize },
+    // Inline if called mid-expression. The function is found by the line it's declared on.
+    Function { first_line: usize, caller_line: usize, target_variable: Option<String>, inline: bool },
+    IfStatement,
+    MatchArm { end_line: usize }, // Where the whole match ends
+    Try { catch_line: usize, end_line: usize },
+    Catch,
+    Test
 }
 
 struct FrameContext
@@ -29,7 +60,12 @@ struct FrameContext
     frame: Frame,
     variables: HashMap<String, Variable>,
     functions: HashMap<String, FunctionInfo>, // beginning line, arguments
-    arrays: HashMap<String, Vec<Variable>>
+    arrays: HashMap<String, Array>,
+    maps: HashMap<String, Map>,
+    structs: HashMap<String, StructInfo>, // fields
+    enums: HashMap<String, EnumInfo>, // variants, with their fields
+    optionals: HashMap<String, VariableType>, // the declared types of variables that may be none
+    constants: HashSet<String>
 }
 
 impl FrameContext
@@ -38,525 +74,2243 @@ impl FrameContext
     {
         self.variables.clear();
         self.functions.clear();
+        self.arrays.clear();
+        self.maps.clear();
+        self.structs.clear();
+        self.enums.clear();
+        self.optionals.clear();
+        self.constants.clear();
     }
 }
 
-#[derive(Default)]
-pub struct State
+// Limits on what a script may do, so that one that runs away ends with an error rather than running forever or out
+// of memory. None means no limit.
+#[derive(Clone, Debug)]
+pub struct Limits
 {
-    line: usize,
-    frames: Vec<FrameContext>
+    pub max_call_depth: Option<usize>,
+    pub max_instructions: Option<u64>,
+    pub max_size: Option<usize>, // Of any one array or string, made by the script
+    pub max_stack: Option<usize>, // In bytes, of the stack used by calls made from within expressions
+    pub timeout: Option<Duration>
 }
 
-impl State
+// Calls made from within expressions recurse within the engine itself, so by default they may only use as much of
+// the stack as any thread (e.g. one spawned with the default size) has to spare, and hitting either limit on depth
+// gives an error rather than overflowing the stack
+impl Default for Limits
 {
-    pub fn execute(&mut self, instructions: Vec<Instruction>)
+    fn default() -> Limits
     {
-        // Set up root frame
-        self.add_frame(Frame::Root);
-
-        // Helper "variables"
-        let one = Variable { variable_type: VariableType::Integer(1) };
-
-        // Boolean declarations - TODO: fix
-        self.make_variable_of_type(&String::from("true"), &VariableType::Boolean(true));
-        self.make_variable_of_type(&String::from("false"), &VariableType::Boolean(false));
-
-        while self.line != instructions.len()
-        {
-            match &instructions[self.line]
-            {
-                FromValueToValue { value, start, end } =>
-                {
-                    // Don't run if conditions not valid
-                    let start_value = self.evaluate_value(start);
-                    if start_value < self.evaluate_value(end)
-                    {
-                        // Ensure the value is a valid variable name
-                        if self.is_numeric(value) {
-                            self.error("invalid variable name");
-                        }
-
-                        self.add_frame(Frame::ForLoop {
-                            variable: value.clone(),
-                            start_line: self.line,
-                            end_value: end.clone()
-                        });
-
-                        self.make_variable_of_type(value, &VariableType::Integer(0));
-                        self.set_variable(value, start);
-                    }
-                },
-
-                IfValue { left_value, last_line } =>
-                {
-                    if self.evaluate_value(left_value).as_integer() != 0 {
-                        self.add_frame(Frame::IfStatement);
-                    }
-                    else {
-                        self.line = *last_line;
-                    }
-                },
-
-                IfValueIsValue { left_value, right_value, last_line } =>
-                {
-                    if self.evaluate_value(left_value) == self.evaluate_value(right_value) {
-                        self.add_frame(Frame::IfStatement);
-                    }
-                    else {
-                        self.line = *last_line;
-                    }
-                },
-
-                IfValueIsNotValue { left_value, right_value, last_line } =>
-                {
-                    if self.evaluate_value(left_value) != self.evaluate_value(right_value) {
-                        self.add_frame(Frame::IfStatement);
-                    }
-                    else {
-                        self.line = *last_line;
-                    }
-                }
+        Limits {
+            max_call_depth: Some(1000), max_instructions: None, max_size: None, max_stack: Some(1024 * 1024), timeout: None
+        }
+    }
+}
 
-                FunctionDeclaration { name, first_line, last_line, arguments } =>
-                {
-                    // Note function then sally on forth
-                    if self.innermost_frame().functions.insert(name.clone(), (*first_line, arguments.clone())).is_some() {
-                        self.error("function already declared");
-                    }
-                    self.line = *last_line;
-                },
+// Told about each line before it runs, so that something (e.g. a debugger) can pause there and look around. It's
+// taken out of the state while it's being told, so nothing it runs (e.g. by inspecting) tells it again.
+pub trait Hook
+{
+    fn before_line(&mut self, state: &mut State);
 
-                FunctionCall { function, values, target_variable } =>
-                {
-                    // Check for user-defined functions first, then if that fails, assume it's in-built
-                    let mut found_function = Option::<FunctionInfo>::default();
-                    self.for_each_frame(|frame, _| {
-                        if found_function.is_none() && frame.functions.contains_key(function) {
-                            let _ = found_function.insert(frame.functions.get(function).unwrap().clone());
-                        }
-                    });
+    // Told about each value worked out (other than literals), and each variable written to, as they happen
+    fn evaluated(&mut self, _state: &State, _value: &str, _result: &Variable) {}
+    fn written(&mut self, _state: &State, _name: &str, _value: &Variable) {}
 
-                    if found_function.is_some()
-                    {
-                        self.add_frame(Frame::Function { caller_line: self.line, target_variable: target_variable.clone() });
+    // Told when a function called from within an expression has returned, as the line it was called from carries on
+    fn returned(&mut self, _state: &State) {}
+}
 
-                        // Check argument lengths match
-                        let desired_args = &found_function.as_ref().unwrap().1;
-                        if desired_args.len() != values.len() {
-                            self.error("invalid number of function arguments");
-                        }
+pub struct State
+{
+    line: usize,
+    frames: Vec<FrameContext>,
+    instructions: Rc<Vec<Instruction>>,
+    returned: Option<Variable>, // The value last returned by a function called from within an expression
+    constants: HashMap<usize, Variable>, // The value of each constant, by the line it's declared on
+    limits: Limits,
+    executed: u64, // How many instructions have been run so far
+    calls: u64, // How many functions have been called so far
+    started: Option<Instant>,
+    stack_base: usize, // Where the stack was up to when the script started running, for limiting how much it uses
+    stopping: bool, // Whether the hook asked for the script to stop
+    natives: Natives,
+    io: Box<dyn Io>,
+    hook: Option<Box<dyn Hook>>
+}
 
-                        // Pass arguments
-                        for i in 0..desired_args.len()
-                        {
-                            let name = &desired_args[i].0;
-                            let variable_type = desired_args[i].1.clone();
-
-                            // Be careful to evaluate the value early, before we make the new one, as if they
-                            // have the same name, we'll accidentally use the new one in any evaluating, as may
-                            // happen in recursive functions.
-                            let value_evaluated_early = self.evaluate_value(&values[i]);
-                            self.make_variable_of_type(name, &variable_type);
-                            self.get_variable(name).set(&value_evaluated_early);
-                        }
+impl Default for State
+{
+    fn default() -> State
+    {
+        State {
+            line: 0,
+            frames: Vec::new(),
+            instructions: Rc::default(),
+            returned: None,
+            constants: HashMap::new(),
+            limits: Limits::default(),
+            executed: 0,
+            calls: 0,
+            started: None,
+            stack_base: 0,
+            stopping: false,
+            natives: Natives::default(),
+            io: Box::new(StandardIo),
+            hook: None
+        }
+    }
+}
 
-                        self.line = found_function.as_ref().unwrap().0;
-                    }
+impl State
+{
+    pub fn set_limits(&mut self, limits: Limits)
+    {
+        self.limits = limits;
+    }
 
-                    // Function not found, assume part of the "standard library"
-                    else
-                    {
-                        // Evaluate arguments first
-                        let arguments = values.iter().map(|v| {
-                            self.evaluate_value(v)
-                        }).collect();
+    // For adding or removing the native functions scripts can call
+    pub fn natives(&mut self) -> &mut Natives
+    {
+        &mut self.natives
+    }
 
-                        // Run function (if any)
-                        let stdlib_result = stdlib_function(function.as_str(), &arguments);
-                        let stdlib_did_run = stdlib_result.0;
-                        let stdlib_return = stdlib_result.1;
+    pub fn set_io(&mut self, io: Box<dyn Io>)
+    {
+        self.io = io;
+    }
 
-                        if stdlib_did_run
-                        {
-                            // Standard library function was found, set target variable if need be
-                            if target_variable.is_some() && stdlib_return.is_some()
-                            {
-                                println!("{:#?}", stdlib_return.as_ref().unwrap().variable_type);
-                                self.make_variable_of_type(target_variable.as_ref().unwrap(), &stdlib_return.as_ref().unwrap().variable_type);
-                                self.get_variable(&target_variable.as_ref().unwrap()).set(&stdlib_return.unwrap());
-                            }
-                        }
-                        else {
-                            self.error("unknown function");
-                        }
-                    }
-                },
+    pub fn io(&mut self) -> &mut dyn Io
+    {
+        self.io.as_mut()
+    }
 
-                Return { value } =>
-                {
-                    // Search for function frame (if any)
-                    let mut frame_info = Option::<(usize, Option<String>)>::default();
-                    let mut frame_index = Option::<usize>::default();
+    pub fn set_hook(&mut self, hook: Option<Box<dyn Hook>>)
+    {
+        self.hook = hook;
+    }
 
-                    self.for_each_frame(|frame, index|
-                    {
-                        if frame_info.is_none()
-                        {
-                            match &frame.frame
-                            {
-                                Frame::Function { caller_line, target_variable } =>
-                                {
-                                    let _ = frame_info.insert((caller_line.clone(), target_variable.clone()));
-                                    let _ = frame_index.insert(index);
-                                }
-                                _ => {}
-                            }
-                        }
-                    });
+    // Called by the hook to stop the script before the line it was told about runs, ending whatever is running it
+    // with an error of kind Stopped
+    pub fn stop(&mut self)
+    {
+        self.stopping = true;
+    }
 
-                    if frame_info.is_some()
-                    {
-                        // We can't just pop the current frame off because we may be returning from a function,
-                        // but within an if statement, for example, so instead we need to put potentially more
-                        // than once!
+    // Runs every instruction, stopping at the first error that isn't caught
+    pub fn execute(&mut self, instructions: Vec<Instruction>) -> Result<(), RuntimeError>
+    {
+        self.execute_from(instructions, 0)
+    }
 
-                        let target_variable = &frame_info.as_ref().unwrap().1;
-                        let line_number = frame_info.as_ref().unwrap().0;
+    // Runs the instructions from the given line on, keeping everything declared by those before it, which must be
+    // exactly the instructions run before (e.g. by an earlier call to this)
+    pub fn execute_from(&mut self, instructions: Vec<Instruction>, first_line: usize) -> Result<(), RuntimeError>
+    {
+        self.instructions = Rc::new(instructions);
+        self.start_running();
 
-                        // Evaluate returned variable first, before we pop the frame
-                        if target_variable.is_some()
-                        {
-                            let evaluated = self.evaluate_value(value);
+        let result = self.evaluate_constants(first_line).and_then(|_|
+        {
+            self.line = first_line;
+            self.run(0)
+        });
+        self.stop_running();
+        result
+    }
 
-                            for _ in 0..(self.frames.len()-frame_index.unwrap()) {
-                                self.frames.pop();
-                            }
+    // Runs the test declared on the given line in a frame of its own, once everything outside of tests has been run
+    pub fn execute_test(&mut self, test_line: usize) -> Result<(), RuntimeError>
+    {
+        self.start_running();
+        self.add_frame(Frame::Test);
 
-                            self.make_variable_of_type(target_variable.as_ref().unwrap(), &evaluated.variable_type);
-                            self.get_variable(target_variable.as_ref().unwrap()).set(&evaluated);
-                            self.line = line_number; // Set last so error names carrying line numbers make sense
-                        }
-                        else
-                        {
-                            for _ in 0..(self.frames.len()-frame_index.unwrap()) {
-                                self.frames.pop();
-                            }
+        self.line = test_line + 1;
+        let result = self.run(1);
+        self.stop_running();
+        result
+    }
 
-                            self.line = line_number;
-                        }
-                    }
-                    else { self.error("cannot return outside of a function"); }
-                },
+    // Calls a function declared by what's been run so far with the given arguments, giving back what it returns, or
+    // none if it doesn't return anything
+    pub fn call(&mut self, function: &str, arguments: Vec<Variable>) -> Result<Variable, RuntimeError>
+    {
+        let Some(function_info) = self.find_function(function) else {
+            return fail(ErrorKind::Name, format!("function \"{}\" does not exist", function))
+        };
 
-                Done =>
-                {
-                    // At the termination of a frame, it's our responsibility to go back to the start (potentially).
-                    // In other words, this is the instruction that'll contain the logic for loops.
+        self.start_running();
+        let depth = self.frames.len();
+        let line = self.line;
 
-                    if self.frames.is_empty() {
-                        self.error("no appropriate frame");
-                    }
+        let result = self.enter_function_with_values(function_info, arguments, None, true).and_then(|_|
+        {
+            self.line += 1;
+            self.run(depth)
+        });
+        self.stop_running();
 
-                    match self.innermost_frame().frame.clone()
-                    {
-                        Frame::ForLoop { variable, start_line, end_value } =>
-                        {
-                            if self.get_variable(&variable).clone() + one.clone() >= self.evaluate_value(&end_value)
-                            {
-                                // End of loop reached
-                                self.frames.pop();
-                            }
-                            else
-                            {
-                                // Loop back, but start with (essentially) a new frame
-                                *self.get_variable(&variable) += one.clone();
-                                let variable_backup = self.get_variable(&variable).clone();
-                                self.innermost_frame().clear();
-                                self.innermost_frame().variables.insert(variable, variable_backup);
-                                self.line = start_line;
-                            }
-                        },
-
-                        Frame::Function { caller_line, target_variable } =>
-                        {
-                            self.frames.pop();
+        self.line = line;
+        result?;
+        Ok(self.returned.take().unwrap_or(Variable { variable_type: VariableType::None }))
+    }
 
-                            if target_variable.is_some()
-                            {
-                                // No value was returned, so raise error
-                                self.error("function did not return valid value")
-                            }
+    // Works out a value in the context of what's been run so far, as if it were on the given line
+    pub fn evaluate(&mut self, value: &str, line: usize) -> Result<Variable, RuntimeError>
+    {
+        self.start_running();
+        self.line = line;
 
-                            self.line = caller_line;
-                        },
+        let result = self.evaluate_value(value).map_err(|error|
+        {
+            if error.line.is_some() { error } else { RuntimeError { trace: self.stack_trace(), ..error.on_line(line) } }
+        });
+        self.stop_running();
+        result
+    }
 
-                        Frame::IfStatement => {
-                            self.frames.pop();
-                        },
+    // The value of a variable, array or map declared at the top level
+    pub fn global(&self, name: &str) -> Option<Variable>
+    {
+        frame_value(self.frames.first()?, name)
+    }
 
-                        Frame::Root => {
-                            self.error("attempt to terminate root frame");
-                        }
-                    }
-                },
+    // Sets a variable at the top level, keeping to its type if it already exists, or declaring it if not
+    pub fn set_global(&mut self, name: &str, value: &Variable) -> Result<(), RuntimeError>
+    {
+        if self.frames.is_empty() {
+            self.add_frame(Frame::Root);
+        }
 
-                IntDeclaration { name, value } =>
-                {
-                    // Evaluate first, before the variable is created, to prevent stuff like "int foo = foo"
-                    let evaluated = self.evaluate_value(&value);
-                    self.make_variable_of_type(name, &VariableType::Integer(0));
-                    self.get_variable(name).set(&evaluated);
-                },
+        let name = name.to_string();
+        if self.frames[0].constants.contains(&name) {
+            return fail(ErrorKind::Name, format!("cannot assign to constant \"{}\"", name))
+        }
 
-                BoolDeclaration { name, value } =>
-                {
-                    // Evaluate first, before the variable is created, to prevent stuff like "int foo = foo"
-                    let evaluated = self.evaluate_value(&value);
-                    self.make_variable_of_type(name, &VariableType::Boolean(false));
-                    self.get_variable(name).set(&evaluated);
-                },
+        let root = &mut self.frames[0];
+        if let Some(variable) = root.variables.get_mut(&name) {
+            return variable.set(value)
+        }
 
-                StringDeclaration { name, value } =>
-                {
-                    // Evaluate first, before the variable is created, to prevent stuff like "int foo = foo"
-                    let evaluated = self.evaluate_value(&value);
-                    self.make_variable_of_type(name, &VariableType::Str(String::new()));
-                    self.get_variable(name).set(&evaluated);
-                },
+        // Arrays and maps are replaced whole, keeping the type of what they hold
+        if let (Some(array), VariableType::Array(value)) = (root.arrays.get_mut(&name), &value.variable_type)
+        {
+            *array = Array::new(array.element_type.as_deref().cloned(), value.elements.clone())?;
+            return Ok(())
+        }
+        if let (Some(map), VariableType::Map(value)) = (root.maps.get_mut(&name), &value.variable_type)
+        {
+            let mut replacement = Map::new(map.value_type.as_deref().cloned());
+            for (key, value) in value.entries() {
+                replacement.insert(key.clone(), value)?;
+            }
+            *map = replacement;
+            return Ok(())
+        }
 
-                ArrayDeclaration { name } =>
-                {
-                    if self.innermost_frame().arrays.insert(name.clone(), Vec::<Variable>::new()).is_some()
-                    {
-                        // Array of same name already existed
-                        self.error("array already exists with specified name");
-                    }
-                },
+        self.make_variable_with_value(&name, value)
+    }
 
-                Assignment { name, value } => { self.set_variable(name, value); }
+    // The line about to run
+    pub fn line(&self) -> usize
+    {
+        self.line
+    }
 
-                NoOp => {},
-            }
+    // How many functions are being run, each called from within the last
+    pub fn call_depth(&self) -> usize
+    {
+        self.frames.iter().filter(|frame| matches!(frame.frame, Frame::Function { .. })).count()
+    }
 
-            self.line += 1;
-        }
+    // How many frames (functions, loops, blocks and so on) the script is within
+    pub fn frame_depth(&self) -> usize
+    {
+        self.frames.len() - 1
     }
 
-    pub fn print_variables(&self)
+    // The names of the functions being run, outermost first
+    pub fn call_stack(&self) -> Vec<String>
     {
-        for i in 0..self.frames.len()
+        self.frames.iter().filter_map(|frame| match frame.frame
         {
-            for variable in &self.frames[i].variables
+            Frame::Function { first_line, .. } => match &self.instructions[first_line]
             {
-                for _ in 0..i { print!("    "); }
-                println!("{}: {:?}", variable.0, variable.1.variable_type);
-            }
+                FunctionDeclaration { name, .. } => Some(name.clone()),
+                _ => None
+            },
+            _ => None
+        }).collect()
+    }
 
-            for (name, array) in &self.frames[i].arrays
-            {
-                for _ in 0..i { print!("    "); }
-                println!("{}: {:#?}", name, array);
-            }
-        }
+    // How many functions have been called so far, including those still being run
+    pub fn calls_made(&self) -> u64
+    {
+        self.calls
     }
 
-    fn error(&self, message: &str) -> !
+    // The functions being run, innermost first
+    pub fn backtrace(&self) -> Vec<Call>
     {
-        error(format!("{} - line {}", message, self.line + 1));
+        self.stack_trace()
     }
 
-    fn is_numeric(&self, value: &String) -> bool
+    // The value a name has from where the script is, be it a variable, array or map
+    pub fn visible(&self, name: &str) -> Option<Variable>
     {
-        for character in value.chars()
-        {
-            if !character.is_numeric() {
-                return false
-            }
-        }
-        true
+        self.frames.iter().rev().find_map(|frame| frame_value(frame, name))
     }
 
-    fn innermost_frame(&mut self) -> &mut FrameContext
+    // Everything visible from where the script is, by name
+    pub fn visible_variables(&self) -> Vec<(String, Variable)>
     {
-        let index = self.frames.len()-1;
-        &mut self.frames[index]
+        variables_in(&self.frames)
     }
 
-    fn add_frame(&mut self, frame: Frame)
+    // Everything declared within one of the calls being run (0 being the innermost, and the call depth being the top
+    // level), by name
+    pub fn call_variables(&self, call: usize) -> Vec<(String, Variable)>
     {
-        self.frames.push(FrameContext {
-            frame,
-            variables: HashMap::<String, Variable>::new(),
-            functions: HashMap::<String, FunctionInfo>::new(),
-            arrays: HashMap::<String, Vec<Variable>>::new()
-        });
+        let starts = (0..self.frames.len())
+            .filter(|i| matches!(self.frames[*i].frame, Frame::Function { .. }))
+            .collect::<Vec<usize>>();
+        if call > starts.len() { return Vec::new() }
+
+        let first = if call == starts.len() { 0 } else { starts[starts.len() - 1 - call] };
+        let last = if call == 0 { self.frames.len() } else { starts[starts.len() - call] };
+        variables_in(&self.frames[first..last])
     }
 
-    fn for_each_frame<F: FnMut(&FrameContext, usize)>(&self, mut f: F)
+    // Works out a value from where the script is, which then carries on as if nothing happened (other than anything
+    // the value itself changes)
+    pub fn inspect(&mut self, value: &str) -> Result<Variable, RuntimeError>
     {
-        // Go from inner-most frame to root (i.e. reversed)
-        for i in 1..=self.frames.len()
-        {
-            let index = self.frames.len() - i;
-            f(&self.frames[index], index);
-        }
+        let (line, depth, returned) = (self.line, self.frames.len(), self.returned.take());
+
+        let result = self.evaluate_value(value);
+        self.frames.truncate(depth);
+        self.line = line;
+        self.returned = returned;
+        result
     }
 
-    fn evaluate_value(&mut self, value: &String) -> Variable
+    fn start_running(&mut self)
     {
-        // A value may simply be something like "3" or "my_variable_name", but may also contain operators like "+" or "-".
-        // To this end, parse each individual "actual value" (inner value) and combine them with any operators to form an
-        // expression of sorts that can be evaluated separately, containing only numbers and operators. Of course, for values
-        // not containing any operators, this can be skipped.
+        self.started = Some(Instant::now());
+        self.stack_base = stack_position();
+        self.stopping = false;
+        self.executed = 0;
+        self.calls = 0;
+
+        if self.frames.is_empty() {
+            self.add_frame(Frame::Root);
+        }
+    }
 
-        if !value_contains_operator(value) { return self.evaluate_inner_value(value) }
+    // An uncaught error may leave us anywhere, so go back to the top level for whatever runs next
+    fn stop_running(&mut self)
+    {
+        self.frames.truncate(1);
+    }
 
-        let mut expression = Vec::<OperatorExpression>::new();
-        let mut word = Vec::<char>::new();
+    // Constants may only depend on literals and each other, so can all be worked out before anything runs. Those
+    // depended upon must be declared first.
+    fn evaluate_constants(&mut self, first_line: usize) -> Result<(), RuntimeError>
+    {
+        self.add_frame(Frame::Root);
 
-        // March along, growing each accumulated "word" until an operator is found (or the string ends)
-        for i in 0..value.len()
+        let instructions = Rc::clone(&self.instructions);
+        for i in first_line..instructions.len()
         {
-            let char = value.chars().nth(i).unwrap();
-            let is_operator = is_char_operator(char);
-            word.push(char);
+            let ConstDeclaration { name, variable_type, value } = &instructions[i] else { continue };
+            self.line = i;
 
-            if is_operator || i == value.len() -1
+            let words = split_operands(value);
+            let is_constant = words.iter().all(|word| is_literal(word) || self.is_constant(word));
+            if !is_constant
             {
-                if i != value.len() - 1 {
-                    word.pop(); // Final character will be the operator, so remove
-                }
-
-                expression.push(OperatorExpression::Variable(
-                    self.evaluate_inner_value(&word.iter().collect())
-                ));
+                let message = format!("constant \"{}\" must only depend on literals and other constants", name);
+                return Err(RuntimeError::new(ErrorKind::Value, message).on_line(i))
+            }
 
-                if is_operator
-                {
-                    expression.push(OperatorExpression::Operator(
-                        operator_char_to_token_type(char)
-                    ));
-                }
+            let evaluated = self.evaluate_value(value).and_then(|v| value_of_type(variable_type, &v))
+                .map_err(|error| error.on_line(i))?;
+            self.constants.insert(i, evaluated.clone());
 
-                word.clear();
-            }
+            // Constants are kept in a frame of their own for now, so that the next ones can refer to them
+            self.innermost_frame().variables.insert(name.clone(), evaluated);
+            self.innermost_frame().constants.insert(name.clone());
         }
 
-        return evaluate_operator_expression(&expression)
+        self.frames.pop();
+        Ok(())
     }
 
-    fn evaluate_inner_value(&mut self, value: &String) -> Variable
+    // Tells the hook something, if there is one
+    fn tell_hook(&mut self, tell: impl FnOnce(&mut dyn Hook, &State))
     {
-        // Treat numbers as temporary ints
-        if self.is_numeric(value) {
-            Variable {
-                variable_type: VariableType::Integer(value.parse().unwrap())
-            }
-        }
-
-        // Strings
-        else if value.len() >= 2 && value.chars().nth(0).unwrap() == '\"' && value.chars().nth(value.len()-1).unwrap() == '\"'
+        if let Some(mut hook) = self.hook.take()
         {
-            let mut new_value = value.clone();
-            new_value.pop();
-            new_value.remove(0);
-
-            Variable {
-                variable_type: VariableType::Str(new_value)
-            }
+            tell(hook.as_mut(), self);
+            self.hook = Some(hook);
         }
-
-        // Otherwise it must be a variable name
-        else { self.get_variable(value).clone() }
     }
 
-    fn get_variable(&mut self, name: &String) -> &mut Variable
+    // Steps through instructions until the frame at the given depth finishes (or everything does), catching errors
+    // with any try block entered along the way
+    fn run(&mut self, depth: usize) -> Result<(), RuntimeError>
     {
-        for i in 1..=self.frames.len()
+        while self.frames.len() > depth && self.line < self.instructions.len()
         {
-            let index = self.frames.len() - i;
-
-            // Arrays - TODO: cache index, etc outside of loop
-            if name.contains("[") && name.contains("]") && name.len() >= 3
+            let line = self.line;
+            if !matches!(self.instructions[line], NoOp)
             {
-                // For now, do not support "embedding" arrays (e.g. foo[foo[0]] - TODO: fix :)
-                if name.matches('[').collect::<Vec<&str>>().len() > 1 ||
-                    name.matches(']').collect::<Vec<&str>>().len() > 1 {
-                    self.error("embedded indexing is not supported");
-                }
-
-                if name.find("]").unwrap() > name.find("[").unwrap() + 1
+                if let Some(mut hook) = self.hook.take()
                 {
-                    let index_begin = name.find("[").unwrap() + 1;
-                    let index_end = name.find("]").unwrap();
+                    hook.before_line(self);
+                    self.hook = Some(hook);
+                }
+                if self.stopping {
+                    return fail(ErrorKind::Stopped, "stopped before finishing".to_string())
+                }
+            }
 
-                    let array_name = &name[0..index_begin-1];
-                    let array_index = self.evaluate_value(&name[index_begin..index_end].to_string())
-                                .as_integer() as usize;
+            if let Err(error) = self.check_limits().and_then(|_| self.step())
+            {
+                // Errors from functions called mid-expression have already been placed where they happened
+                let error = if error.line.is_some() { error } else {
+                    RuntimeError { trace: self.stack_trace(), ..error.on_line(line) }
+                };
+                self.catch(error, depth)?;
+            }
+        }
 
-                    if self.frames[index].arrays.contains_key(array_name)
-                    {
-                        let array = self.frames[index].arrays.get_mut(array_name).unwrap();
+        Ok(())
+    }
 
-                        // If element exists...
-                        return if array.len() > array_index {
-                            &mut array[array_index]
-                        }
+    fn check_limits(&mut self) -> Result<(), RuntimeError>
+    {
+        self.executed += 1;
 
-                        // ...else create it (since this function's callers do not distinguish between using
-                        // this for both setting and getting from arrays, and it makes the language more
-                        // convenient to use... I suppose)
-                        else
-                        {
-                            // Use string as the default because that way, it can adopt any value, whereas
-                            // something like an integer could not. E.g. the following would not work:
-                            // foo[i] (of type int) = "hello"
-                            array.resize(array_index + 1, Variable {
-                                variable_type: VariableType::Str(String::new())
-                            });
-                            &mut array[array_index]
-                        }
-                    }
-                }
+        if let Some(max_instructions) = self.limits.max_instructions
+        {
+            if self.executed > max_instructions {
+                return fail(ErrorKind::InstructionLimit, format!("exceeded the limit of {} instructions", max_instructions))
             }
+        }
 
-            // Normal variables
-            else if self.frames[index].variables.contains_key(name) {
-                return self.frames[index].variables.get_mut(name).unwrap();
+        if let (Some(timeout), Some(started)) = (self.limits.timeout, self.started)
+        {
+            if started.elapsed() > timeout {
+                return fail(ErrorKind::Timeout, format!("timed out after {:?}", timeout))
             }
         }
 
-        self.error(format!("variable \"{}\" does not exist", name).as_str());
+        Ok(())
     }
 
-    fn set_variable(&mut self, name: &String, value: &String)
+    // Jumps to the catch of the innermost try block, so long as it was entered within what's being run, otherwise
+    // leaving the error for whatever called it (e.g. the expression a function was called from) to deal with. Limits
+    // being reached, or the script being stopped, can't be caught, as the script would then be free to carry on.
+    fn catch(&mut self, error: RuntimeError, depth: usize) -> Result<(), RuntimeError>
     {
-        let evaluated = self.evaluate_value(value);
-        self.get_variable(name).set(&evaluated);
+        if !error.kind.can_be_caught() { return Err(error) }
+
+        let try_frame = self.frames.iter().rposition(|frame| matches!(frame.frame, Frame::Try { .. }));
+        let Some(index) = try_frame.filter(|index| *index >= depth) else { return Err(error) };
+        let Frame::Try { catch_line, .. } = self.frames[index].frame else { unreachable!() };
+
+        // Leave everything inside the try block, including any functions called from within it
+        self.frames.truncate(index);
+        self.add_frame(Frame::Catch);
+
+        let instructions = Rc::clone(&self.instructions);
+        if let Catch { name: Some(name) } = &instructions[catch_line] {
+            self.make_variable_with_value(name, &error_value(&error))?;
+        }
+
+        self.line = catch_line + 1;
+        Ok(())
     }
 
-    fn make_variable_of_type(&mut self, name: &String, variable_type: &VariableType)
+    fn step(&mut self) -> Result<(), RuntimeError>
     {
-        let len = self.frames.len();
+        // Helper "variables"
+        let one = Variable { variable_type: VariableType::Integer(1) };
 
-        if self.is_numeric(name) ||
-            value_contains_operator(name) ||
-            is_str_valid_type(name.as_str()) ||
-            name.contains("[") ||
-            name.contains("]")
+        let instructions = Rc::clone(&self.instructions);
+        match &instructions[self.line]
         {
-            self.error("invalid variable name");
-        }
+            FromValueToValue { value, start, end, .. } =>
+            {
+                // Don't run if conditions not valid
+                let start_value = self.evaluate_value(start)?;
+                if start_value.compare(&self.evaluate_value(end)?)? == Ordering::Less
+                {
+                    // Ensure the value is a valid variable name
+                    if self.is_numeric(value) {
+                        return fail(ErrorKind::Name, "invalid variable name".to_string())
+                    }
 
-        if !self.frames[len-1].variables.contains_key(name)
-        {
-            self.frames[len-1].variables.insert(name.clone(), Variable {
-                variable_type: variable_type.clone()
-            });
-        }
-        else {
-            self.error("variable already exists");
-        }
+                    self.add_frame(Frame::ForLoop {
+                        variable: value.clone(),
+                        start_line: self.line,
+                        end_value: end.clone()
+                    });
+
+                    self.make_variable_of_type(value, &VariableType::Integer(0))?;
+                    self.set_variable(value, start)?;
+                }
+            },
+
+            ForEach { key, value, collection, last_line } =>
+            {
+                // Iterate over a copy, so that the loop isn't affected by any changes to the collection itself
+                let collection = self.evaluate_value(collection)?;
+                let items = self.items_of(&collection, value.is_some())?;
+
+                // Don't run if there's nothing to iterate over
+                if items.is_empty() {
+                    self.line = *last_line;
+                }
+                else
+                {
+                    self.add_frame(Frame::ForEachLoop {
+                        key: key.clone(),
+                        value: value.clone(),
+                        items: Rc::new(items),
+                        index: 0,
+                        start_line: self.line
+                    });
+                    self.make_loop_variables(0)?;
+                }
+            },
+
+            IfValue { left_value, last_line } =>
+            {
+                if self.evaluate_value(left_value)?.as_integer()? != 0 {
+                    self.add_frame(Frame::IfStatement);
+                }
+                else {
+                    self.line = *last_line;
+                }
+            },
+
+            IfValueIsValue { left_value, right_value, last_line } =>
+            {
+                if self.evaluate_value(left_value)?.equals(&self.evaluate_value(right_value)?)? {
+                    self.add_frame(Frame::IfStatement);
+                }
+                else {
+                    self.line = *last_line;
+                }
+            },
+
+            IfValueIsNotValue { left_value, right_value, last_line } =>
+            {
+                if !self.evaluate_value(left_value)?.equals(&self.evaluate_value(right_value)?)? {
+                    self.add_frame(Frame::IfStatement);
+                }
+                else {
+                    self.line = *last_line;
+                }
+            }
+
+            Match { value, arms, last_line } =>
+            {
+                let evaluated = self.evaluate_value(value)?;
+                let VariableType::Enum(instance) = &evaluated.variable_type else {
+                    return fail(ErrorKind::Type, format!("cannot match on {}", evaluated.type_name()))
+                };
+
+                let Some(arm) = arms.iter().find(|arm| arm.variant == instance.variant || arm.variant == "_") else {
+                    return fail(ErrorKind::Value, format!("unmatched variant {} of {}", instance.variant, instance.name))
+                };
+
+                if arm.variant != "_" && arm.bindings.len() != instance.fields.len()
+                {
+                    return fail(ErrorKind::Argument, format!(
+                        "{} carries {} value(s) but {} were bound", instance.variant, instance.fields.len(), arm.bindings.len()
+                    ))
+                }
+
+                // Run the case as though it were an if statement, that then skips the rest of the match
+                self.add_frame(Frame::MatchArm { end_line: *last_line });
+                for i in 0..arm.bindings.len()
+                {
+                    if arm.bindings[i] != "_" {
+                        self.make_variable_with_value(&arm.bindings[i], &instance.fields[i].1)?;
+                    }
+                }
+                self.line = arm.first_line;
+            },
+
+            Case => { return fail(ErrorKind::Runtime, "case outside of match".to_string()) },
+
+            Try { catch_line, last_line } =>
+            {
+                self.add_frame(Frame::Try { catch_line: *catch_line, end_line: *last_line });
+            },
+
+            Catch { .. } =>
+            {
+                // The try block finished without an error, so skip what would have handled one
+                let Frame::Try { end_line, .. } = self.innermost_frame().frame else {
+                    return fail(ErrorKind::Runtime, "catch outside of try".to_string())
+                };
+
+                self.frames.pop();
+                self.line = end_line;
+            },
+
+            Throw { value } =>
+            {
+                let evaluated = self.evaluate_value(value)?;
+                return Err(thrown_error(&evaluated))
+            },
+
+            // Tests are only run on their own, once everything else has been
+            Test { last_line, .. } => self.line = *last_line,
+
+            FunctionDeclaration { name, first_line, last_line, arguments } =>
+            {
+                // Note function then sally on forth
+                for (_, variable_type) in arguments {
+                    self.check_type_exists(variable_type)?;
+                }
+                if self.innermost_frame().functions.insert(name.clone(), (*first_line, arguments.clone())).is_some() {
+                    return fail(ErrorKind::Name, "function already declared".to_string())
+                }
+                self.line = *last_line;
+            },
+
+            FunctionCall { function, values, target_variable } =>
+            {
+                // Check for user-defined functions first, then if that fails, assume it's in-built
+                if let Some(function_info) = self.find_function(function) {
+                    self.enter_function(function_info, values, target_variable.clone(), false)?;
+                }
+
+                // Structs and enum variants are constructed as if they were functions (e.g. "Point(1 2) -> point")
+                else if let Some(instance) = self.construct(function, values)?
+                {
+                    if let Some(target_variable) = target_variable {
+                        self.make_variable_with_value(target_variable, &instance)?;
+                    }
+                }
+
+                // Function not found, assume part of the "standard library"
+                else
+                {
+                    let stdlib_return = self.call_stdlib_function(function, values)?;
+
+                    // Standard library function was found, set target variable if need be
+                    if let (Some(target_variable), Some(stdlib_return)) = (target_variable, stdlib_return) {
+                        self.make_variable_with_value(target_variable, &stdlib_return)?;
+                    }
+                }
+            },
+
+            StructDeclaration { name, fields } =>
+            {
+                // Fields may only be of structs declared beforehand, so a struct can never contain itself
+                for (_, variable_type) in fields {
+                    self.check_type_exists(variable_type)?;
+                }
+
+                if self.innermost_frame().structs.insert(name.clone(), fields.clone()).is_some() {
+                    return fail(ErrorKind::Name, "struct already declared".to_string())
+                }
+            },
+
+            EnumDeclaration { name, variants } =>
+            {
+                // Variants are constructed by name alone, so they can't be shared with anything else
+                for (variant, fields) in variants
+                {
+                    if self.find_variant(variant).is_some() || self.find_struct(variant).is_some() {
+                        return fail(ErrorKind::Name, format!("\"{}\" is already declared", variant))
+                    }
+
+                    for (_, variable_type) in fields {
+                        self.check_type_exists(variable_type)?;
+                    }
+                }
+
+                if self.innermost_frame().enums.insert(name.clone(), variants.clone()).is_some() {
+                    return fail(ErrorKind::Name, "enum already declared".to_string())
+                }
+            },
+
+            StructInstanceDeclaration { struct_name, name, value, optional } =>
+            {
+                let struct_type = VariableType::Struct(Struct { name: struct_name.clone(), fields: Vec::new() });
+                self.check_type_exists(&struct_type)?;
+                self.declare_variable(name, &optional_if(*optional, struct_type), value)?;
+            },
+
+            Return { value } =>
+            {
+                // Search for function frame (if any)
+                let mut frame_info = Option::<(usize, Option<String>, bool)>::default();
+                let mut frame_index = Option::<usize>::default();
+
+                self.for_each_frame(|frame, index|
+                {
+                    if frame_info.is_none()
+                    {
+                        if let Frame::Function { caller_line, target_variable, inline, .. } = &frame.frame
+                        {
+                            let _ = frame_info.insert((*caller_line, target_variable.clone(), *inline));
+                            let _ = frame_index.insert(index);
+                        }
+                    }
+                });
+
+                if let (Some((line_number, target_variable, inline)), Some(frame_index)) = (frame_info, frame_index)
+                {
+                    // We can't just pop the current frame off because we may be returning from a function,
+                    // but within an if statement, for example, so instead we need to put potentially more
+                    // than once!
+
+                    // Evaluate returned variable first, before we pop the frame
+                    if target_variable.is_some() || inline
+                    {
+                        let evaluated = self.evaluate_value(value)?;
+
+                        for _ in 0..(self.frames.len()-frame_index) {
+                            self.frames.pop();
+                        }
+
+                        // Back on the line it was called from before anything's written there
+                        self.line = line_number;
+                        if let Some(target_variable) = target_variable {
+                            self.make_variable_with_value(&target_variable, &evaluated)?;
+                        }
+                        else {
+                            self.returned = Some(evaluated);
+                        }
+                    }
+                    else
+                    {
+                        for _ in 0..(self.frames.len()-frame_index) {
+                            self.frames.pop();
+                        }
+
+                        self.line = line_number;
+                    }
+                }
+                else { return fail(ErrorKind::Runtime, "cannot return outside of a function".to_string()) }
+            },
+
+            Done =>
+            {
+                // At the termination of a frame, it's our responsibility to go back to the start (potentially).
+                // In other words, this is the instruction that'll contain the logic for loops.
+
+                if self.frames.is_empty() {
+                    return fail(ErrorKind::Runtime, "no appropriate frame".to_string())
+                }
+
+                match self.innermost_frame().frame.clone()
+                {
+                    Frame::ForLoop { variable, start_line, end_value } =>
+                    {
+                        let next = self.get_variable(&variable)?.combine(&one, isize::checked_add)?;
+                        if next.compare(&self.evaluate_value(&end_value)?)? != Ordering::Less
+                        {
+                            // End of loop reached
+                            self.frames.pop();
+                        }
+                        else
+                        {
+                            // Loop back, but start with (essentially) a new frame
+                            self.innermost_frame().clear();
+                            self.innermost_frame().variables.insert(variable.clone(), next.clone());
+                            self.tell_hook(|hook, state| hook.written(state, &variable, &next));
+                            self.line = start_line;
+                        }
+                    },
+
+                    Frame::ForEachLoop { key, value, items, index, start_line } =>
+                    {
+                        if index + 1 >= items.len()
+                        {
+                            // End of loop reached
+                            self.frames.pop();
+                        }
+                        else
+                        {
+                            // Loop back with a new frame, just like for loops
+                            self.innermost_frame().clear();
+                            self.innermost_frame().frame = Frame::ForEachLoop { key, value, items, index: index + 1, start_line };
+                            self.make_loop_variables(index + 1)?;
+                            self.line = start_line;
+                        }
+                    },
+
+                    Frame::Function { caller_line, target_variable, .. } =>
+                    {
+                        self.frames.pop();
+
+                        if target_variable.is_some()
+                        {
+                            // No value was returned, so raise error
+                            return fail(ErrorKind::Runtime, "function did not return valid value".to_string())
+                        }
+
+                        // Whatever called it inline decides whether it needed a value
+                        self.line = caller_line;
+                    },
+
+                    Frame::IfStatement | Frame::Try { .. } | Frame::Catch | Frame::Test => {
+                        self.frames.pop();
+                    },
+
+                    Frame::MatchArm { end_line } =>
+                    {
+                        self.frames.pop();
+                        self.line = end_line;
+                    },
+
+                    Frame::Root => {
+                        return fail(ErrorKind::Runtime, "attempt to terminate root frame".to_string())
+                    }
+                }
+            },
+
+            IntDeclaration { name, value, optional } =>
+            {
+                self.declare_variable(name, &optional_if(*optional, VariableType::Integer(0)), value)?;
+            },
+
+            BoolDeclaration { name, value, optional } =>
+            {
+                self.declare_variable(name, &optional_if(*optional, VariableType::Boolean(false)), value)?;
+            },
+
+            StringDeclaration { name, value, optional } =>
+            {
+                self.declare_variable(name, &optional_if(*optional, VariableType::Str(String::new())), value)?;
+            },
+
+            ArrayDeclaration { name, element_type, value } =>
+            {
+                // Evaluate first, before the array is created, to prevent stuff like "array foo = foo"
+                let elements = match value.as_ref().map(|v| self.evaluate_value(v)).transpose()?
+                {
+                    Some(Variable { variable_type: VariableType::Array(array) }) => array.elements,
+                    Some(_) => return fail(ErrorKind::Type, "arrays can only be initialised with other arrays".to_string()),
+                    None => Vec::new()
+                };
+
+                if self.is_numeric(name) || name.contains('[') || name.contains(']') || name.contains('.') {
+                    return fail(ErrorKind::Name, "invalid variable name".to_string())
+                }
+
+                let array = Array::new(element_type.clone(), elements)?;
+                if self.innermost_frame().arrays.insert(name.clone(), array).is_some()
+                {
+                    // Array of same name already existed
+                    return fail(ErrorKind::Name, "array already exists with specified name".to_string())
+                }
+            },
+
+            MapDeclaration { name, value_type, value } =>
+            {
+                // Evaluate first, before the map is created, to prevent stuff like "map foo = foo"
+                let mut map = Map::new(value_type.clone());
+                match value.as_ref().map(|v| self.evaluate_value(v)).transpose()?
+                {
+                    Some(Variable { variable_type: VariableType::Map(other) }) =>
+                    {
+                        for (key, value) in other.entries() {
+                            map.insert(key.clone(), value)?;
+                        }
+                    },
+                    Some(_) => return fail(ErrorKind::Type, "maps can only be initialised with other maps".to_string()),
+                    None => {}
+                }
+
+                if self.is_numeric(name) || name.contains('[') || name.contains(']') || name.contains('.') {
+                    return fail(ErrorKind::Name, "invalid variable name".to_string())
+                }
+
+                if self.innermost_frame().maps.insert(name.clone(), map).is_some() {
+                    return fail(ErrorKind::Name, "map already exists with specified name".to_string())
+                }
+            },
+
+            ConstDeclaration { name, .. } =>
+            {
+                let value = self.constants[&self.line].clone();
+                self.make_variable_with_value(name, &value)?;
+                self.innermost_frame().constants.insert(name.clone());
+            },
+
+            LetDeclaration { name, value } =>
+            {
+                // Evaluate first, before the variable is created, to prevent stuff like "let foo = foo"
+                let evaluated = self.evaluate_value(value)?;
+                self.make_variable_with_value(name, &evaluated)?;
+            },
+
+            Assignment { name, value } => { self.set_variable(name, value)?; }
+
+            NoOp => {},
+        }
+
+        self.line += 1;
+        Ok(())
+    }
+
+    pub fn print_variables(&self)
+    {
+        for i in 0..self.frames.len()
+        {
+            for variable in &self.frames[i].variables
+            {
+                for _ in 0..i { print!("    "); }
+                println!("{}: {:?}", variable.0, variable.1.variable_type);
+            }
+
+            for (name, array) in &self.frames[i].arrays
+            {
+                for _ in 0..i { print!("    "); }
+                println!("{}: {:#?}", name, array);
+            }
+
+            for (name, map) in &self.frames[i].maps
+            {
+                for _ in 0..i { print!("    "); }
+                println!("{}: {:#?}", name, map);
+            }
+        }
+    }
+
+    // The functions being run, innermost first, along with the current values of their arguments
+    fn stack_trace(&self) -> Vec<Call>
+    {
+        let mut trace = Vec::<Call>::new();
+
+        for frame in self.frames.iter().rev()
+        {
+            let Frame::Function { first_line, caller_line, .. } = &frame.frame else { continue };
+            let FunctionDeclaration { name, arguments, .. } = &self.instructions[*first_line] else { continue };
+
+            let arguments = arguments.iter().map(|(argument, _)|
+            {
+                let value = frame_value(frame, argument).map(|value| value.printed_string());
+                (argument.clone(), value.unwrap_or("?".to_string()))
+            }).collect();
+
+            trace.push(Call { function: name.clone(), arguments, line: *caller_line });
+        }
+
+        trace
+    }
+
+    fn is_numeric(&self, value: &str) -> bool
+    {
+        !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+    }
+
+    fn innermost_frame(&mut self) -> &mut FrameContext
+    {
+        let index = self.frames.len()-1;
+        &mut self.frames[index]
+    }
+
+    fn add_frame(&mut self, frame: Frame)
+    {
+        self.frames.push(FrameContext {
+            frame,
+            variables: HashMap::<String, Variable>::new(),
+            functions: HashMap::<String, FunctionInfo>::new(),
+            arrays: HashMap::<String, Array>::new(),
+            maps: HashMap::<String, Map>::new(),
+            structs: HashMap::<String, StructInfo>::new(),
+            enums: HashMap::<String, EnumInfo>::new(),
+            optionals: HashMap::<String, VariableType>::new(),
+            constants: HashSet::<String>::new()
+        });
+    }
+
+    fn for_each_frame<F: FnMut(&FrameContext, usize)>(&self, mut f: F)
+    {
+        // Go from inner-most frame to root (i.e. reversed)
+        for i in 1..=self.frames.len()
+        {
+            let index = self.frames.len() - i;
+            f(&self.frames[index], index);
+        }
+    }
+
+    // Gives the (key, value) pairs of a collection to loop over. A lone loop variable takes each element of an
+    // array or string, but each key of a map.
+    fn items_of(&self, collection: &Variable, has_value: bool) -> Result<Vec<(Variable, Variable)>, RuntimeError>
+    {
+        let index = |i: usize| Variable { variable_type: VariableType::Integer(i as isize) };
+
+        let items: Vec<(Variable, Variable)> = match &collection.variable_type
+        {
+            VariableType::Array(array) => array.elements.iter().enumerate()
+                .map(|(i, element)| (index(i), element.clone())).collect(),
+
+            VariableType::Str(string) => string.chars().enumerate()
+                .map(|(i, c)| (index(i), Variable { variable_type: VariableType::Str(c.to_string()) })).collect(),
+
+            VariableType::Map(map) => map.entries().iter()
+                .map(|(key, value)| (key.to_variable(), value.clone())).collect(),
+
+            _ => return fail(ErrorKind::Type, format!("cannot loop over {}", collection.type_name()))
+        };
+
+        if has_value || matches!(collection.variable_type, VariableType::Map(_)) {
+            Ok(items)
+        }
+        else {
+            Ok(items.into_iter().map(|(_, value)| (value.clone(), value)).collect())
+        }
+    }
+
+    fn make_loop_variables(&mut self, index: usize) -> Result<(), RuntimeError>
+    {
+        if let Frame::ForEachLoop { key, value, items, .. } = self.innermost_frame().frame.clone()
+        {
+            let (key_item, value_item) = &items[index];
+            self.make_variable_with_value(&key, key_item)?;
+            if let Some(value) = value {
+                self.make_variable_with_value(&value, value_item)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn evaluate_value(&mut self, value: &str) -> Result<Variable, RuntimeError>
+    {
+        let result = self.evaluate_expression(value)?;
+        if self.hook.is_some() && !is_literal(value) {
+            self.tell_hook(|hook, state| hook.evaluated(state, value, &result));
+        }
+        Ok(result)
+    }
+
+    fn evaluate_expression(&mut self, value: &str) -> Result<Variable, RuntimeError>
+    {
+        // A value may simply be something like "3" or "my_variable_name", but may also contain operators like "+" or "-".
+        // To this end, parse each individual "actual value" (inner value) and combine them with any operators to form an
+        // expression of sorts that can be evaluated separately, containing only numbers and operators. Of course, for values
+        // not containing any operators, this can be skipped. Operators inside strings or indices are left for the inner
+        // value to deal with.
+
+        if !value_contains_operator(value) { return self.evaluate_inner_value(value) }
+
+        let chars = value.chars().collect::<Vec<char>>();
+        let mut expression = Vec::<OperatorExpression>::new();
+        let mut word_start = 0;
+
+        // March along, cutting out each accumulated "word" when an operator is found (or the string ends)
+        let operators = top_level_operators(value).map(|(position, operator)| (position, Some(operator)));
+        for (position, operator) in operators.chain(std::iter::once((chars.len(), None)))
+        {
+            let word = chars[word_start..position].iter().collect::<String>();
+
+            expression.push(OperatorExpression::Variable(
+                self.evaluate_operand(&word, operator == Some(TokenType::DefaultTo))?
+            ));
+
+            if let Some(operator) = operator
+            {
+                word_start = position + operator_symbol(&operator).chars().count();
+                expression.push(OperatorExpression::Operator(operator));
+            }
+        }
+
+        evaluate_operator_expression(&expression)
+    }
+
+    // Variables and fields that may be none have to be given a default before anything else can be done with them.
+    // A field can only be told apart once what it's taken from has been evaluated, which is then only done once.
+    fn evaluate_operand(&mut self, word: &str, defaulted: bool) -> Result<Variable, RuntimeError>
+    {
+        let word = word.trim();
+        let may_be_none = || fail(ErrorKind::Type, format!(
+            "\"{}\" may be none, so must be given a default (e.g. \"{} ?? 0\") before use in an expression", word, word
+        ));
+
+        if defaulted { return self.evaluate_inner_value(word) }
+        if self.find_optional(word).is_some() { return may_be_none() }
+
+        let Some(Segment::Field(field)) = split_accessors(word).and_then(|(_, segments)| segments.last().copied()) else {
+            return self.evaluate_inner_value(word)
+        };
+
+        let receiver = self.evaluate_inner_value(&word[..word.len() - field.len() - 1])?;
+        if let VariableType::Struct(instance) = &receiver.variable_type
+        {
+            let declared = self.field_types(field).into_iter().find(|(name, _)| *name == instance.name);
+            if let Some((_, VariableType::Optional(_))) = declared {
+                return may_be_none()
+            }
+        }
+
+        Ok(self.access(Cow::Owned(receiver), &[Accessor::Field(field.to_string())])?.into_owned())
+    }
+
+    fn evaluate_inner_value(&mut self, value: &str) -> Result<Variable, RuntimeError>
+    {
+        let value = value.trim();
+
+        // Treat numbers as temporary ints
+        if self.is_numeric(value)
+        {
+            match value.parse()
+            {
+                Ok(number) => Ok(Variable { variable_type: VariableType::Integer(number) }),
+                Err(_) => fail(ErrorKind::Value, "integer literal is too large".to_string())
+            }
+        }
+
+        else if value == "none" {
+            Ok(Variable { variable_type: VariableType::None })
+        }
+
+        else if let Some(boolean) = boolean_literal(value) {
+            Ok(Variable { variable_type: VariableType::Boolean(boolean) })
+        }
+
+        // Strings
+        else if value.len() >= 2 && value.starts_with('\"') && value.ends_with('\"')
+        {
+            Ok(Variable {
+                variable_type: VariableType::Str(value[1..value.len()-1].to_string())
+            })
+        }
+
+        // Array literals (e.g. "[1, 2, 3]"), which are untyped until they're stored somewhere
+        else if value.starts_with('[') && value.ends_with(']')
+        {
+            let inner = &value[1..value.len()-1];
+            let elements = if inner.trim().is_empty() { Vec::new() } else {
+                split_top_level(inner, ',').iter().map(|e| self.evaluate_value(e))
+                    .collect::<Result<Vec<Variable>, RuntimeError>>()?
+            };
+            check_size("array", elements.len(), self.limits.max_size)?;
+
+            Ok(Variable { variable_type: VariableType::Array(Array::untyped(elements)) })
+        }
+
+        // Map literals (e.g. "{"a": 1, "b": 2}"), which are untyped until they're stored somewhere
+        else if value.starts_with('{') && value.ends_with('}')
+        {
+            let inner = &value[1..value.len()-1];
+            let mut map = Map::new(None);
+
+            for entry in split_top_level(inner, ',').into_iter().filter(|e| !e.trim().is_empty())
+            {
+                let [key, value] = split_top_level(entry, ':')[..] else {
+                    return fail(ErrorKind::Value, "map entries must be of the form \"key: value\"".to_string())
+                };
+
+                let key = MapKey::from_variable(&self.evaluate_value(key)?)?;
+                let value = self.evaluate_value(value)?;
+                map.insert(key, &value)?;
+            }
+
+            Ok(Variable { variable_type: VariableType::Map(map) })
+        }
+
+        // Otherwise it must be a variable, array, map or function call, possibly indexed (e.g. "foo[i].bar[j]")
+        else
+        {
+            let Some((root, segments)) = split_accessors(value) else {
+                return fail(ErrorKind::Value, format!("invalid value \"{}\"", value))
+            };
+
+            // Method calls take everything before them as their first argument, such that "foo[0].len()" is
+            // "len(foo[0])", so only what comes after the last one is left to access
+            if let Some(last_method) = segments.iter().rposition(|s| matches!(s, Segment::Method { .. }))
+            {
+                let Segment::Method { name, arguments, receiver_end } = segments[last_method] else { unreachable!() };
+                let mut values = vec![value[..receiver_end].to_string()];
+                values.extend(split_arguments(arguments));
+
+                let returned = self.call_function_inline(name, &values)?;
+                let accessors = self.evaluate_accessors(&segments[last_method+1..])?;
+                return Ok(self.access(Cow::Owned(returned), &accessors)?.into_owned())
+            }
+
+            let accessors = self.evaluate_accessors(&segments)?;
+
+            if let Some((function, arguments)) = split_call(root)
+            {
+                let returned = self.call_function_inline(function, &split_arguments(arguments))?;
+                return Ok(self.access(Cow::Owned(returned), &accessors)?.into_owned())
+            }
+
+            // Arrays are only copied in their entirety if they aren't indexed
+            if let Some(array) = self.get_array(root)
+            {
+                return match accessors.split_first()
+                {
+                    Some((first, rest)) => Ok(self.access(self.access_array(array, first)?, rest)?.into_owned()),
+                    None => Ok(Variable { variable_type: VariableType::Array(array.clone()) })
+                }
+            }
+
+            if let Some(map) = self.get_map(root)
+            {
+                return match accessors.split_first()
+                {
+                    Some((first, rest)) => Ok(self.access(self.access_map(map, first)?, rest)?.into_owned()),
+                    None => Ok(Variable { variable_type: VariableType::Map(map.clone()) })
+                }
+            }
+
+            if let Some(variable) = self.find_variable(root) {
+                return Ok(self.access(Cow::Borrowed(variable), &accessors)?.into_owned())
+            }
+
+            // Variants that don't carry anything can be used by name alone (e.g. "Empty")
+            match self.construct(root, &Vec::new())?
+            {
+                Some(instance) => Ok(self.access(Cow::Owned(instance), &accessors)?.into_owned()),
+                None => fail(ErrorKind::Name, format!("variable \"{}\" does not exist", root))
+            }
+        }
+    }
+
+    fn evaluate_accessors(&mut self, segments: &[Segment]) -> Result<Vec<Accessor>, RuntimeError>
+    {
+        let mut evaluated = Vec::<Accessor>::new();
+
+        for segment in segments
+        {
+            let accessor = match segment
+            {
+                Segment::Index(accessor) => accessor,
+                Segment::Field(field) =>
+                {
+                    evaluated.push(Accessor::Field(field.to_string()));
+                    continue;
+                },
+                Segment::Method { .. } => return fail(ErrorKind::Runtime, "cannot modify the value returned by a method".to_string())
+            };
+
+            let mut evaluate_bound = |bound: &str| {
+                if bound.trim().is_empty() { Ok(None) } else { self.evaluate_value(bound).map(Some) }
+            };
+
+            evaluated.push(match split_top_level(accessor, ':')[..]
+            {
+                [index] => Accessor::Index(self.evaluate_value(index)?),
+                [start, end] => Accessor::Slice(evaluate_bound(start)?, evaluate_bound(end)?),
+                _ => return fail(ErrorKind::Value, "too many colons in slice".to_string())
+            });
+        }
+
+        Ok(evaluated)
+    }
+
+    // Applies each accessor in turn, only copying what's needed
+    fn access<'a>(&self, value: Cow<'a, Variable>, accessors: &[Accessor]) -> Result<Cow<'a, Variable>, RuntimeError>
+    {
+        let mut value = value;
+
+        for accessor in accessors
+        {
+            value = match value
+            {
+                Cow::Borrowed(value) => self.access_value(value, accessor)?,
+                Cow::Owned(value) => Cow::Owned(self.access_value(&value, accessor)?.into_owned())
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn access_value<'a>(&self, value: &'a Variable, accessor: &Accessor) -> Result<Cow<'a, Variable>, RuntimeError>
+    {
+        match &value.variable_type
+        {
+            VariableType::Array(array) => self.access_array(array, accessor),
+            VariableType::Map(map) => self.access_map(map, accessor),
+            VariableType::Struct(instance) => field_of(instance, accessor).map(Cow::Borrowed),
+
+            _ if matches!(accessor, Accessor::Field(_)) => Err(no_field_error(value.type_name(), accessor)),
+
+            // Strings are indexed by character, and are read-only
+            VariableType::Str(string) =>
+            {
+                let chars = string.chars().collect::<Vec<char>>();
+                let substring = match accessor
+                {
+                    Accessor::Index(index) => chars[index_within(index, chars.len(), "string")?].to_string(),
+
+                    Accessor::Slice(start, end) =>
+                    {
+                        let (start, end) = self.slice_bounds(start, end, chars.len())?;
+                        chars[start..end].iter().collect()
+                    },
+
+                    Accessor::Field(_) => unreachable!()
+                };
+
+                Ok(Cow::Owned(Variable { variable_type: VariableType::Str(substring) }))
+            },
+
+            _ => fail(ErrorKind::Type, format!("cannot index into {}", value.type_name()))
+        }
+    }
+
+    fn access_map<'a>(&self, map: &'a Map, accessor: &Accessor) -> Result<Cow<'a, Variable>, RuntimeError>
+    {
+        let key = map_key(accessor)?;
+
+        match map.get(&key)
+        {
+            Some(value) => Ok(Cow::Borrowed(value)),
+            None => fail(ErrorKind::Key, format!("key \"{}\" not found in map", key.to_variable().printed_string()))
+        }
+    }
+
+    fn access_array<'a>(&self, array: &'a Array, accessor: &Accessor) -> Result<Cow<'a, Variable>, RuntimeError>
+    {
+        match accessor
+        {
+            Accessor::Index(index) => Ok(Cow::Borrowed(&array.elements[index_within(index, array.elements.len(), "array")?])),
+
+            Accessor::Slice(start, end) =>
+            {
+                let (start, end) = self.slice_bounds(start, end, array.elements.len())?;
+                let mut slice = Array::new(array.element_type.as_deref().cloned(), Vec::new())?;
+                slice.elements = array.elements[start..end].to_vec();
+                Ok(Cow::Owned(Variable { variable_type: VariableType::Array(slice) }))
+            },
+
+            Accessor::Field(_) => Err(no_field_error("array".to_string(), accessor))
+        }
+    }
+
+    // Both bounds are optional, such that "foo[:3]" and "foo[1:]" are valid
+    fn slice_bounds(&self, start: &Option<Variable>, end: &Option<Variable>, length: usize) -> Result<(usize, usize), RuntimeError>
+    {
+        let bound = |bound: &Option<Variable>, default: usize| match bound
+        {
+            // Bounds may be equal to the length, as they're exclusive
+            Some(bound) if bound.as_integer()? == length as isize => Ok(length),
+            Some(bound) => index_within(bound, length, "slice"),
+            None => Ok(default)
+        };
+
+        let (start, end) = (bound(start, 0)?, bound(end, length)?);
+        if start > end {
+            return fail(ErrorKind::Value, "slice start cannot be after its end".to_string())
+        }
+
+        Ok((start, end))
+    }
+
+    fn find_variable(&self, name: &str) -> Option<&Variable>
+    {
+        self.frames.iter().rev().find_map(|frame| frame.variables.get(name))
+    }
+
+    fn get_array(&self, name: &str) -> Option<&Array>
+    {
+        self.frames.iter().rev().find_map(|frame| frame.arrays.get(name))
+    }
+
+    fn get_map(&self, name: &str) -> Option<&Map>
+    {
+        self.frames.iter().rev().find_map(|frame| frame.maps.get(name))
+    }
+
+    // Finds the collection a value refers to, be it a whole array or map (e.g. "foo") or one nested inside another
+    // (e.g. "foo[0]" or "foo.bar"), so that the standard library can modify it in-place
+    fn get_receiver(&mut self, name: &str) -> Option<Receiver<'_>>
+    {
+        let (root, segments) = split_accessors(name)?;
+
+        if segments.is_empty()
+        {
+            return self.frames.iter_mut().rev().find_map(|frame| {
+                match frame.arrays.get_mut(root) {
+                    Some(array) => Some(Receiver::Array(array)),
+                    None => frame.maps.get_mut(root).map(Receiver::Map)
+                }
+            })
+        }
+
+        // Only something that could be assigned to can be modified, which excludes slices and method calls
+        let is_place = segments.iter().all(|s| match s {
+            Segment::Index(index) => split_top_level(index, ':').len() == 1,
+            Segment::Field(_) => true,
+            Segment::Method { .. } => false
+        }) && (self.get_array(root).is_some() || self.get_map(root).is_some() || self.find_variable(root).is_some());
+        if !is_place { return None }
+
+        match &mut self.get_variable(name).ok()?.variable_type
+        {
+            VariableType::Array(array) => Some(Receiver::Array(array)),
+            VariableType::Map(map) => Some(Receiver::Map(map)),
+            _ => None
+        }
+    }
+
+    // Finds the variable a (possibly indexed) name refers to
+    fn get_variable(&mut self, name: &str) -> Result<&mut Variable, RuntimeError>
+    {
+        let Some((root, segments)) = split_accessors(name) else {
+            return fail(ErrorKind::Name, format!("invalid variable name \"{}\"", name))
+        };
+
+        // Only evaluate indices once, before borrowing the frame they'll be used on
+        let accessors = self.evaluate_accessors(&segments)?;
+
+        for i in 1..=self.frames.len()
+        {
+            let index = self.frames.len() - i;
+
+            // Arrays, which can only be modified element by element
+            if self.frames[index].arrays.contains_key(root) && !accessors.is_empty()
+            {
+                let array = self.frames[index].arrays.get_mut(root).unwrap();
+                return index_array_mut(array, &accessors[0])
+                    .and_then(|element| access_mut(element, &accessors[1..]))
+            }
+
+            // Maps, which like arrays can only be modified entry by entry
+            else if self.frames[index].maps.contains_key(root) && !accessors.is_empty()
+            {
+                let map = self.frames[index].maps.get_mut(root).unwrap();
+                return index_map_mut(map, &accessors[0])
+                    .and_then(|value| access_mut(value, &accessors[1..]))
+            }
+
+            // Normal variables
+            else if self.frames[index].variables.contains_key(root)
+            {
+                let variable = self.frames[index].variables.get_mut(root).unwrap();
+                return access_mut(variable, &accessors)
+            }
+        }
+
+        fail(ErrorKind::Name, format!("variable \"{}\" does not exist", name))
+    }
+
+    fn set_variable(&mut self, name: &str, value: &str) -> Result<(), RuntimeError>
+    {
+        let evaluated = self.evaluate_value(value)?;
+        self.assign(name, &evaluated)?;
+        self.tell_hook(|hook, state| hook.written(state, name, &evaluated));
+        Ok(())
+    }
+
+    fn assign(&mut self, name: &str, evaluated: &Variable) -> Result<(), RuntimeError>
+    {
+        let Some((root, segments)) = split_accessors(name) else {
+            return fail(ErrorKind::Name, format!("invalid variable name \"{}\"", name))
+        };
+        let accessors = self.evaluate_accessors(&segments)?;
+
+        if self.is_constant(root) {
+            return fail(ErrorKind::Runtime, format!("cannot assign to constant \"{}\"", root))
+        }
+
+        // Array elements and map values take on the collection's type (or anything at all if it doesn't have one),
+        // whereas struct fields keep their own. Maps also gain any keys that are assigned to.
+        if let Some((last, parents)) = accessors.split_last()
+        {
+            let field_types = match last
+            {
+                Accessor::Field(field) => self.field_types(field),
+                _ => Vec::new()
+            };
+
+            for frame in self.frames.iter_mut().rev()
+            {
+                return if let Some(array) = frame.arrays.get_mut(root)
+                {
+                    match parents.split_first()
+                    {
+                        Some((first, rest)) => index_array_mut(array, first)
+                            .and_then(|element| access_mut(element, rest))
+                            .and_then(|container| assign_within(container, last, evaluated, &field_types)),
+                        None => assign_to_array(array, last, evaluated)
+                    }
+                }
+                else if let Some(map) = frame.maps.get_mut(root)
+                {
+                    match parents.split_first()
+                    {
+                        Some((first, rest)) => index_map_mut(map, first)
+                            .and_then(|value| access_mut(value, rest))
+                            .and_then(|container| assign_within(container, last, evaluated, &field_types)),
+                        None => assign_to_map(map, last, evaluated)
+                    }
+                }
+                else if let Some(variable) = frame.variables.get_mut(root)
+                {
+                    access_mut(variable, parents)
+                        .and_then(|container| assign_within(container, last, evaluated, &field_types))
+                }
+                else { continue };
+            }
+
+            return fail(ErrorKind::Name, format!("variable \"{}\" does not exist", root))
+        }
+
+        // Whole arrays and maps may be replaced by another (e.g. "foo = [1, 2, 3]")
+        if let Some(array) = self.get_array(name)
+        {
+            let replacement = match &evaluated.variable_type
+            {
+                VariableType::Array(other) => Array::new(array.element_type.as_deref().cloned(), other.elements.clone())?,
+                _ => return fail(ErrorKind::Type, "arrays can only be assigned other arrays".to_string())
+            };
+
+            for frame in self.frames.iter_mut().rev()
+            {
+                if let Some(array) = frame.arrays.get_mut(name)
+                {
+                    *array = replacement;
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(map) = self.get_map(name)
+        {
+            let mut replacement = Map::new(map.value_type.as_deref().cloned());
+            match &evaluated.variable_type
+            {
+                VariableType::Map(other) => for (key, value) in other.entries() {
+                    replacement.insert(key.clone(), value)?;
+                },
+                _ => return fail(ErrorKind::Type, "maps can only be assigned other maps".to_string())
+            }
+
+            for frame in self.frames.iter_mut().rev()
+            {
+                if let Some(map) = frame.maps.get_mut(name)
+                {
+                    *map = replacement;
+                    return Ok(());
+                }
+            }
+        }
+
+        // Variables that may be none keep to their declared type when they aren't
+        match self.find_optional(name)
+        {
+            Some(variable_type) =>
+            {
+                let converted = value_of_type(&variable_type, evaluated)?;
+                *self.get_variable(name)? = converted;
+                Ok(())
+            },
+            None => self.get_variable(name)?.set(evaluated)
+        }
+    }
+
+    // Gives the declared type of a field for each struct that has it, as a field may be optional
+    fn field_types(&self, field: &str) -> Vec<(String, VariableType)>
+    {
+        let mut field_types = Vec::<(String, VariableType)>::new();
+
+        for frame in self.frames.iter().rev()
+        {
+            for (name, fields) in &frame.structs
+            {
+                let declared = fields.iter().find(|(f, _)| f == field);
+                if let (Some((_, variable_type)), false) = (declared, field_types.iter().any(|(n, _)| n == name)) {
+                    field_types.push((name.clone(), variable_type.clone()));
+                }
+            }
+        }
+
+        field_types
+    }
+
+    fn is_constant(&self, name: &str) -> bool
+    {
+        let frame = self.frames.iter().rev().find(|frame| frame.variables.contains_key(name));
+        frame.is_some_and(|frame| frame.constants.contains(name))
+    }
+
+    fn find_optional(&self, name: &str) -> Option<VariableType>
+    {
+        let frame = self.frames.iter().rev().find(|frame| frame.variables.contains_key(name))?;
+        frame.optionals.get(name).cloned()
+    }
+
+    fn find_function(&self, name: &str) -> Option<FunctionInfo>
+    {
+        let mut found_function = Option::<FunctionInfo>::default();
+        self.for_each_frame(|frame, _| {
+            if found_function.is_none() && frame.functions.contains_key(name) {
+                let _ = found_function.insert(frame.functions.get(name).unwrap().clone());
+            }
+        });
+        found_function
+    }
+
+    fn enter_function(&mut self, function: FunctionInfo, values: &[String], target_variable: Option<String>, inline: bool)
+        -> Result<(), RuntimeError>
+    {
+        // Be careful to evaluate the values early, before we make the new frame, as if they have the same name as
+        // the arguments, we'll accidentally use the new ones in any evaluating, as may happen in recursive functions.
+        let evaluated = values.iter().map(|v| self.evaluate_value(v)).collect::<Result<Vec<Variable>, RuntimeError>>()?;
+        self.enter_function_with_values(function, evaluated, target_variable, inline)
+    }
+
+    fn enter_function_with_values(&mut self, function: FunctionInfo, evaluated: Vec<Variable>, target_variable: Option<String>,
+        inline: bool) -> Result<(), RuntimeError>
+    {
+        let (first_line, desired_args) = function;
+
+        // Check argument lengths match
+        if desired_args.len() != evaluated.len() {
+            return fail(ErrorKind::Argument, "invalid number of function arguments".to_string())
+        }
+
+        if let Some(max_call_depth) = self.limits.max_call_depth
+        {
+            if self.call_depth() >= max_call_depth {
+                return fail(ErrorKind::DepthLimit, format!("exceeded the maximum call depth of {}", max_call_depth))
+            }
+        }
+
+        // Calls from within expressions are run by recursing, so may run out of stack first
+        if let (Some(max_stack), true) = (self.limits.max_stack, inline)
+        {
+            if stack_position().abs_diff(self.stack_base) > max_stack {
+                return fail(ErrorKind::DepthLimit, format!("exceeded the maximum stack size of {} bytes", max_stack))
+            }
+        }
+
+        self.add_frame(Frame::Function { first_line, caller_line: self.line, target_variable, inline });
+
+        // Pass arguments, which are written to on the line the function is declared on
+        self.line = first_line;
+        for i in 0..desired_args.len() {
+            self.make_typed_variable(&desired_args[i].0, &desired_args[i].1, &evaluated[i])?;
+        }
+        self.calls += 1;
+
+        Ok(())
+    }
+
+    // Runs a function to completion from within an expression, rather than jumping to it like a normal call
+    fn call_function_inline(&mut self, function: &str, values: &[String]) -> Result<Variable, RuntimeError>
+    {
+        let function = function.to_string();
+
+        let Some(function_info) = self.find_function(&function) else
+        {
+            if let Some(instance) = self.construct(&function, values)? {
+                return Ok(instance)
+            }
+
+            return match self.call_stdlib_function(&function, values)?
+            {
+                Some(returned) => Ok(returned),
+                None => fail(ErrorKind::Runtime, format!("function \"{}\" did not return a value", function))
+            }
+        };
+
+        let depth = self.frames.len();
+        let line = self.line;
+
+        self.enter_function(function_info, values, None, true)?;
+        self.line += 1;
+        self.run(depth)?;
+
+        self.line = line;
+        self.tell_hook(|hook, state| hook.returned(state));
+        match self.returned.take()
+        {
+            Some(returned) => Ok(returned),
+            None => fail(ErrorKind::Runtime, "function did not return valid value".to_string())
+        }
+    }
+
+    fn find_struct(&self, name: &str) -> Option<StructInfo>
+    {
+        self.frames.iter().rev().find_map(|frame| frame.structs.get(name)).cloned()
+    }
+
+    // Finds the enum a variant belongs to, along with the variant's fields
+    fn find_variant(&self, variant: &str) -> Option<(String, Vec<(String, VariableType)>)>
+    {
+        self.frames.iter().rev().find_map(|frame| {
+            frame.enums.iter().find_map(|(name, variants)| {
+                variants.iter().find(|(v, _)| v == variant).map(|(_, fields)| (name.clone(), fields.clone()))
+            })
+        })
+    }
+
+    // Builds a struct or enum variant (if one exists with the given name) from the values of its fields, given in
+    // the order they were declared
+    fn construct(&mut self, name: &str, values: &[String]) -> Result<Option<Variable>, RuntimeError>
+    {
+        let (enum_name, fields) = match self.find_struct(name)
+        {
+            Some(fields) => (None, fields),
+            None => match self.find_variant(name)
+            {
+                Some((enum_name, fields)) => (Some(enum_name), fields),
+                None => return Ok(None)
+            }
+        };
+
+        if fields.len() != values.len() {
+            return fail(ErrorKind::Argument, format!("{} expects {} field(s) but was given {}", name, fields.len(), values.len()))
+        }
+
+        let mut constructed = Vec::<(String, Variable)>::new();
+        for i in 0..fields.len()
+        {
+            let value = self.evaluate_value(&values[i])?;
+            constructed.push((fields[i].0.clone(), value_of_type(&fields[i].1, &value)?));
+        }
+
+        let variable_type = match enum_name
+        {
+            Some(enum_name) => VariableType::Enum(Enum { name: enum_name, variant: name.to_string(), fields: constructed }),
+            None => VariableType::Struct(Struct { name: name.to_string(), fields: constructed })
+        };
+        Ok(Some(Variable { variable_type }))
+    }
+
+    // Structs and enums are named the same way in types (see token_to_variable_type)
+    fn check_type_exists(&self, variable_type: &VariableType) -> Result<(), RuntimeError>
+    {
+        if let VariableType::Optional(inner) = variable_type {
+            return self.check_type_exists(inner)
+        }
+
+        if let VariableType::Struct(instance) = variable_type
+        {
+            let is_enum = self.frames.iter().any(|frame| frame.enums.contains_key(&instance.name));
+            if self.find_struct(&instance.name).is_none() && !is_enum {
+                return fail(ErrorKind::Name, format!("unknown type \"{}\"", instance.name))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn call_stdlib_function(&mut self, function: &str, values: &[String]) -> Result<Option<Variable>, RuntimeError>
+    {
+        // Built-in functions can be taken away like any native one, though one may then be registered in its place
+        if self.natives.get(function).is_none() && !self.natives.has_builtin(function) {
+            return fail(ErrorKind::Name, "unknown function".to_string())
+        }
+
+        // What assert_error is given is expected to fail, so can only be evaluated by it
+        if function == "assert_error" && self.natives.get(function).is_none() {
+            return self.assert_error(values).map(|_| None)
+        }
+
+        // Evaluate arguments first
+        let arguments = values.iter().map(|v| {
+            self.evaluate_value(v)
+        }).collect::<Result<Vec<Variable>, RuntimeError>>()?;
+
+        // Native functions come first, so that those given by the host can take the place of those built in
+        if let Some(native) = self.natives.get(function) {
+            return native.call(function, &arguments, self.io.as_mut())
+        }
+
+        let max_size = self.limits.max_size;
+
+        // Run function (if any), letting it modify the array or map its first argument names, if any
+        let receiver = match (values.first(), arguments.first()) {
+            (Some(value), Some(Variable { variable_type: VariableType::Array(_) | VariableType::Map(_) })) =>
+                self.get_receiver(value),
+            _ => None
+        };
+
+        match stdlib_function(function, &arguments, receiver, max_size)?
+        {
+            (true, returned) => Ok(returned),
+            (false, _) => fail(ErrorKind::Name, "unknown function".to_string())
+        }
+    }
+
+    // Checks that evaluating something fails, optionally with an error of the given kind or message
+    fn assert_error(&mut self, values: &[String]) -> Result<(), RuntimeError>
+    {
+        if values.is_empty() || values.len() > 2 {
+            return fail(ErrorKind::Argument, "assert_error expects a value and an optional kind or message".to_string())
+        }
+        let expected = values.get(1).map(|value| self.evaluate_value(value)).transpose()?.map(|v| v.printed_string());
+
+        // Functions called along the way are left behind when they fail, just as with try blocks
+        let (depth, line) = (self.frames.len(), self.line);
+        let result = self.evaluate_value(&values[0]);
+        self.frames.truncate(depth);
+        self.line = line;
+
+        match (result, expected)
+        {
+            (Ok(_), _) => fail(ErrorKind::Assertion, format!("expected an error from \"{}\"", values[0])),
+            (Err(error), _) if !error.kind.can_be_caught() => Err(error),
+            (Err(error), Some(expected)) if expected != error.kind.name() && expected != error.message =>
+            {
+                let message = format!("expected the error \"{}\", but got \"{}\" ({})", expected, error.message, error.kind.name());
+                fail(ErrorKind::Assertion, message)
+            },
+            _ => Ok(())
+        }
+    }
+
+    fn declare_variable(&mut self, name: &str, variable_type: &VariableType, value: &str) -> Result<(), RuntimeError>
+    {
+        // Evaluate first, before the variable is created, to prevent stuff like "int foo = foo"
+        let evaluated = self.evaluate_value(value)?;
+        self.make_typed_variable(name, variable_type, &evaluated)
+    }
+
+    // Creates a variable of a declared type, remembering if it may be none
+    fn make_typed_variable(&mut self, name: &str, variable_type: &VariableType, value: &Variable) -> Result<(), RuntimeError>
+    {
+        self.make_variable_with_value(name, &value_of_type(variable_type, value)?)?;
+        if let VariableType::Optional(_) = variable_type {
+            self.innermost_frame().optionals.insert(name.to_string(), variable_type.clone());
+        }
+        Ok(())
+    }
+
+    fn make_variable_with_value(&mut self, name: &str, value: &Variable) -> Result<(), RuntimeError>
+    {
+        // Arrays and maps are created with their contents already in place
+        self.make_variable_of_type(name, &value.variable_type)?;
+        if !matches!(value.variable_type, VariableType::Array(_) | VariableType::Map(_)) {
+            self.get_variable(name)?.set(value)?;
+        }
+        self.tell_hook(|hook, state| hook.written(state, name, value));
+        Ok(())
+    }
+
+    fn make_variable_of_type(&mut self, name: &str, variable_type: &VariableType) -> Result<(), RuntimeError>
+    {
+        let len = self.frames.len();
+
+        if self.is_numeric(name) ||
+            value_contains_operator(name) ||
+            is_str_valid_type(name) ||
+            is_literal(name) ||
+            name.contains('[') ||
+            name.contains(']') ||
+            name.contains('.')
+        {
+            return fail(ErrorKind::Name, "invalid variable name".to_string())
+        }
+
+        // Arrays and maps live separately to normal variables
+        if let VariableType::Array(array) = variable_type
+        {
+            if self.frames[len-1].arrays.insert(name.to_string(), array.clone()).is_some() {
+                return fail(ErrorKind::Name, "array already exists with specified name".to_string())
+            }
+        }
+
+        else if let VariableType::Map(map) = variable_type
+        {
+            if self.frames[len-1].maps.insert(name.to_string(), map.clone()).is_some() {
+                return fail(ErrorKind::Name, "map already exists with specified name".to_string())
+            }
+        }
+
+        else if !self.frames[len-1].variables.contains_key(name)
+        {
+            self.frames[len-1].variables.insert(name.to_string(), Variable {
+                variable_type: variable_type.clone()
+            });
+        }
+        else {
+            return fail(ErrorKind::Name, "variable already exists".to_string())
+        }
+
+        Ok(())
+    }
+}
+
+// Everything declared in the frames, with the innermost of any sharing a name, by name
+fn variables_in(frames: &[FrameContext]) -> Vec<(String, Variable)>
+{
+    let mut names = frames.iter()
+        .flat_map(|frame| frame.variables.keys().chain(frame.arrays.keys()).chain(frame.maps.keys()))
+        .cloned().collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+
+    names.into_iter().filter_map(|name| {
+        frames.iter().rev().find_map(|frame| frame_value(frame, &name)).map(|value| (name, value))
+    }).collect()
+}
+
+// The value of a variable, array or map declared in the frame
+fn frame_value(frame: &FrameContext, name: &str) -> Option<Variable>
+{
+    match (frame.variables.get(name), frame.arrays.get(name), frame.maps.get(name))
+    {
+        (Some(variable), _, _) => Some(variable.clone()),
+        (_, Some(array), _) => Some(Variable { variable_type: VariableType::Array(array.clone()) }),
+        (_, _, Some(map)) => Some(Variable { variable_type: VariableType::Map(map.clone()) }),
+        _ => None
+    }
+}
+
+// Roughly where the stack is up to, going by the address of something on it
+fn stack_position() -> usize
+{
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// Literals that can stand alone in an expression, leaving aside arrays and maps
+pub fn is_literal(value: &str) -> bool
+{
+    let value = value.trim();
+    let is_string = value.len() >= 2 && value.starts_with('\"') && value.ends_with('\"');
+    let is_number = !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+    is_string || is_number || value == "none" || boolean_literal(value).is_some()
+}
+
+// Splits an expression into the values either side of its operators
+pub fn split_operands(value: &str) -> Vec<String>
+{
+    let chars = value.chars().collect::<Vec<char>>();
+    let mut operands = Vec::<String>::new();
+    let mut operand_start = 0;
+
+    for (position, operator) in top_level_operators(value)
+    {
+        operands.push(chars[operand_start..position].iter().collect());
+        operand_start = position + operator_symbol(&operator).chars().count();
+    }
+
+    operands.push(chars[operand_start..].iter().collect());
+    operands.iter().map(|operand| operand.trim().to_string()).collect()
+}
+
+fn optional_if(optional: bool, variable_type: VariableType) -> VariableType
+{
+    if optional { VariableType::Optional(Box::new(variable_type)) } else { variable_type }
+}
+
+// What a catch block is given, e.g. "Error(message: cannot pop from an empty array, kind: index, line: 4)"
+fn error_value(error: &RuntimeError) -> Variable
+{
+    let line = error.line.map(|line| line as isize + 1).unwrap_or(0);
+    let fields = vec![
+        ("message".to_string(), Variable { variable_type: VariableType::Str(error.message.clone()) }),
+        ("kind".to_string(), Variable { variable_type: VariableType::Str(error.kind.name().to_string()) }),
+        ("line".to_string(), Variable { variable_type: VariableType::Integer(line) })
+    ];
+
+    Variable { variable_type: VariableType::Struct(Struct { name: "Error".to_string(), fields }) }
+}
+
+// Anything can be thrown, becoming the message of the error. Caught errors are thrown again as they were.
+fn thrown_error(value: &Variable) -> RuntimeError
+{
+    if let VariableType::Struct(instance) = &value.variable_type
+    {
+        if let (Some(message), Some(kind), Some(line)) = (instance.get("message"), instance.get("kind"), instance.get("line"))
+        {
+            if instance.name == "Error"
+            {
+                let mut error = RuntimeError::new(ErrorKind::from_name(&kind.printed_string()), message.printed_string());
+                if let Ok(line) = line.as_integer() {
+                    error.line = Some((line - 1).max(0) as usize);
+                }
+                return error
+            }
+        }
+    }
+
+    RuntimeError::new(ErrorKind::Thrown, value.printed_string())
+}
+
+// An index (or slice) into an array or string, or a struct's field, evaluated ahead of time
+enum Accessor
+{
+    Index(Variable),
+    Slice(Option<Variable>, Option<Variable>),
+    Field(String)
+}
+
+// One step taken from a value, as written in the source (e.g. "[i]", ".bar" or ".len()")
+#[derive(Clone, Copy)]
+enum Segment<'a>
+{
+    Index(&'a str),
+    Field(&'a str),
+    Method { name: &'a str, arguments: &'a str, receiver_end: usize } // Ends where the receiver does
+}
+
+// Checks an index is within something (e.g. an "array") of the given length
+fn index_within(index: &Variable, length: usize, within: &str) -> Result<usize, RuntimeError>
+{
+    match index.as_integer()?
+    {
+        index if index < 0 => fail(ErrorKind::Index, format!("{} index {} cannot be negative", within, index)),
+        index if index as usize >= length =>
+            fail(ErrorKind::Index, format!("{} index {} out of range for length {}", within, index, length)),
+        index => Ok(index as usize)
+    }
+}
+
+fn no_field_error(type_name: String, accessor: &Accessor) -> RuntimeError
+{
+    match accessor
+    {
+        Accessor::Field(field) => RuntimeError::new(ErrorKind::Name, format!("{} has no field \"{}\"", type_name, field)),
+        _ => RuntimeError::new(ErrorKind::Type, format!("cannot index into {}", type_name))
+    }
+}
+
+fn field_of<'a>(instance: &'a Struct, accessor: &Accessor) -> Result<&'a Variable, RuntimeError>
+{
+    match accessor
+    {
+        Accessor::Field(field) => instance.get(field).ok_or_else(|| no_field_error(instance.name.clone(), accessor)),
+        _ => Err(no_field_error(instance.name.clone(), accessor))
+    }
+}
+
+fn field_mut<'a>(instance: &'a mut Struct, accessor: &Accessor) -> Result<&'a mut Variable, RuntimeError>
+{
+    let error = no_field_error(instance.name.clone(), accessor);
+    match accessor
+    {
+        Accessor::Field(field) => instance.get_mut(field).ok_or(error),
+        _ => Err(error)
+    }
+}
+
+fn index_array_mut<'a>(array: &'a mut Array, accessor: &Accessor) -> Result<&'a mut Variable, RuntimeError>
+{
+    match accessor
+    {
+        Accessor::Index(index) =>
+        {
+            // Arrays only ever grow through explicit operations, so going past the end is an error
+            let index = index_within(index, array.elements.len(), "array")?;
+            Ok(&mut array.elements[index])
+        },
+        Accessor::Slice(_, _) => fail(ErrorKind::Type, "cannot assign to a slice".to_string()),
+        Accessor::Field(_) => Err(no_field_error("array".to_string(), accessor))
+    }
+}
+
+fn index_map_mut<'a>(map: &'a mut Map, accessor: &Accessor) -> Result<&'a mut Variable, RuntimeError>
+{
+    let key = map_key(accessor)?;
+    let missing = format!("key \"{}\" not found in map", key.to_variable().printed_string());
+    map.get_mut(&key).ok_or(RuntimeError::new(ErrorKind::Key, missing))
+}
+
+fn map_key(accessor: &Accessor) -> Result<MapKey, RuntimeError>
+{
+    match accessor
+    {
+        Accessor::Index(key) => MapKey::from_variable(key),
+        Accessor::Slice(_, _) => fail(ErrorKind::Type, "maps cannot be sliced".to_string()),
+        Accessor::Field(_) => Err(no_field_error("map".to_string(), accessor))
+    }
+}
+
+fn access_mut<'a>(mut value: &'a mut Variable, accessors: &[Accessor]) -> Result<&'a mut Variable, RuntimeError>
+{
+    for accessor in accessors
+    {
+        let type_name = value.type_name();
+        value = match &mut value.variable_type
+        {
+            VariableType::Array(array) => index_array_mut(array, accessor)?,
+            VariableType::Map(map) => index_map_mut(map, accessor)?,
+            VariableType::Struct(instance) => field_mut(instance, accessor)?,
+            VariableType::Str(_) if !matches!(accessor, Accessor::Field(_)) =>
+                return fail(ErrorKind::Type, "strings cannot be modified by index".to_string()),
+            _ => return Err(no_field_error(type_name, accessor))
+        };
+    }
+
+    Ok(value)
+}
+
+// Sets an element, entry or field of something, converting the value to suit it. Fields are converted to the type
+// they were declared with in their struct, if known.
+fn assign_within(container: &mut Variable, accessor: &Accessor, value: &Variable, field_types: &[(String, VariableType)])
+    -> Result<(), RuntimeError>
+{
+    match &mut container.variable_type
+    {
+        VariableType::Array(array) => assign_to_array(array, accessor, value),
+        VariableType::Map(map) => assign_to_map(map, accessor, value),
+        VariableType::Struct(instance) =>
+        {
+            let declared = field_types.iter().find(|(name, _)| *name == instance.name).map(|(_, t)| t.clone());
+            let field = field_mut(instance, accessor)?;
+            match declared
+            {
+                Some(variable_type) => *field = value_of_type(&variable_type, value)?,
+                None => field.set(value)?
+            }
+            Ok(())
+        },
+        _ => access_mut(container, std::slice::from_ref(accessor)).map(|_| ())
+    }
+}
+
+fn assign_to_array(array: &mut Array, accessor: &Accessor, value: &Variable) -> Result<(), RuntimeError>
+{
+    let element = array.element_from(value)?;
+    *index_array_mut(array, accessor)? = element;
+    Ok(())
+}
+
+fn assign_to_map(map: &mut Map, accessor: &Accessor, value: &Variable) -> Result<(), RuntimeError>
+{
+    map.insert(map_key(accessor)?, value)
+}
+
+// Splits something like "foo[i].bar[j - 1]" into "foo" and the steps taken from it: indexing by "i", taking the
+// field "bar", then indexing by "j - 1". The "root" may also be a function call, such as "bar(1 2)[0]", and steps
+// may be method calls, such as ".len()".
+fn split_accessors(value: &str) -> Option<(&str, Vec<Segment<'_>>)>
+{
+    let root_end = top_level_position(value, |c| c == '[' || c == '.').unwrap_or(value.len());
+    let root = &value[..root_end];
+    if root.is_empty() { return None }
+
+    let mut segments = Vec::<Segment>::new();
+    let mut position = root_end;
+    while position < value.len()
+    {
+        let rest = &value[position..];
+
+        // Find the bracket closing this one, which must exist
+        if let Some(inner) = rest.strip_prefix('[')
+        {
+            let end = top_level_position(inner, |c| c == ']')?;
+            segments.push(Segment::Index(&inner[..end]));
+            position += end + 2;
+        }
+
+        else if let Some(inner) = rest.strip_prefix('.')
+        {
+            let name_end = inner.find(['[', '.', '(']).unwrap_or(inner.len());
+            let name = &inner[..name_end];
+            if name.is_empty() { return None }
+
+            match inner[name_end..].strip_prefix('(')
+            {
+                Some(arguments) =>
+                {
+                    let end = top_level_position(arguments, |c| c == ')')?;
+                    segments.push(Segment::Method { name, arguments: &arguments[..end], receiver_end: position });
+                    position += name_end + end + 3;
+                },
+                None =>
+                {
+                    segments.push(Segment::Field(name));
+                    position += name_end + 1;
+                }
+            }
+        }
+
+        else { return None }
+    }
+
+    Some((root, segments))
+}
+
+// Splits something like "foo(1 2)" into "foo" and "1 2"
+pub fn split_call(value: &str) -> Option<(&str, &str)>
+{
+    let arguments_begin = value.find('(')?;
+    if arguments_begin == 0 || !value.ends_with(')') { return None }
+    Some((&value[..arguments_begin], &value[arguments_begin+1..value.len()-1]))
+}
+
+// Splits call arguments on spaces, like the lexer would, keeping operators with what they operate on
+fn split_arguments(arguments: &str) -> Vec<String>
+{
+    let mut split = Vec::<String>::new();
+
+    for argument in split_top_level(arguments, ' ').into_iter().filter(|a| !a.is_empty())
+    {
+        match split.last_mut()
+        {
+            Some(last) if last.ends_with(is_char_operator) || argument.starts_with(is_char_operator) =>
+            {
+                last.push(' ');
+                last.push_str(argument);
+            },
+            _ => split.push(argument.to_string())
+        }
+    }
+
+    split
+}
+
+// Finds the first character matching a predicate that isn't inside a string or brackets
+fn top_level_position(value: &str, is_target: impl Fn(char) -> bool) -> Option<usize>
+{
+    let mut inside_string = false;
+    let mut depth = 0;
+
+    for (i, c) in value.char_indices()
+    {
+        if is_target(c) && !inside_string && depth == 0 { return Some(i) }
+
+        match c
+        {
+            '\"' => inside_string = !inside_string,
+            '[' | '(' | '{' if !inside_string => depth += 1,
+            ']' | ')' | '}' if !inside_string && depth > 0 => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Splits a value at every separator that isn't inside a string or nested brackets, such that
+// "1, [2, 3], \"a, b\"" gives three values and "1:3" gives two
+fn split_top_level(value: &str, separator: char) -> Vec<&str>
+{
+    let mut parts = Vec::<&str>::new();
+    let mut inside_string = false;
+    let mut bracket_depth = 0;
+    let mut part_start = 0;
+
+    for (i, c) in value.char_indices()
+    {
+        match c
+        {
+            '\"' => inside_string = !inside_string,
+            '[' | '(' | '{' if !inside_string => bracket_depth += 1,
+            ']' | ')' | '}' if !inside_string && bracket_depth > 0 => bracket_depth -= 1,
+            _ if c == separator && !inside_string && bracket_depth == 0 =>
+            {
+                parts.push(&value[part_start..i]);
+                part_start = i + c.len_utf8();
+            },
+            _ => {}
+        }
     }
-}
\ No newline at end of file
+
+    parts.push(&value[part_start..]);
+    parts
+}
@@ -0,0 +1,128 @@
+use super::lexer::tokenise_line;
+use super::lexer::tokenise_lines_with_trivia;
+use super::lexer::Token;
+use super::lexer::TokenType;
+use super::operators::operator_symbol;
+use super::operators::top_level_operators;
+use super::parser::FRAME_TOKENS;
+
+/*
+    Lays scripts out the same way: each block indented by four spaces per level, one space between tokens (and around
+    operators), and comments kept where they are. Blank lines are kept too, except at the start and end of the script
+    and where there are several in a row. A line is only changed if it still tokenises to exactly the same thing, so
+    formatting can never change what a script does.
+*/
+
+const INDENT: &str = "    ";
+
+pub fn format_source(lines: &[String]) -> String
+{
+    let mut formatted = Vec::<String>::new();
+    let mut depth = 0usize;
+
+    for line in &tokenise_lines_with_trivia(lines)
+    {
+        if line.tokens.is_empty() && line.comment.is_none()
+        {
+            // Blank lines, of which only one in a row is kept
+            if formatted.last().is_some_and(|last| !last.is_empty()) {
+                formatted.push(String::new());
+            }
+            continue
+        }
+
+        // A "done" closes a block, and a "catch" is level with the "try" it belongs to
+        let first = line.tokens.first().map(|token| &token.token_type);
+        if matches!(first, Some(TokenType::Done) | Some(TokenType::Catch)) {
+            depth = depth.saturating_sub(1);
+        }
+
+        let mut code = join_tokens(&line.tokens);
+        if !same_tokens(&tokenise_line(&code).0, &line.tokens)
+        {
+            // Keep the line as it was written (besides its indentation) rather than risk changing what it means
+            code = line.line().trim().to_string();
+        }
+        else if let Some(comment) = line.comment.as_deref().map(str::trim_end) {
+            code = if code.is_empty() { comment.to_string() } else { format!("{} {}", code, comment) };
+        }
+
+        formatted.push(format!("{}{}", INDENT.repeat(depth), code));
+
+        if first.is_some_and(|token_type| FRAME_TOKENS.contains(token_type) || *token_type == TokenType::Catch) {
+            depth += 1;
+        }
+    }
+
+    while formatted.last().is_some_and(|last| last.is_empty()) {
+        formatted.pop();
+    }
+
+    formatted.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+// Gives the (1-based) number of the first line that formatting would change, if there is one. Where every line stays
+// the same, only how the last one ends (e.g. without a newline) can be what changes.
+pub fn first_unformatted_line(source: &str) -> Option<usize>
+{
+    let lines = source.lines().map(String::from).collect::<Vec<String>>();
+    let formatted = format_source(&lines);
+    if formatted == source { return None }
+
+    let formatted_lines = formatted.lines().collect::<Vec<&str>>();
+    let differs = (0..lines.len()).find(|i| formatted_lines.get(*i) != Some(&lines[*i].as_str()));
+    Some(differs.unwrap_or(lines.len().saturating_sub(1)) + 1)
+}
+
+fn join_tokens(tokens: &[Token]) -> String
+{
+    let mut joined = String::new();
+
+    for i in 0..tokens.len()
+    {
+        // Calls are written "foo(a b)", and colons directly follow what they belong to (e.g. "fn foo: int a")
+        let spaced = i > 0 && !matches!(tokens[i].token_type, TokenType::Colon | TokenType::RightBracket) &&
+            tokens[i-1].token_type != TokenType::LeftBracket &&
+            !(tokens[i].token_type == TokenType::LeftBracket && tokens[i-1].token_type == TokenType::Value);
+        if spaced {
+            joined.push(' ');
+        }
+
+        if tokens[i].token_type == TokenType::Value {
+            joined.push_str(&space_operators(&tokens[i].string));
+        }
+        else {
+            joined.push_str(&tokens[i].string);
+        }
+    }
+
+    joined
+}
+
+// Puts a single space either side of each operator in a value, other than those within strings or brackets
+fn space_operators(value: &str) -> String
+{
+    let chars = value.chars().collect::<Vec<char>>();
+    let mut spaced = String::new();
+    let mut last_end = 0;
+
+    for (position, operator) in top_level_operators(value)
+    {
+        let symbol = operator_symbol(&operator);
+        spaced.push_str(chars[last_end..position].iter().collect::<String>().trim_end());
+        spaced.push_str(&format!(" {} ", symbol));
+        last_end = position + symbol.len();
+        while last_end < chars.len() && chars[last_end] == ' ' {
+            last_end += 1;
+        }
+    }
+
+    spaced.push_str(&chars[last_end..].iter().collect::<String>());
+    spaced
+}
+
+fn same_tokens(left: &[Token], right: &[Token]) -> bool
+{
+    left.len() == right.len() &&
+        (0..left.len()).all(|i| left[i].token_type == right[i].token_type && left[i].string == right[i].string)
+}
@@ -0,0 +1,286 @@
+use super::lexer::tokenise_lines;
+use super::lexer::tokenise_line;
+use super::parser::parse_lines;
+use super::checker::check;
+use super::engine::State;
+use super::engine::Limits;
+use super::streams::BufferIo;
+use super::common::ParseError;
+use super::lsp;
+use super::json::Json;
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/*
+    Runs every script (ending ".ls") in a directory, checking each against what it's expected to do. What it should
+    print comes either from a file of the same name ending ".out", or else from "// expect: ..." comments, one per
+    line printed. Each "// stdin: ..." comment is a line given to input, and an "// expect error: ..." comment is the
+    first line of the error it should end with (e.g. "index out of range - line 4"), after printing what's expected.
+    Any more of them are the lines of the error that follow (without their indentation), such as its stack trace. A
+    "// limit: ..." comment runs the script with one of its limits changed (e.g. "max_instructions 100", or
+    "timeout 0.5" in seconds).
+
+    Language server sessions (ending ".lsp") are run too. Each line is a message sent to the server, and each
+    "// expect: ..." comment is a message it should send back, in the order they're sent.
+*/
+
+struct Expectations
+{
+    output: String,
+    input: String,
+    error: Vec<String>, // Each line of it, if there should be one
+    limits: Limits
+}
+
+// Returns if every script passed
+pub fn run_directory(directory: &str, limits: &Limits) -> bool
+{
+    let entries = match fs::read_dir(directory)
+    {
+        Ok(entries) => entries,
+        Err(error) =>
+        {
+            println!("could not read \"{}\": {}", directory, error);
+            return false
+        }
+    };
+
+    let mut scripts = entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ls" || extension == "lsp"))
+        .collect::<Vec<PathBuf>>();
+    scripts.sort();
+
+    let mut failed = 0;
+    for script in &scripts
+    {
+        let is_session = script.extension().is_some_and(|extension| extension == "lsp");
+        let problems = if is_session { run_session(script) } else { run_script(script, limits) };
+        if problems.is_empty() {
+            println!("PASS {}", script.display());
+        }
+        else
+        {
+            failed += 1;
+            println!("FAIL {}", script.display());
+            for problem in problems {
+                println!("    {}", problem.replace('\n', "\n    "));
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", scripts.len() - failed, failed);
+    failed == 0
+}
+
+// Gives everything the script did differently to what was expected
+fn run_script(script: &Path, limits: &Limits) -> Vec<String>
+{
+    let source = match fs::read_to_string(script)
+    {
+        Ok(source) => source,
+        Err(error) => return vec![format!("could not read script: {}", error)]
+    };
+
+    let expectations = match read_expectations(script, &source, limits)
+    {
+        Ok(expectations) => expectations,
+        Err(error) => return vec![error]
+    };
+
+    let io = BufferIo::new(&expectations.input);
+    let result = run_source(&source, &io, &expectations.limits);
+    let mut problems = Vec::<String>::new();
+
+    let output = io.output();
+    if output != expectations.output {
+        problems.push(format!("output differs from what was expected:\n{}", diff(&expectations.output, &output)));
+    }
+
+    // Only as many lines of the error as are expected are compared, so a long stack trace needn't be given in full.
+    // Comments are trimmed, so neither is the indentation of each line.
+    let expected = if expectations.error.is_empty() { None } else { Some(expectations.error.join("\n")) };
+    let error = result.err().map(|error| {
+        error.lines().take(expectations.error.len().max(1)).map(str::trim).collect::<Vec<&str>>().join("\n")
+    });
+    match (expected, error)
+    {
+        (Some(expected), Some(error)) if expected != error =>
+            problems.push(format!("expected the error \"{}\", but got \"{}\"", expected, error)),
+        (Some(expected), None) => problems.push(format!("expected the error \"{}\", but there wasn't one", expected)),
+        (None, Some(error)) => problems.push(format!("unexpected error \"{}\"", error)),
+        _ => {}
+    }
+
+    problems
+}
+
+fn read_expectations(script: &Path, source: &str, limits: &Limits) -> Result<Expectations, String>
+{
+    let mut expectations = Expectations {
+        output: String::new(), input: String::new(), error: Vec::new(), limits: limits.clone()
+    };
+
+    for line in source.lines()
+    {
+        // Only a comment ending the line counts, not one that just appears inside a string
+        let (_, comment) = tokenise_line(line.trim());
+        let Some(comment) = comment else { continue };
+        let after = |marker: &str| comment.strip_prefix(marker).map(|rest| rest.trim().to_string());
+
+        if let Some(output) = after("// expect:") {
+            expectations.output.push_str(&format!("{}\n", output));
+        }
+        else if let Some(input) = after("// stdin:") {
+            expectations.input.push_str(&format!("{}\n", input));
+        }
+        else if let Some(error) = after("// expect error:") {
+            expectations.error.push(error);
+        }
+        else if let Some(limit) = after("// limit:") {
+            set_limit(&mut expectations.limits, &limit)?;
+        }
+    }
+
+    // Output given separately takes the place of any in the script
+    let output_file = script.with_extension("out");
+    if output_file.exists() {
+        expectations.output = fs::read_to_string(&output_file).map_err(|error| format!("could not read output: {}", error))?;
+    }
+
+    Ok(expectations)
+}
+
+fn set_limit(limits: &mut Limits, limit: &str) -> Result<(), String>
+{
+    let invalid = || format!("invalid limit \"{}\"", limit);
+    let (name, value) = limit.split_once(' ').ok_or_else(invalid)?;
+    let value = value.trim();
+
+    match name
+    {
+        "max_call_depth" => limits.max_call_depth = Some(value.parse().map_err(|_| invalid())?),
+        "max_instructions" => limits.max_instructions = Some(value.parse().map_err(|_| invalid())?),
+        "max_size" => limits.max_size = Some(value.parse().map_err(|_| invalid())?),
+        "max_stack" => limits.max_stack = Some(value.parse().map_err(|_| invalid())?),
+        "timeout" => limits.timeout = Some(Duration::from_secs_f64(value.parse().map_err(|_| invalid())?)),
+        _ => return Err(invalid())
+    }
+    Ok(())
+}
+
+fn run_session(script: &Path) -> Vec<String>
+{
+    let source = match fs::read_to_string(script)
+    {
+        Ok(source) => source,
+        Err(error) => return vec![format!("could not read session: {}", error)]
+    };
+
+    let mut input = Vec::<u8>::new();
+    let mut expected = Vec::<String>::new();
+    for (i, line) in source.lines().map(str::trim).enumerate()
+    {
+        let (message, is_expected) = match line.strip_prefix("// expect:")
+        {
+            Some(message) => (message, true),
+            None if line.is_empty() || line.starts_with("//") => continue,
+            None => (line, false)
+        };
+
+        // Expected messages are compared as they'd be written, so that spacing doesn't matter
+        let message = match Json::parse(message)
+        {
+            Ok(message) => message,
+            Err(error) => return vec![format!("invalid message on line {}: {}", i + 1, error)]
+        };
+        if is_expected {
+            expected.push(message.to_string());
+        }
+        else {
+            lsp::write_message(&mut input, &message).expect("Could not write to memory");
+        }
+    }
+
+    let mut output = Vec::<u8>::new();
+    lsp::serve(&mut Cursor::new(input), &mut output);
+
+    let mut replies = Vec::<String>::new();
+    let mut output = Cursor::new(output);
+    while let Some(reply) = lsp::read_message(&mut output) {
+        replies.push(reply.map(|reply| reply.to_string()).unwrap_or_else(|error| error));
+    }
+
+    if replies == expected { Vec::new() }
+    else {
+        vec![format!("messages differ from what was expected:\n{}", diff(&expected.join("\n"), &replies.join("\n")))]
+    }
+}
+
+// Runs a script the same way as from the command line, giving the error it ended with, if any
+fn run_source(source: &str, io: &BufferIo, limits: &Limits) -> Result<(), String>
+{
+    let lines = source.lines().map(String::from).collect::<Vec<String>>();
+    let instructions = parse_lines(&tokenise_lines(&lines)).map_err(|error| error.to_string())?;
+
+    let problems = check(&instructions);
+    if !problems.is_empty() {
+        return Err(problems.iter().map(ParseError::to_string).collect::<Vec<String>>().join("\n"))
+    }
+
+    let mut state = State::default();
+    state.set_limits(limits.clone());
+    state.set_io(Box::new(io.clone()));
+    state.execute(instructions).map_err(|error| error.to_string())
+}
+
+// Shows the lines that were expected but missing ("-") and those that weren't expected ("+"), in order, by finding the
+// longest run of lines the two have in common
+fn diff(expected: &str, actual: &str) -> String
+{
+    let expected = expected.lines().collect::<Vec<&str>>();
+    let actual = actual.lines().collect::<Vec<&str>>();
+
+    // The length of the longest common run of lines from each pair of positions onwards
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev()
+    {
+        for j in (0..actual.len()).rev()
+        {
+            common[i][j] = if expected[i] == actual[j] { common[i+1][j+1] + 1 }
+                else { common[i+1][j].max(common[i][j+1]) };
+        }
+    }
+
+    let mut lines = Vec::<String>::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len()
+    {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j]
+        {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        }
+        else if j < actual.len() && (i == expected.len() || common[i][j+1] >= common[i+1][j])
+        {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+        else
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        }
+    }
+
+    // Output that only differs in how it ends (e.g. a missing newline) has nothing else to show
+    if lines.iter().all(|line| line.starts_with("  ")) {
+        lines.push("(differs only in how lines end)".to_string());
+    }
+
+    lines.join("\n")
+}
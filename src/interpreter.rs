@@ -0,0 +1,135 @@
+use super::lexer::tokenise_lines;
+use super::lexer::TokenType;
+use super::parser::parse_lines;
+use super::checker::check;
+use super::engine::State;
+use super::engine::Limits;
+use super::engine::Hook;
+use super::natives::Natives;
+use super::streams::Io;
+use super::variables::Variable;
+use super::variables::VariableType;
+use super::common::RuntimeError;
+use super::common::ParseError;
+
+use std::fmt;
+
+/*
+    Runs lukascript from within Rust. Each interpreter keeps everything run on it so far, so that later source can
+    use the functions and variables declared by earlier source, and so that functions can be called and globals read
+    or written in between. Interpreters are entirely independent of one another. Everything run is treated as one
+    long script, so line numbers in errors count from the start of the first source given.
+*/
+#[derive(Default)]
+pub struct Interpreter
+{
+    state: State,
+    lines: Vec<String> // Everything run so far
+}
+
+#[derive(Debug)]
+pub enum Error
+{
+    Parse(Vec<ParseError>), // Found before running anything, so nothing was run
+    Runtime(RuntimeError)
+}
+
+impl Interpreter
+{
+    pub fn new() -> Interpreter
+    {
+        Interpreter::default()
+    }
+
+    pub fn set_limits(&mut self, limits: Limits)
+    {
+        self.state.set_limits(limits);
+    }
+
+    // The native functions scripts run here can call, which can be added to or taken away from
+    pub fn natives(&mut self) -> &mut Natives
+    {
+        self.state.natives()
+    }
+
+    // Where scripts run here read input from and write output to, which is the terminal unless given otherwise
+    pub fn set_io(&mut self, io: Box<dyn Io>)
+    {
+        self.state.set_io(io);
+    }
+
+    // Something told about each line before it runs (e.g. a debugger), or None to stop telling anything
+    pub fn set_hook(&mut self, hook: Option<Box<dyn Hook>>)
+    {
+        self.state.set_hook(hook);
+    }
+
+    // Runs the source, giving back the value of its last line if that's an expression on its own (e.g. "x * 2"), or
+    // none otherwise
+    pub fn eval(&mut self, source: &str) -> Result<Variable, Error>
+    {
+        let first_line = self.lines.len();
+        let mut lines = self.lines.clone();
+        lines.extend(source.lines().map(String::from));
+
+        // An expression isn't an instruction, so is left out of what's run (keeping its line) and worked out after
+        let mut tokens = tokenise_lines(&lines);
+        let last = (first_line..lines.len()).rev().find(|i| !tokens[*i].is_empty());
+        let expression = last.filter(|i| tokens[*i].len() == 1 && tokens[*i][0].token_type == TokenType::Value)
+            .map(|i| (i, tokens[i].remove(0).string));
+
+        if let Some((line, _)) = &expression {
+            lines[*line] = String::new();
+        }
+
+        let instructions = parse_lines(&tokens).map_err(|error| Error::Parse(vec![error]))?;
+        let problems = check(&instructions);
+        if !problems.is_empty() {
+            return Err(Error::Parse(problems))
+        }
+
+        // Anything run is kept even if it fails part way through, as what came before the failure has still happened
+        self.lines = lines;
+        self.state.execute_from(instructions, first_line).map_err(Error::Runtime)?;
+
+        match expression
+        {
+            Some((line, value)) => self.state.evaluate(&value, line).map_err(Error::Runtime),
+            None => Ok(Variable { variable_type: VariableType::None })
+        }
+    }
+
+    // Calls a function declared by anything run so far, giving back what it returns (or none)
+    pub fn call(&mut self, function: &str, arguments: Vec<Variable>) -> Result<Variable, Error>
+    {
+        self.state.call(function, arguments).map_err(Error::Runtime)
+    }
+
+    pub fn global(&self, name: &str) -> Option<Variable>
+    {
+        self.state.global(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Variable) -> Result<(), Error>
+    {
+        self.state.set_global(name, &value).map_err(Error::Runtime)
+    }
+}
+
+impl fmt::Display for Error
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Error::Parse(problems) =>
+            {
+                let problems = problems.iter().map(ParseError::to_string).collect::<Vec<String>>();
+                write!(f, "{}", problems.join("\n"))
+            },
+            Error::Runtime(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,324 @@
+use std::fmt;
+
+// Just enough JSON for the language server, which speaks it over the wire. Objects keep their keys in the order given,
+// so that what's written out is always the same.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json
+{
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json
+{
+    pub fn object(fields: Vec<(&str, Json)>) -> Json
+    {
+        Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json>
+    {
+        match self
+        {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    // Follows a path of keys through nested objects (e.g. ["textDocument", "uri"])
+    pub fn at(&self, path: &[&str]) -> Option<&Json>
+    {
+        path.iter().try_fold(self, |json, key| json.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str>
+    {
+        if let Json::Str(string) = self { Some(string) } else { None }
+    }
+
+    pub fn as_usize(&self) -> Option<usize>
+    {
+        match self
+        {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as usize),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>>
+    {
+        if let Json::Array(array) = self { Some(array) } else { None }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String>
+    {
+        let mut parser = Parser { chars: text.chars().collect(), position: 0 };
+        let json = parser.value()?;
+
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(format!("unexpected \"{}\" after JSON at {}", parser.chars[parser.position], parser.position))
+        }
+        Ok(json)
+    }
+}
+
+impl From<&str> for Json
+{
+    fn from(string: &str) -> Json
+    {
+        Json::Str(string.to_string())
+    }
+}
+
+impl From<String> for Json
+{
+    fn from(string: String) -> Json
+    {
+        Json::Str(string)
+    }
+}
+
+impl From<usize> for Json
+{
+    fn from(number: usize) -> Json
+    {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<bool> for Json
+{
+    fn from(value: bool) -> Json
+    {
+        Json::Bool(value)
+    }
+}
+
+impl From<Vec<Json>> for Json
+{
+    fn from(array: Vec<Json>) -> Json
+    {
+        Json::Array(array)
+    }
+}
+
+struct Parser
+{
+    chars: Vec<char>,
+    position: usize
+}
+
+impl Parser
+{
+    fn skip_whitespace(&mut self)
+    {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String>
+    {
+        self.skip_whitespace();
+        if self.chars.get(self.position) != Some(&c) {
+            return Err(format!("expected \"{}\" at {}", c, self.position))
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Whether the next character (after any whitespace) is the one given, moving past it if so
+    fn next_is(&mut self, c: char) -> bool
+    {
+        self.skip_whitespace();
+        let found = self.chars.get(self.position) == Some(&c);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json, String>
+    {
+        self.skip_whitespace();
+        match self.chars.get(self.position)
+        {
+            Some('{') =>
+            {
+                self.position += 1;
+                let mut fields = Vec::<(String, Json)>::new();
+                if self.next_is('}') {
+                    return Ok(Json::Object(fields))
+                }
+
+                loop
+                {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+
+                    if self.next_is('}') { return Ok(Json::Object(fields)) }
+                    self.expect(',')?;
+                }
+            },
+
+            Some('[') =>
+            {
+                self.position += 1;
+                let mut elements = Vec::<Json>::new();
+                if self.next_is(']') {
+                    return Ok(Json::Array(elements))
+                }
+
+                loop
+                {
+                    elements.push(self.value()?);
+
+                    if self.next_is(']') { return Ok(Json::Array(elements)) }
+                    self.expect(',')?;
+                }
+            },
+
+            Some('\"') => Ok(Json::Str(self.string()?)),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('n') => self.literal("null", Json::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("unexpected \"{}\" at {}", c, self.position)),
+            None => Err("unexpected end of JSON".to_string())
+        }
+    }
+
+    fn literal(&mut self, word: &str, json: Json) -> Result<Json, String>
+    {
+        let end = self.position + word.len();
+        if end > self.chars.len() || self.chars[self.position..end].iter().collect::<String>() != word {
+            return Err(format!("unexpected \"{}\" at {}", self.chars[self.position], self.position))
+        }
+        self.position = end;
+        Ok(json)
+    }
+
+    fn number(&mut self) -> Result<Json, String>
+    {
+        let start = self.position;
+        while self.position < self.chars.len() && (self.chars[self.position].is_ascii_digit() ||
+            matches!(self.chars[self.position], '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.position += 1;
+        }
+
+        let number = self.chars[start..self.position].iter().collect::<String>();
+        number.parse::<f64>().map(Json::Number).map_err(|_| format!("invalid number \"{}\" at {}", number, start))
+    }
+
+    fn string(&mut self) -> Result<String, String>
+    {
+        self.expect('\"')?;
+        let mut string = String::new();
+
+        loop
+        {
+            let Some(c) = self.chars.get(self.position).copied() else { return Err("unterminated string".to_string()) };
+            self.position += 1;
+
+            match c
+            {
+                '\"' => return Ok(string),
+                '\\' =>
+                {
+                    let Some(escaped) = self.chars.get(self.position).copied() else { continue };
+                    self.position += 1;
+                    match escaped
+                    {
+                        'n' => string.push('\n'),
+                        'r' => string.push('\r'),
+                        't' => string.push('\t'),
+                        'b' => string.push('\u{8}'),
+                        'f' => string.push('\u{c}'),
+                        'u' =>
+                        {
+                            let mut code = self.hex()?;
+
+                            // Characters outside the basic plane come as a pair of surrogates
+                            if (0xD800..0xDC00).contains(&code) && self.chars.get(self.position) == Some(&'\\')
+                            {
+                                self.position += 2;
+                                let low = self.hex()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            string.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        },
+                        other => string.push(other)
+                    }
+                },
+                _ => string.push(c)
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String>
+    {
+        let end = (self.position + 4).min(self.chars.len());
+        let digits = self.chars[self.position..end].iter().collect::<String>();
+        self.position = end;
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape \"\\u{}\"", digits))
+    }
+}
+
+impl fmt::Display for Json
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => write!(f, "{}", number), // Whole numbers are written without a fraction
+            Json::Str(string) =>
+            {
+                write!(f, "\"")?;
+                for c in string.chars()
+                {
+                    match c
+                    {
+                        '\"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?
+                    }
+                }
+                write!(f, "\"")
+            },
+            Json::Array(elements) =>
+            {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate()
+                {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) =>
+            {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate()
+                {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}:{}", Json::Str(key.clone()), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
@@ -18,6 +18,18 @@ pub enum TokenType
     Bool,
     Str,
     Array,
+    Map,
+    Struct,
+    Enum,
+    Const,
+    Let,
+    Match,
+    Case,
+    Try,
+    Catch,
+    Throw,
+    Test,
+    In,
     If,
     Is,
     Not,
@@ -25,6 +37,7 @@ pub enum TokenType
     Minus,
     LessThan,
     GreaterThan,
+    DefaultTo,
     LeftBracket,
     RightBracket
 }
@@ -44,63 +57,154 @@ impl Token
     }
 }
 
-pub fn tokenise_lines(lines: &Vec<String>) -> Vec<Vec<Token>>
+pub fn tokenise_lines(lines: &[String]) -> Vec<Vec<Token>>
 {
     let mut tokenised_lines = Vec::<Vec<Token>>::new();
 
     for line in lines {
         tokenised_lines.push(
-            get_tokens_from_line(
-                &line.trim().to_string()
-            )
+            get_tokens_from_line(line.trim())
         );
     }
 
     tokenised_lines
 }
 
-fn get_tokens_from_line(input: &String) -> Vec<Token>
+// A line along with what tokenising it throws away (its comment and whitespace), for tools that rewrite source rather
+// than run it. The line as written is its indent, then each token as written followed by its spacing, then its comment.
+pub struct TriviaLine
+{
+    pub indent: String, // The whitespace before anything else, or all of a line with nothing else on it
+    pub tokens: Vec<Token>,
+    pub written: Vec<String>, // Each token as written, which may be spaced differently (e.g. "a > 1" for "a>1")
+    pub spacing: Vec<String>, // The whitespace after each token
+    pub comment: Option<String> // Including the leading "//", and any whitespace after it
+}
+
+impl TriviaLine
+{
+    pub fn line(&self) -> String
+    {
+        let tokens = self.written.iter().zip(&self.spacing).map(|(written, spacing)| format!("{}{}", written, spacing));
+        format!("{}{}{}", self.indent, tokens.collect::<String>(), self.comment.as_deref().unwrap_or_default())
+    }
+}
+
+pub fn tokenise_lines_with_trivia(lines: &[String]) -> Vec<TriviaLine>
+{
+    let mut tokenised_lines = Vec::<TriviaLine>::new();
+
+    for line in lines
+    {
+        let (tokens, comment) = tokenise_line(line.trim());
+        let indent = line[..line.len() - line.trim_start().len()].to_string();
+
+        let mut rest = line.trim_start();
+        let mut written = Vec::<String>::new();
+        let mut spacing = Vec::<String>::new();
+        for token in &tokens
+        {
+            let (token, after) = rest.split_at(written_length(rest, &token.string));
+            let next = after.trim_start();
+            written.push(token.to_string());
+            spacing.push(after[..after.len() - next.len()].to_string());
+            rest = next;
+        }
+
+        let comment = comment.map(|_| rest.to_string());
+        tokenised_lines.push(TriviaLine { indent, tokens, written, spacing, comment });
+    }
+
+    tokenised_lines
+}
+
+// How much of the text (from its start) a token was written as. Tokens made from several (e.g. "a>1" from "a", ">"
+// and "1") are spaced in their own way, so whitespace is skipped wherever one has it and the other doesn't.
+fn written_length(text: &str, token: &str) -> usize
+{
+    let mut text_chars = text.char_indices().peekable();
+    let mut token_chars = token.chars().peekable();
+    let mut length = 0;
+
+    while let (Some(&c), Some(&(i, t))) = (token_chars.peek(), text_chars.peek())
+    {
+        if c == t
+        {
+            length = i + t.len_utf8();
+            token_chars.next();
+            text_chars.next();
+        }
+        else if t.is_whitespace() { text_chars.next(); }
+        else if c.is_whitespace() { token_chars.next(); }
+        else { break }
+    }
+
+    length
+}
+
+fn get_tokens_from_line(input: &str) -> Vec<Token>
+{
+    tokenise_line(input).0
+}
+
+// Gives the tokens on a line, and the comment ending it, if any
+pub fn tokenise_line(input: &str) -> (Vec<Token>, Option<String>)
 {
     // There are some tokens that, if found, are definitely tokens, regardless of spaces
     // (e.g. a bracket anywhere is always a bracket, as is a "*", but "int" might be part
     // of a variable called "my_integer", for example. However, if we're inside a string,
-    // no new tokens may arise until the string terminates.
+    // no new tokens may arise until the string terminates. The same goes for anything
+    // between square or curly brackets, such that "foo[i - 1]", "bar[1:3]" or "{1: 2}"
+    // remain one value.
 
     let mut tokens = Vec::<Token>::new();
     let mut word = String::new();
     let mut inside_string = false;
+    let mut bracket_depth = 0;
     let mut comment_reached = false;
 
+    // Work on characters rather than bytes so that non-ASCII strings are handled properly
+    let chars = input.chars().collect::<Vec<char>>();
+
+    let mut comment = Option::<String>::None;
+
     // Ignore empty lines
-    if input.is_empty() { return tokens }
+    if chars.is_empty() { return (tokens, comment) }
 
-    for i in 0..input.len()
+    for i in 0..chars.len()
     {
         if comment_reached { continue }
 
-        let char = input.chars().nth(i).unwrap();
-        let single_found = is_single_token(char, input.chars().nth(i+1));
+        let char = chars[i];
+        let inside_brackets = bracket_depth > 0;
+        let single_found = !inside_brackets && is_single_token(char, chars.get(i+1).copied());
 
         // Add character to buffer, even if it's a string quote
         word.push(char);
 
         // Detect comments
-        if i < input.len() - 2 && char == '/' && input.chars().nth(i+1).unwrap() == '/'
+        if !inside_string && i + 2 < chars.len() && char == '/' && chars[i+1] == '/'
         {
             // Still process word buffer, but don't include this character, and stop after
             word.pop();
             comment_reached = true;
+            comment = Some(chars[i..].iter().collect::<String>().trim_end().to_string());
         }
 
         // Keep track of state
         if char == '\"' {
             inside_string = !inside_string;
         }
-        let string_ended = char == '\"' && !inside_string;
-        let normal_word_ended = !inside_string && !single_found && (char == ' ' || i == input.len()-1);
+        else if !inside_string && (char == '[' || char == '{') {
+            bracket_depth += 1;
+        }
+        else if !inside_string && (char == ']' || char == '}') && bracket_depth > 0 {
+            bracket_depth -= 1;
+        }
 
-        // If a string's on-going
-        if inside_string {}
+        let string_ended = char == '\"' && !inside_string && bracket_depth == 0;
+        let normal_word_ended = !inside_string && bracket_depth == 0 && !single_found &&
+            (char == ' ' || i == chars.len()-1);
 
         // If a string or a normal word just ended, or this is the last loop iteration because we've just found a comment
         if string_ended || normal_word_ended || comment_reached
@@ -141,8 +245,55 @@ fn get_tokens_from_line(input: &String) -> Vec<Token>
         }
     }
 
+    // An unterminated string or bracket swallows the rest of the line, so keep what we have
+    if !word.trim().is_empty()
+    {
+        tokens.push(Token {
+            token_type: token_from_string(word.trim()),
+            string: word.trim().to_string()
+        });
+    }
+
+    collect_calls(&mut tokens);
     collect_operators(&mut tokens);
-    tokens
+    (tokens, comment)
+}
+
+/*
+    Only a call at the very start of a line is an instruction in its own right, so any others (e.g.
+    the "len(foo)" in "int a = len(foo)" or "print(len(foo))") are combined into a single value to be
+    evaluated later. Innermost calls are collected first so that they end up within their callers.
+*/
+fn collect_calls(tokens: &mut Vec<Token>)
+{
+    for i in (1..tokens.len()).rev()
+    {
+        if i + 1 >= tokens.len() ||
+            tokens[i].token_type != TokenType::Value ||
+            tokens[i+1].token_type != TokenType::LeftBracket
+        {
+            continue
+        }
+
+        let Some(end) = tokens[i+2..].iter().position(|t| t.token_type == TokenType::RightBracket) else { continue };
+        let end = i + 2 + end;
+
+        let arguments = tokens[i+2..end].iter().map(|t| t.string.clone()).collect::<Vec<String>>();
+        let mut call = Token {
+            token_type: TokenType::Value,
+            string: format!("{}({})", tokens[i].string, arguments.join(" "))
+        };
+
+        // Keep any field access or method call on what's returned (e.g. "foo(1).bar") with the call itself
+        let mut end = end;
+        if tokens.get(end+1).is_some_and(|t| t.token_type == TokenType::Value && t.string.starts_with('.'))
+        {
+            end += 1;
+            call.string.push_str(&tokens[end].string);
+        }
+
+        tokens.splice(i..=end, [call]);
+    }
 }
 
 fn is_single_token(c: char, next_char: Option<char>) -> bool
@@ -158,16 +309,31 @@ fn is_single_token(c: char, next_char: Option<char>) -> bool
         '(' |
         ')' |
         '*' |
-        '-' => next_char.is_none() || next_char.unwrap() != '>',
-        '<' |
-        '>' |
+        '-' => next_char != Some('>'),
         _ => false
     }
 }
 
-fn token_from_string(input: &String) -> TokenType
+// Every word with a meaning of its own (i.e. that can't be a name)
+pub const KEYWORDS: [&str; 28] = [
+    "for", "from", "to", "done", "fn", "return", "int", "bool", "string", "array", "map", "struct", "enum", "const",
+    "let", "match", "case", "try", "catch", "throw", "test", "in", "if", "is", "not", "true", "false", "none"
+];
+
+// The value of a boolean literal, which is a value in its own right rather than a name
+pub fn boolean_literal(value: &str) -> Option<bool>
+{
+    match value
+    {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None
+    }
+}
+
+fn token_from_string(input: &str) -> TokenType
 {
-    match input.chars().collect::<String>().as_str()
+    match input
     {
         "=" => TokenType::Equals,
         "for" => TokenType::For,
@@ -181,10 +347,22 @@ fn token_from_string(input: &String) -> TokenType
         "(" => TokenType::LeftBracket,
         ")" => TokenType::RightBracket,
         "return" => TokenType::Return,
-        "int" => TokenType::Int,
-        "bool" => TokenType::Bool,
-        "string" => TokenType::Str,
+        "int" | "int?" => TokenType::Int,
+        "bool" | "bool?" => TokenType::Bool,
+        "string" | "string?" => TokenType::Str,
         "array" => TokenType::Array,
+        "map" => TokenType::Map,
+        "struct" => TokenType::Struct,
+        "enum" => TokenType::Enum,
+        "const" => TokenType::Const,
+        "let" => TokenType::Let,
+        "match" => TokenType::Match,
+        "case" => TokenType::Case,
+        "try" => TokenType::Try,
+        "catch" => TokenType::Catch,
+        "throw" => TokenType::Throw,
+        "test" => TokenType::Test,
+        "in" => TokenType::In,
         "if" => TokenType::If,
         "is" => TokenType::Is,
         "not" => TokenType::Not,
@@ -192,6 +370,7 @@ fn token_from_string(input: &String) -> TokenType
         "-" => TokenType::Minus,
         "<" => TokenType::LessThan,
         ">" => TokenType::GreaterThan,
+        "??" => TokenType::DefaultTo,
         _ => TokenType::Value
     }
 }
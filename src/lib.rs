@@ -0,0 +1,32 @@
+pub mod lexer;
+pub mod parser;
+pub mod engine;
+pub mod variables;
+pub mod common;
+pub mod operators;
+pub mod stdlib;
+pub mod checker;
+pub mod natives;
+pub mod streams;
+pub mod golden;
+pub mod testing;
+pub mod lint;
+pub mod formatter;
+pub mod json;
+pub mod lsp;
+pub mod debugger;
+pub mod dap;
+pub mod tracer;
+pub mod profiler;
+pub mod interpreter;
+
+pub use interpreter::Interpreter;
+pub use interpreter::Error;
+pub use variables::Variable as Value;
+pub use engine::Limits;
+pub use engine::Hook;
+pub use engine::State;
+pub use natives::Natives;
+pub use natives::Parameters;
+pub use streams::Io;
+pub use streams::BufferIo;
@@ -0,0 +1,274 @@
+use super::lexer::Token;
+use super::lexer::TokenType;
+use super::lexer::tokenise_line;
+use super::parser::Instruction;
+use super::parser::get_corresponding_end_of_frame;
+use super::parser::FRAME_TOKENS;
+use super::engine::is_literal;
+use super::engine::split_operands;
+
+use std::collections::HashSet;
+use std::fmt;
+
+/*
+    Finds things that are likely mistakes, even though they'd run without error. Each kind of warning has a code, so
+    that it can be silenced on a line by a comment (e.g. "int unused = 1 // lint: allow W001"), or all of them can be
+    with "// lint: allow". Scopes aren't tracked, so a name is taken to mean any variable or function with it.
+*/
+
+pub struct Warning
+{
+    pub code: &'static str,
+    pub message: String,
+    pub line: usize
+}
+
+impl Warning
+{
+    fn new(code: &'static str, message: String, line: usize) -> Warning
+    {
+        Warning { code, message, line }
+    }
+}
+
+const UNUSED_VARIABLE: &str = "W001";
+const UNREACHABLE_CODE: &str = "W002";
+const UNUSED_FUNCTION: &str = "W003";
+const SHADOWED_PARAMETER: &str = "W004";
+const CONSTANT_CONDITION: &str = "W005";
+
+pub fn lint(source: &[String], lines: &[Vec<Token>], instructions: &[Instruction]) -> Vec<Warning>
+{
+    let mut warnings = Vec::<Warning>::new();
+
+    // The names each line reads from, and the variables declared (with the line they're declared on)
+    let uses = instructions.iter().map(used_names).collect::<Vec<Vec<String>>>();
+    let declarations = (0..instructions.len())
+        .filter_map(|i| declared_name(&instructions[i]).map(|name| (name, i)))
+        .collect::<Vec<(String, usize)>>();
+    let is_used_outside = |name: &str, first_line: usize, last_line: usize| {
+        (0..uses.len()).any(|i| (i < first_line || i > last_line) && uses[i].iter().any(|used| used == name))
+    };
+
+    for (name, line) in &declarations
+    {
+        if !name.starts_with('_') && !is_used_outside(name, *line, *line) {
+            warnings.push(Warning::new(UNUSED_VARIABLE, format!("variable \"{}\" is never used", name), *line));
+        }
+    }
+
+    // Variables declared outside of any block, which parameters of the same name hide
+    let globals = top_level_lines(lines).into_iter()
+        .filter_map(|i| declared_name(&instructions[i]).map(|name| (name, i)))
+        .collect::<Vec<(String, usize)>>();
+
+    let constants = instructions.iter().filter_map(|instruction| match instruction {
+        Instruction::ConstDeclaration { name, .. } => Some(name.clone()),
+        _ => None
+    }).collect::<HashSet<String>>();
+
+    for (i, instruction) in instructions.iter().enumerate()
+    {
+        match instruction
+        {
+            Instruction::FunctionDeclaration { name, first_line, last_line, arguments } =>
+            {
+                // Calls from within the function itself don't count, as they'd never be reached
+                if !name.starts_with('_') && !is_used_outside(name, *first_line, *last_line) {
+                    warnings.push(Warning::new(UNUSED_FUNCTION, format!("function \"{}\" is never called", name), i));
+                }
+
+                for (argument, _) in arguments
+                {
+                    if let Some((_, line)) = globals.iter().find(|(global, _)| global == argument)
+                    {
+                        let message = format!("parameter \"{}\" hides the variable declared on line {}", argument, line + 1);
+                        warnings.push(Warning::new(SHADOWED_PARAMETER, message, i));
+                    }
+
+                    let redeclared = declarations.iter()
+                        .filter(|(name, line)| name == argument && line > first_line && line < last_line);
+                    for (_, line) in redeclared
+                    {
+                        let message = format!("\"{}\" is declared again, hiding the parameter of the same name", argument);
+                        warnings.push(Warning::new(SHADOWED_PARAMETER, message, *line));
+                    }
+                }
+            },
+
+            Instruction::Return { .. } | Instruction::Throw { .. } =>
+            {
+                // Nothing can follow in the same block, which ends at a "done", or where a catch or case begins
+                let next = (i+1..lines.len()).find(|j| !lines[*j].is_empty());
+                let ends_block = |j: &usize| matches!(lines[*j][0].token_type, TokenType::Done | TokenType::Catch | TokenType::Case);
+                if let Some(next) = next.filter(|j| !ends_block(j))
+                {
+                    let after = if matches!(instruction, Instruction::Return { .. }) { "return" } else { "throw" };
+                    warnings.push(Warning::new(UNREACHABLE_CODE, format!("unreachable code after {}", after), next));
+                }
+            },
+
+            Instruction::IfValue { left_value, .. } if is_constant(left_value, &constants) =>
+                warnings.push(Warning::new(CONSTANT_CONDITION, "condition is always the same".to_string(), i)),
+
+            Instruction::IfValueIsValue { left_value, right_value, .. } |
+            Instruction::IfValueIsNotValue { left_value, right_value, .. }
+                if is_constant(left_value, &constants) && is_constant(right_value, &constants) =>
+                warnings.push(Warning::new(CONSTANT_CONDITION, "condition is always the same".to_string(), i)),
+
+            _ => {}
+        }
+    }
+
+    warnings.retain(|warning| !is_allowed(source.get(warning.line), warning.code));
+    warnings.sort_by_key(|warning| warning.line);
+    warnings
+}
+
+// Whether the line has a comment silencing the given warning (or all of them). Only the comment ending the line
+// counts, not the same text inside a string.
+fn is_allowed(line: Option<&String>, code: &str) -> bool
+{
+    let Some((_, Some(comment))) = line.map(|line| tokenise_line(line.trim())) else { return false };
+    let Some(start) = comment.find("// lint: allow") else { return false };
+
+    let codes = comment[start + "// lint: allow".len()..].split([' ', ',']).filter(|c| !c.is_empty()).collect::<Vec<&str>>();
+    codes.is_empty() || codes.contains(&code)
+}
+
+fn is_constant(value: &str, constants: &HashSet<String>) -> bool
+{
+    split_operands(value).iter().all(|operand| is_literal(operand) || constants.contains(operand))
+}
+
+// The lines outside of any block (i.e. not within a loop, function, etc.)
+fn top_level_lines(lines: &[Vec<Token>]) -> Vec<usize>
+{
+    let mut top_level = Vec::<usize>::new();
+    let mut i = 0;
+
+    while i < lines.len()
+    {
+        top_level.push(i);
+        match lines[i].first()
+        {
+            Some(token) if FRAME_TOKENS.contains(&token.token_type) =>
+                i = get_corresponding_end_of_frame(lines, i).unwrap_or(lines.len()) + 1,
+            _ => i += 1
+        }
+    }
+
+    top_level
+}
+
+// The variable an instruction declares, if any. Loop variables and caught errors are left out, as they're often
+// declared only to be ignored.
+fn declared_name(instruction: &Instruction) -> Option<String>
+{
+    match instruction
+    {
+        Instruction::IntDeclaration { name, .. } |
+        Instruction::BoolDeclaration { name, .. } |
+        Instruction::StringDeclaration { name, .. } |
+        Instruction::ConstDeclaration { name, .. } |
+        Instruction::LetDeclaration { name, .. } |
+        Instruction::ArrayDeclaration { name, .. } |
+        Instruction::MapDeclaration { name, .. } |
+        Instruction::StructInstanceDeclaration { name, .. } => Some(name.clone()),
+        Instruction::FunctionCall { target_variable, .. } => target_variable.clone(),
+        _ => None
+    }
+}
+
+// The names of the variables and functions an instruction reads from
+fn used_names(instruction: &Instruction) -> Vec<String>
+{
+    let values = match instruction
+    {
+        Instruction::FromValueToValue { start, end, .. } => vec![start, end],
+        Instruction::ForEach { collection, .. } => vec![collection],
+        Instruction::IfValue { left_value, .. } => vec![left_value],
+        Instruction::IfValueIsValue { left_value, right_value, .. } |
+        Instruction::IfValueIsNotValue { left_value, right_value, .. } => vec![left_value, right_value],
+        Instruction::Match { value, .. } |
+        Instruction::Throw { value } |
+        Instruction::Return { value } |
+        Instruction::IntDeclaration { value, .. } |
+        Instruction::BoolDeclaration { value, .. } |
+        Instruction::StringDeclaration { value, .. } |
+        Instruction::ConstDeclaration { value, .. } |
+        Instruction::LetDeclaration { value, .. } |
+        Instruction::StructInstanceDeclaration { value, .. } => vec![value],
+        Instruction::ArrayDeclaration { value, .. } |
+        Instruction::MapDeclaration { value, .. } => value.iter().collect(),
+
+        Instruction::FunctionCall { function, values, .. } =>
+        {
+            let mut names = values.iter().flat_map(|value| identifiers(value)).collect::<Vec<String>>();
+            names.push(function.clone());
+            return names
+        },
+
+        // Assigning to a variable isn't reading from it, but anything used to index into it is
+        Instruction::Assignment { name, value } =>
+        {
+            let mut names = identifiers(value);
+            names.extend(identifiers(name).into_iter().skip(1));
+            return names
+        },
+
+        _ => Vec::new()
+    };
+
+    values.iter().flat_map(|value| identifiers(value)).collect()
+}
+
+// Every name in an expression, other than fields and methods (e.g. "foo" and "i" but not "bar" in "foo[i].bar")
+fn identifiers(value: &str) -> Vec<String>
+{
+    let chars = value.chars().collect::<Vec<char>>();
+    let mut names = Vec::<String>::new();
+    let mut inside_string = false;
+    let mut i = 0;
+
+    while i < chars.len()
+    {
+        if chars[i] == '\"' {
+            inside_string = !inside_string;
+        }
+
+        else if !inside_string && (chars[i].is_alphabetic() || chars[i] == '_')
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+
+            if start == 0 || chars[start - 1] != '.' {
+                names.push(chars[start..i].iter().collect());
+            }
+            continue
+        }
+
+        // Skip the rest of numbers, so that something like "1e" isn't taken to hold a name
+        else if !inside_string && chars[i].is_ascii_digit()
+        {
+            while i < chars.len() && chars[i].is_alphanumeric() {
+                i += 1;
+            }
+            continue
+        }
+
+        i += 1;
+    }
+
+    names
+}
+
+impl fmt::Display for Warning
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{} {} - line {}", self.code, self.message, self.line + 1)
+    }
+}
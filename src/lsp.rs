@@ -0,0 +1,511 @@
+use super::lexer::tokenise_lines;
+use super::lexer::KEYWORDS;
+use super::parser::parse_lines;
+use super::parser::Instruction;
+use super::checker::check;
+use super::lint::lint;
+use super::natives::Natives;
+use super::variables::Variable;
+use super::variables::VariableType;
+use super::json::Json;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::Write;
+
+/*
+    A language server, speaking the Language Server Protocol (JSON-RPC, each message preceded by a "Content-Length"
+    header) over whatever it's given to read from and write to. Documents are always sent whole, and are reparsed on
+    each change. Columns are counted in UTF-16 code units, as the protocol does unless told otherwise, so a character
+    outside the basic plane (e.g. an emoji in a string) takes up two.
+*/
+
+const ERROR: usize = 1;
+const WARNING: usize = 2;
+
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_REQUEST: f64 = -32600.0;
+
+const FUNCTION_KIND: usize = 3; // For completions, whereas symbols number their kinds differently
+const VARIABLE_KIND: usize = 6;
+const KEYWORD_KIND: usize = 14;
+const FUNCTION_SYMBOL: usize = 12;
+
+// A name declared somewhere in a document, which is only visible between the given lines
+struct Declaration
+{
+    name: String,
+    line: usize,
+    detail: String, // How it was declared (e.g. "int count" or "fn add: int a || int b")
+    scope: (usize, usize),
+    is_function: bool
+}
+
+struct Document
+{
+    lines: Vec<String>,
+    instructions: Option<Vec<Instruction>> // None if it couldn't be parsed
+}
+
+#[derive(Default)]
+struct Server
+{
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool
+}
+
+// Handles messages until told to exit (or there's nothing left to read). Returns if it was shut down first, as it
+// should have been.
+pub fn serve(input: &mut dyn BufRead, output: &mut dyn Write) -> bool
+{
+    let mut server = Server::default();
+
+    while !server.exited
+    {
+        let message = match read_message(input)
+        {
+            Some(Ok(message)) => message,
+            Some(Err(error)) =>
+            {
+                let response = error_response(Json::Null, INVALID_REQUEST, &error);
+                if write_message(output, &response).is_err() { break }
+                continue
+            },
+            None => break
+        };
+
+        for reply in server.handle(&message)
+        {
+            if write_message(output, &reply).is_err() {
+                return server.shut_down
+            }
+        }
+    }
+
+    server.shut_down
+}
+
+// Gives None once there's nothing left to read
+pub fn read_message(input: &mut dyn BufRead) -> Option<Result<Json, String>>
+{
+    let mut length = Option::<usize>::None;
+
+    loop
+    {
+        let mut header = String::new();
+        match input.read_line(&mut header)
+        {
+            Ok(0) | Err(_) => return None,
+            _ => {}
+        }
+
+        let header = header.trim();
+        if header.is_empty()
+        {
+            // Blank lines between messages are skipped
+            if length.is_some() { break }
+            continue
+        }
+
+        if let Some((name, value)) = header.split_once(':')
+        {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; length?];
+    if input.read_exact(&mut body).is_err() {
+        return None
+    }
+
+    let body = String::from_utf8(body).map_err(|_| "message is not valid UTF-8".to_string());
+    Some(body.and_then(|body| Json::parse(&body)))
+}
+
+pub fn write_message(output: &mut dyn Write, message: &Json) -> std::io::Result<()>
+{
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn response(id: Json, result: Json) -> Json
+{
+    Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("result", result)])
+}
+
+fn error_response(id: Json, code: f64, message: &str) -> Json
+{
+    let error = Json::object(vec![("code", Json::Number(code)), ("message", message.into())]);
+    Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("error", error)])
+}
+
+fn notification(method: &str, params: Json) -> Json
+{
+    Json::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+fn position(line: usize, character: usize) -> Json
+{
+    Json::object(vec![("line", line.into()), ("character", character.into())])
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json
+{
+    Json::object(vec![("start", position(line, start)), ("end", position(line, end))])
+}
+
+impl Server
+{
+    // Gives the messages to send back, if any
+    fn handle(&mut self, message: &Json) -> Vec<Json>
+    {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or_default();
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params.at(&["textDocument", "uri"]).and_then(Json::as_str).unwrap_or_default().to_string();
+
+        // Notifications have no id, and get no response
+        let Some(id) = message.get("id").cloned() else
+        {
+            return match method
+            {
+                "textDocument/didOpen" =>
+                {
+                    let text = params.at(&["textDocument", "text"]).and_then(Json::as_str).unwrap_or_default();
+                    self.open(&uri, text)
+                },
+                "textDocument/didChange" =>
+                {
+                    // Only whole documents are ever sent, so the last change is all there is
+                    let changes = params.get("contentChanges").and_then(Json::as_array);
+                    match changes.and_then(|changes| changes.last()).and_then(|change| change.get("text"))
+                    {
+                        Some(Json::Str(text)) => self.open(&uri, text),
+                        _ => Vec::new()
+                    }
+                },
+                "textDocument/didClose" =>
+                {
+                    self.documents.remove(&uri);
+                    vec![diagnostics_notification(&uri, Vec::new())]
+                },
+                "exit" =>
+                {
+                    self.exited = true;
+                    Vec::new()
+                },
+                _ => Vec::new()
+            }
+        };
+
+        let result = match method
+        {
+            "initialize" => initialize_result(),
+            "shutdown" =>
+            {
+                self.shut_down = true;
+                Json::Null
+            },
+            "textDocument/definition" => self.with_declaration(&uri, params, |declaration, document| {
+                let text = &document.lines[declaration.line];
+                let start = name_position(text, &declaration.name);
+                let end = start + declaration.name.chars().count();
+                let range = range(declaration.line, utf16_column(text, start), utf16_column(text, end));
+                Json::object(vec![("uri", uri.clone().into()), ("range", range)])
+            }),
+            "textDocument/hover" => self.with_declaration(&uri, params, |declaration, _| Json::object(vec![
+                ("contents", Json::object(vec![
+                    ("kind", "markdown".into()),
+                    ("value", format!("```lukascript\n{}\n```", declaration.detail).into())
+                ]))
+            ])),
+            "textDocument/completion" => self.completions(&uri),
+            "textDocument/documentSymbol" => self.symbols(&uri),
+            _ => return vec![error_response(id, METHOD_NOT_FOUND, &format!("unknown method \"{}\"", method))]
+        };
+
+        vec![response(id, result)]
+    }
+
+    fn open(&mut self, uri: &str, text: &str) -> Vec<Json>
+    {
+        let lines = text.lines().map(String::from).collect::<Vec<String>>();
+        let tokens = tokenise_lines(&lines);
+        let mut diagnostics = Vec::<Json>::new();
+
+        let instructions = match parse_lines(&tokens)
+        {
+            Ok(instructions) =>
+            {
+                for problem in check(&instructions) {
+                    diagnostics.push(diagnostic(&lines, problem.line, ERROR, &problem.message, None));
+                }
+                for warning in lint(&lines, &tokens, &instructions) {
+                    diagnostics.push(diagnostic(&lines, warning.line, WARNING, &warning.message, Some(warning.code)));
+                }
+                Some(instructions)
+            },
+            Err(error) =>
+            {
+                diagnostics.push(diagnostic(&lines, error.line, ERROR, &error.message, None));
+                None
+            }
+        };
+
+        self.documents.insert(uri.to_string(), Document { lines, instructions });
+        vec![diagnostics_notification(uri, diagnostics)]
+    }
+
+    // Gives what's made from the declaration of the name at the position in the params, or null if there isn't one
+    fn with_declaration(&self, uri: &str, params: &Json, make: impl Fn(&Declaration, &Document) -> Json) -> Json
+    {
+        let Some(document) = self.documents.get(uri) else { return Json::Null };
+        let line = params.at(&["position", "line"]).and_then(Json::as_usize).unwrap_or_default();
+        let character = params.at(&["position", "character"]).and_then(Json::as_usize).unwrap_or_default();
+
+        let Some(text) = document.lines.get(line) else { return Json::Null };
+        let Some(name) = word_at(text, char_column(text, character)) else { return Json::Null };
+        match find_declaration(&declarations(document), &name, line)
+        {
+            Some(declaration) => make(declaration, document),
+            None => Json::Null
+        }
+    }
+
+    fn completions(&self, uri: &str) -> Json
+    {
+        let mut items = Vec::<(String, usize)>::new();
+        items.extend(KEYWORDS.iter().map(|keyword| (keyword.to_string(), KEYWORD_KIND)));
+        items.extend(Natives::default().names().into_iter().map(|function| (function, FUNCTION_KIND)));
+
+        if let Some(document) = self.documents.get(uri)
+        {
+            for declaration in declarations(document)
+            {
+                let kind = if declaration.is_function { FUNCTION_KIND } else { VARIABLE_KIND };
+                items.push((declaration.name, kind));
+            }
+        }
+
+        let mut seen = Vec::<String>::new();
+        items.retain(|(label, _)| if seen.contains(label) { false } else { seen.push(label.clone()); true });
+
+        Json::Array(items.into_iter().map(|(label, kind)| {
+            Json::object(vec![("label", label.into()), ("kind", kind.into())])
+        }).collect())
+    }
+
+    fn symbols(&self, uri: &str) -> Json
+    {
+        let Some(document) = self.documents.get(uri) else { return Json::Array(Vec::new()) };
+        let Some(instructions) = &document.instructions else { return Json::Array(Vec::new()) };
+        let mut symbols = Vec::<Json>::new();
+
+        for instruction in instructions
+        {
+            if let Instruction::FunctionDeclaration { name, first_line, last_line, arguments } = instruction
+            {
+                let end = utf16_length(&document.lines[*last_line]);
+                symbols.push(Json::object(vec![
+                    ("name", name.clone().into()),
+                    ("detail", function_signature(name, arguments).into()),
+                    ("kind", FUNCTION_SYMBOL.into()),
+                    ("range", Json::object(vec![("start", position(*first_line, 0)), ("end", position(*last_line, end))])),
+                    ("selectionRange", range(*first_line, 0, utf16_length(&document.lines[*first_line])))
+                ]));
+            }
+        }
+
+        Json::Array(symbols)
+    }
+}
+
+fn initialize_result() -> Json
+{
+    let capabilities = Json::object(vec![
+        ("textDocumentSync", 1.into()), // Whole documents
+        ("definitionProvider", true.into()),
+        ("hoverProvider", true.into()),
+        ("completionProvider", Json::object(Vec::new())),
+        ("documentSymbolProvider", true.into())
+    ]);
+
+    Json::object(vec![
+        ("capabilities", capabilities),
+        ("serverInfo", Json::object(vec![("name", "lukascript".into())]))
+    ])
+}
+
+fn diagnostics_notification(uri: &str, diagnostics: Vec<Json>) -> Json
+{
+    notification("textDocument/publishDiagnostics", Json::object(vec![
+        ("uri", uri.into()),
+        ("diagnostics", Json::Array(diagnostics))
+    ]))
+}
+
+// Covers the whole line, as that's as precise as errors get
+fn diagnostic(lines: &[String], line: usize, severity: usize, message: &str, code: Option<&str>) -> Json
+{
+    let line = line.min(lines.len().saturating_sub(1));
+    let end = lines.get(line).map(|text| utf16_length(text)).unwrap_or_default();
+    let message = message.lines().next().unwrap_or_default().trim_end_matches(':');
+
+    let mut fields = vec![("range", range(line, 0, end)), ("severity", severity.into())];
+    if let Some(code) = code {
+        fields.push(("code", code.into()));
+    }
+    fields.push(("source", "lukascript".into()));
+    fields.push(("message", message.into()));
+    Json::object(fields)
+}
+
+fn utf16_length(text: &str) -> usize
+{
+    text.encode_utf16().count()
+}
+
+// The column (in UTF-16 code units) of the character at the given index, and back again. A column part way through a
+// character is taken to be that character.
+fn utf16_column(text: &str, character: usize) -> usize
+{
+    text.chars().take(character).map(char::len_utf16).sum()
+}
+
+fn char_column(text: &str, column: usize) -> usize
+{
+    let mut units = 0;
+    text.chars().take_while(|c| { units += c.len_utf16(); units <= column }).count()
+}
+
+// The name (of a variable or function) the given character is part of
+fn word_at(text: &str, character: usize) -> Option<String>
+{
+    let chars = text.chars().collect::<Vec<char>>();
+    let is_name = |c: &char| c.is_alphanumeric() || *c == '_';
+
+    let mut start = character.min(chars.len());
+    while start > 0 && is_name(&chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = character.min(chars.len());
+    while end < chars.len() && is_name(&chars[end]) {
+        end += 1;
+    }
+
+    let word = chars[start..end].iter().collect::<String>();
+    if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) { None } else { Some(word) }
+}
+
+// Where a name first appears on a line as a whole word (so that "a" isn't found in "int a")
+fn name_position(text: &str, name: &str) -> usize
+{
+    let chars = text.chars().collect::<Vec<char>>();
+    let length = name.chars().count();
+    let is_name = |i: usize| chars.get(i).is_some_and(|c| c.is_alphanumeric() || *c == '_');
+
+    (0..chars.len()).find(|i| {
+        chars[*i..].iter().take(length).collect::<String>() == name && (*i == 0 || !is_name(i - 1)) && !is_name(i + length)
+    }).unwrap_or_default()
+}
+
+fn type_name(variable_type: &VariableType) -> String
+{
+    Variable { variable_type: variable_type.clone() }.type_name()
+}
+
+fn typed_names(names: &[(String, VariableType)]) -> String
+{
+    names.iter().map(|(name, variable_type)| format!("{} {}", type_name(variable_type), name))
+        .collect::<Vec<String>>().join(" || ")
+}
+
+fn function_signature(name: &str, arguments: &[(String, VariableType)]) -> String
+{
+    if arguments.is_empty() { format!("fn {}", name) } else { format!("fn {}: {}", name, typed_names(arguments)) }
+}
+
+// Everything a document declares. Like the linter, this doesn't track scopes other than those of function parameters.
+fn declarations(document: &Document) -> Vec<Declaration>
+{
+    let Some(instructions) = &document.instructions else { return Vec::new() };
+    let everywhere = (0, instructions.len());
+    let mut declarations = Vec::<Declaration>::new();
+    let mut declare = |name: &str, line: usize, detail: String, scope: (usize, usize), is_function: bool| {
+        declarations.push(Declaration { name: name.to_string(), line, detail, scope, is_function });
+    };
+
+    for (i, instruction) in instructions.iter().enumerate()
+    {
+        let optional = |optional: &bool| if *optional { "?" } else { "" };
+        match instruction
+        {
+            Instruction::FunctionDeclaration { name, first_line, last_line, arguments } =>
+            {
+                declare(name, i, function_signature(name, arguments), everywhere, true);
+                for (argument, variable_type) in arguments {
+                    let detail = format!("{} {}", type_name(variable_type), argument);
+                    declare(argument, i, detail, (*first_line, *last_line), false);
+                }
+            },
+            Instruction::StructDeclaration { name, fields } =>
+                declare(name, i, format!("struct {}: {}", name, typed_names(fields)), everywhere, false),
+            Instruction::EnumDeclaration { name, variants } =>
+            {
+                let names = variants.iter().map(|(variant, _)| variant.clone()).collect::<Vec<String>>();
+                declare(name, i, format!("enum {} ({})", name, names.join(", ")), everywhere, false);
+            },
+            Instruction::IntDeclaration { name, optional: is_optional, .. } =>
+                declare(name, i, format!("int{} {}", optional(is_optional), name), everywhere, false),
+            Instruction::BoolDeclaration { name, optional: is_optional, .. } =>
+                declare(name, i, format!("bool{} {}", optional(is_optional), name), everywhere, false),
+            Instruction::StringDeclaration { name, optional: is_optional, .. } =>
+                declare(name, i, format!("string{} {}", optional(is_optional), name), everywhere, false),
+            Instruction::StructInstanceDeclaration { struct_name, name, optional: is_optional, .. } =>
+                declare(name, i, format!("{}{} {}", struct_name, optional(is_optional), name), everywhere, false),
+            Instruction::ConstDeclaration { name, variable_type, .. } =>
+                declare(name, i, format!("const {} {}", type_name(variable_type), name), everywhere, false),
+            Instruction::LetDeclaration { name, .. } => declare(name, i, format!("let {}", name), everywhere, false),
+            Instruction::ArrayDeclaration { name, element_type, .. } =>
+            {
+                let element = element_type.as_ref().map(|t| format!(" {}", type_name(t))).unwrap_or_default();
+                declare(name, i, format!("array{} {}", element, name), everywhere, false);
+            },
+            Instruction::MapDeclaration { name, value_type, .. } =>
+            {
+                let value = value_type.as_ref().map(|t| format!(" {}", type_name(t))).unwrap_or_default();
+                declare(name, i, format!("map{} {}", value, name), everywhere, false);
+            },
+            Instruction::FunctionCall { function, target_variable: Some(name), .. } =>
+                declare(name, i, format!("{} (returned by {})", name, function), everywhere, false),
+            Instruction::FromValueToValue { value, .. } =>
+                declare(value, i, format!("int {}", value), everywhere, false),
+            Instruction::ForEach { key, value, .. } =>
+            {
+                declare(key, i, key.clone(), everywhere, false);
+                if let Some(value) = value {
+                    declare(value, i, value.clone(), everywhere, false);
+                }
+            },
+            Instruction::Catch { name: Some(name) } =>
+                declare(name, i, format!("{} (caught error)", name), everywhere, false),
+            _ => {}
+        }
+    }
+
+    declarations
+}
+
+// Prefers the last declaration in scope at or before the line, falling back on the first anywhere (e.g. for functions
+// called before they're declared)
+fn find_declaration<'a>(declarations: &'a [Declaration], name: &str, line: usize) -> Option<&'a Declaration>
+{
+    let visible = declarations.iter()
+        .filter(|declaration| declaration.name == name && declaration.scope.0 <= line && line <= declaration.scope.1)
+        .collect::<Vec<&Declaration>>();
+
+    visible.iter().rev().find(|declaration| declaration.line <= line).or(visible.first()).copied()
+}
@@ -1,37 +1,259 @@
-pub mod lexer;
-pub mod parser;
-pub mod engine;
-pub mod variables;
-pub mod common;
-pub mod operators;
-pub mod stdlib;
+use lukascript::lexer;
+use lukascript::parser;
+use lukascript::engine;
+use lukascript::common;
+use lukascript::common::ErrorKind;
+use lukascript::checker;
+use lukascript::golden;
+use lukascript::testing;
+use lukascript::lint;
+use lukascript::formatter;
+use lukascript::lsp;
+use lukascript::dap;
+use lukascript::debugger::Debugger;
+use lukascript::tracer;
+use lukascript::tracer::Tracer;
+use lukascript::profiler::Profile;
+use lukascript::profiler::Profiler;
 
 use std::fs;
 use std::env;
+use std::thread;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::Duration;
+
+// Calls made from within expressions recurse within the engine itself, so give it room for the default call depth,
+// and let those calls use all but a little of it
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+const MAX_STACK: usize = STACK_SIZE - 16 * 1024 * 1024;
+
+const USAGE: &str = "usage: lukascript [test <directory> | check [file] | fmt [file] | lsp | dap | debug <file>] [options]";
 
 fn main()
 {
-    // Get debug mode
+    let arguments = env::args().collect::<Vec<String>>();
+
+    // Get debug mode, test mode, tracing, profiling and any limits, leaving anything else to say what to do
     let mut debug = false;
-    for argument in env::args()
+    let mut test = false;
+    let mut trace = Option::<tracer::TraceOptions>::None; // Any of the trace options turns tracing on
+    let mut profiling = Profiling::default();
+    let mut check_only = false; // Report unformatted files, rather than formatting them
+    let mut limits = engine::Limits { max_stack: Some(MAX_STACK), ..Default::default() };
+    let mut command = Vec::<String>::new();
+    let mut i = 1;
+    while i < arguments.len()
+    {
+        match arguments[i].as_str()
+        {
+            "--debug" => debug = true,
+            "--test" => test = true,
+            "--check" => check_only = true,
+            "--trace" => { trace.get_or_insert_with(Default::default); }
+            "--trace-json" => trace.get_or_insert_with(Default::default).json = true,
+            "--trace-function" =>
+            {
+                trace.get_or_insert_with(Default::default).function = Some(flag_value(&arguments, i));
+                i += 1;
+            }
+            "--trace-lines" =>
+            {
+                let message = "expected a line or range of lines (e.g. \"12-20\") after \"--trace-lines\"";
+                let lines = tracer::parse_line_range(&flag_value::<String>(&arguments, i))
+                    .unwrap_or_else(|| common::error(message.to_string()));
+                trace.get_or_insert_with(Default::default).lines = Some(lines);
+                i += 1;
+            }
+            "--profile" => profiling.enabled = true,
+            "--profile-folded" =>
+            {
+                profiling = Profiling { enabled: true, folded: Some(flag_value(&arguments, i)) };
+                i += 1;
+            }
+            "--max-call-depth" => { limits.max_call_depth = Some(flag_value(&arguments, i)); i += 1; }
+            "--max-instructions" => { limits.max_instructions = Some(flag_value(&arguments, i)); i += 1; }
+            "--max-size" => { limits.max_size = Some(flag_value(&arguments, i)); i += 1; }
+            "--timeout" => { limits.timeout = Some(Duration::from_secs_f64(flag_value(&arguments, i))); i += 1; }
+            flag if flag.starts_with("--") => common::error(format!("unknown option \"{}\"\n{}", flag, USAGE)),
+            _ => command.push(arguments[i].clone())
+        }
+        i += 1;
+    }
+
+    let interpreter = thread::Builder::new().stack_size(STACK_SIZE).spawn(move ||
     {
-        if argument == "--debug" {
-            debug = true;
+        match command.iter().map(String::as_str).collect::<Vec<&str>>().as_slice()
+        {
+            [] => run(debug, test, trace, profiling, limits),
+            ["test", directory] => if !golden::run_directory(directory, &limits) { std::process::exit(1) },
+            ["check"] => check("./src.txt"),
+            ["check", file] => check(file),
+            ["fmt"] => format("./src.txt", check_only),
+            ["fmt", file] => format(file, check_only),
+            ["debug", file] => debug_script(file, limits),
+            ["dap"] => dap::serve(Box::new(std::io::stdin().lock()), Box::new(std::io::stdout()), &limits),
+            ["lsp"] => if !lsp::serve(&mut std::io::stdin().lock(), &mut std::io::stdout()) { std::process::exit(1) },
+            _ => common::error(USAGE.to_string())
         }
+    }).expect("Could not start the interpreter");
+    if interpreter.join().is_err() {
+        std::process::exit(1);
     }
+}
 
-    let lines: Vec<String> = fs::read_to_string("./src.txt").expect("Could not locate source file, src.txt")
-    .lines()
-    .map(|l| String::from(l))
-    .collect();
+#[derive(Default)]
+struct Profiling
+{
+    enabled: bool,
+    folded: Option<String> // Where to write folded stacks, if anywhere
+}
+
+// Gets the value given after the flag at the given index
+fn flag_value<T: std::str::FromStr>(arguments: &[String], i: usize) -> T
+{
+    match arguments.get(i+1).map(|value| value.parse::<T>())
+    {
+        Some(Ok(value)) => value,
+        _ => common::error(format!("expected a valid value after \"{}\"", arguments[i]))
+    }
+}
+
+fn read_lines(file: &str) -> Vec<String>
+{
+    match fs::read_to_string(file)
+    {
+        Ok(source) => source.lines().map(String::from).collect(),
+        Err(error) => common::error(format!("could not read \"{}\": {}", file, error))
+    }
+}
+
+// Reports any errors and likely mistakes without running anything
+fn check(file: &str)
+{
+    let lines = read_lines(file);
+    let tokens = lexer::tokenise_lines(&lines);
+    let instructions = parser::parse_lines(&tokens).unwrap_or_else(|error| common::error(error.message));
+
+    let errors = checker::check(&instructions);
+    let warnings = lint::lint(&lines, &tokens, &instructions);
+    for error in &errors {
+        println!("Error: {}", error);
+    }
+    for warning in &warnings {
+        println!("Warning {}", warning);
+    }
+
+    println!("{} error(s), {} warning(s)", errors.len(), warnings.len());
+    if !errors.is_empty() || !warnings.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+// Rewrites the file formatted, or just says whether it needs to be
+fn format(file: &str, check_only: bool)
+{
+    let source = fs::read_to_string(file)
+        .unwrap_or_else(|error| common::error(format!("could not read \"{}\": {}", file, error)));
+
+    if check_only
+    {
+        if let Some(line) = formatter::first_unformatted_line(&source)
+        {
+            println!("{} is not formatted, starting at line {}", file, line);
+            std::process::exit(1);
+        }
+        return
+    }
+
+    let formatted = formatter::format_source(&source.lines().map(String::from).collect::<Vec<String>>());
+    if formatted != source && fs::write(file, formatted).is_err() {
+        common::error(format!("could not write \"{}\"", file));
+    }
+}
+
+// Runs the script under the debugger, which starts paused on the first line
+fn debug_script(file: &str, limits: engine::Limits)
+{
+    let lines = read_lines(file);
+    let tokens = lexer::tokenise_lines(&lines);
+    let instructions = parser::parse_lines(&tokens).unwrap_or_else(|error| common::error(error.message));
+
+    let problems = checker::check(&instructions);
+    if !problems.is_empty() {
+        common::errors(problems);
+    }
+
+    let mut state = engine::State::default();
+    state.set_limits(limits);
+    let debugger = Debugger::new(lines, Box::new(std::io::stdin().lock()), Box::new(std::io::stdout()));
+    state.set_hook(Some(Box::new(debugger)));
+    match state.execute(instructions)
+    {
+        Ok(()) => println!("script finished"),
+        Err(error) if error.kind == ErrorKind::Stopped => {},
+        Err(error) =>
+        {
+            state.io().write_error(&format!("Error: {}\n", error));
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run(debug: bool, test: bool, trace: Option<tracer::TraceOptions>, profiling: Profiling, limits: engine::Limits)
+{
+    let lines = read_lines("./src.txt");
 
     let lexer_output = lexer::tokenise_lines(&lines);
     if debug { println!("=== Lexer ===\n{:#?}\n", lexer_output); }
 
-    let parser_output = parser::parse_lines(&lexer_output);
+    let parser_output = parser::parse_lines(&lexer_output).unwrap_or_else(|error| common::error(error.message));
     if debug { println!("=== Parser ===\n{:#?}\n", parser_output); }
 
+    // Catch what we can before running anything
+    let problems = checker::check(&parser_output);
+    if !problems.is_empty() {
+        common::errors(problems);
+    }
+
+    // Tests are run instead of the script itself
+    if test
+    {
+        let passed = testing::run_tests(&parser_output, &limits, &mut std::io::stdout()).unwrap_or(false);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     let mut state = engine::State::default();
-    state.execute(parser_output);
+    state.set_limits(limits);
+
+    // Traces and profiles go to stderr, so they don't get mixed up with what the script prints
+    let profile = Rc::new(RefCell::new(Profile::default()));
+    match trace
+    {
+        Some(_) if profiling.enabled => common::error("cannot trace and profile at the same time".to_string()),
+        Some(options) => state.set_hook(Some(Box::new(Tracer::new(lines.clone(), parser_output.clone(), options,
+            Box::new(std::io::stderr()))))),
+        None if profiling.enabled => state.set_hook(Some(Box::new(Profiler::new(profile.clone())))),
+        None => {}
+    }
+
+    let result = state.execute(parser_output);
+    if profiling.enabled
+    {
+        profile.borrow_mut().finish();
+        eprint!("{}", profile.borrow().report(&lines));
+        if let Some(file) = profiling.folded
+        {
+            if fs::write(&file, profile.borrow().folded()).is_err() {
+                common::error(format!("could not write \"{}\"", file));
+            }
+        }
+    }
+
+    if let Err(error) = result
+    {
+        state.io().write_error(&format!("Error: {}\n", error));
+        std::process::exit(1);
+    }
     if debug { state.print_variables(); }
 }
\ No newline at end of file
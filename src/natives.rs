@@ -0,0 +1,165 @@
+use super::variables::Variable;
+use super::variables::VariableType;
+use super::variables::value_of_type;
+use super::common::RuntimeError;
+use super::common::ErrorKind;
+use super::common::fail;
+use super::streams::Io;
+use super::stdlib::STDLIB_FUNCTIONS;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+// A function written in Rust that scripts call just like any other, given its arguments already converted to the types
+// it asked for, along with whatever the script is reading from and writing to
+pub type NativeFunction = Rc<dyn Fn(&mut dyn Io, &[Variable]) -> Result<Option<Variable>, RuntimeError>>;
+
+// The types of what a native function takes, where None means anything at all
+#[derive(Clone, Debug)]
+pub enum Parameters
+{
+    Exactly(Vec<Option<VariableType>>),
+    AnyNumber(Option<VariableType>) // Each of the same type
+}
+
+#[derive(Clone)]
+pub struct Native
+{
+    pub parameters: Parameters,
+    pub returns: Option<VariableType>, // None if nothing is returned
+    pub function: NativeFunction
+}
+
+// The native functions available to scripts, which starts with those for input and output along with the standard
+// library. Each interpreter has its own, so that one can be given more functions, or have some taken away (built-in
+// ones included), without affecting any other.
+#[derive(Clone)]
+pub struct Natives
+{
+    functions: HashMap<String, Native>,
+    builtins: HashSet<String> // Standard library functions still available, which are run by the engine itself
+}
+
+impl Default for Natives
+{
+    fn default() -> Natives
+    {
+        let mut natives = Natives::empty();
+        natives.builtins = STDLIB_FUNCTIONS.iter().map(|function| function.to_string()).collect();
+
+        // Each print is written all at once, so that it isn't split up wherever it's shown (e.g. by a debugger)
+        natives.register("print", Parameters::AnyNumber(None), None, |io, arguments|
+        {
+            io.write(&format!("{}\n", printed(arguments)));
+            Ok(None)
+        });
+
+        natives.register("input", Parameters::AnyNumber(None), Some(VariableType::Str(String::new())), |io, arguments|
+        {
+            // Any arguments are a prompt, shown before we take input
+            if !arguments.is_empty() {
+                io.write(&printed(arguments));
+            }
+
+            // Get input itself, without its newline
+            match io.read_line()
+            {
+                Some(input) => Ok(Some(Variable { variable_type: VariableType::Str(input) })),
+                None => fail(ErrorKind::EndOfInput, "no more input to read".to_string())
+            }
+        });
+
+        natives
+    }
+}
+
+impl Natives
+{
+    pub fn empty() -> Natives
+    {
+        Natives { functions: HashMap::new(), builtins: HashSet::new() }
+    }
+
+    // Adds a function, replacing any other of the same name
+    pub fn register<F>(&mut self, name: &str, parameters: Parameters, returns: Option<VariableType>, function: F)
+        where F: Fn(&mut dyn Io, &[Variable]) -> Result<Option<Variable>, RuntimeError> + 'static
+    {
+        let native = Native { parameters, returns, function: Rc::new(function) };
+        self.functions.insert(name.to_string(), native);
+    }
+
+    // Returns if there was a function to remove, which may be one from the standard library
+    pub fn remove(&mut self, name: &str) -> bool
+    {
+        let native = self.functions.remove(name).is_some();
+        let builtin = self.builtins.remove(name);
+        native || builtin
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Native>
+    {
+        self.functions.get(name)
+    }
+
+    pub fn has_builtin(&self, name: &str) -> bool
+    {
+        self.builtins.contains(name)
+    }
+
+    pub fn names(&self) -> Vec<String>
+    {
+        let mut names = self.functions.keys().chain(&self.builtins).cloned().collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+impl Native
+{
+    // Checks the arguments are what the function expects, converting each as a script function would, then makes
+    // sure it returns what it said it would
+    pub fn call(&self, name: &str, arguments: &[Variable], io: &mut dyn Io) -> Result<Option<Variable>, RuntimeError>
+    {
+        let types = match &self.parameters
+        {
+            Parameters::Exactly(types) =>
+            {
+                if types.len() != arguments.len() {
+                    let message = format!("{} expects {} argument(s) but was given {}", name, types.len(), arguments.len());
+                    return fail(ErrorKind::Argument, message)
+                }
+                types.clone()
+            },
+            Parameters::AnyNumber(parameter_type) => vec![parameter_type.clone(); arguments.len()]
+        };
+
+        let mut converted = Vec::<Variable>::new();
+        for i in 0..arguments.len()
+        {
+            converted.push(match &types[i]
+            {
+                Some(parameter_type) => value_of_type(parameter_type, &arguments[i]).map_err(|error| RuntimeError::new(
+                    ErrorKind::Type, format!("argument {} of {} is invalid: {}", i + 1, name, error.message)
+                ))?,
+                None => arguments[i].clone()
+            });
+        }
+
+        let returned = (self.function)(io, &converted)?;
+        match (&self.returns, returned)
+        {
+            (Some(return_type), Some(returned)) => value_of_type(return_type, &returned).map(Some),
+            (None, None) => Ok(None),
+            (Some(_), None) => fail(ErrorKind::Runtime, format!("{} did not return a value", name)),
+            (None, Some(_)) => fail(ErrorKind::Runtime, format!("{} returned a value when it shouldn't", name))
+        }
+    }
+}
+
+// Values printed one after another, as print and input show them
+fn printed(arguments: &[Variable]) -> String
+{
+    arguments.iter().map(|argument| argument.printed_string()).collect()
+}
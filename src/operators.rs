@@ -2,6 +2,8 @@ use super::lexer::Token;
 use super::lexer::TokenType;
 use super::variables::Variable;
 use super::variables::VariableType;
+use super::common::RuntimeError;
+use std::cmp::Ordering;
 
 #[derive(Debug, Clone)]
 pub enum OperatorExpression
@@ -22,33 +24,80 @@ pub fn operator_char_to_token_type(c: char) -> TokenType
     }
 }
 
-fn is_token_operator(token_type: &TokenType) -> bool
+pub fn operator_symbol(token_type: &TokenType) -> &'static str
 {
     match token_type
     {
-        TokenType::Multiply => true,
-        TokenType::Minus => true,
-        TokenType::LessThan => true,
-        TokenType::GreaterThan => true,
-        _ => false
+        TokenType::Multiply => "*",
+        TokenType::Minus => "-",
+        TokenType::LessThan => "<",
+        TokenType::GreaterThan => ">",
+        TokenType::DefaultTo => "??",
+        _ => ""
     }
 }
 
+fn is_token_operator(token_type: &TokenType) -> bool
+{
+    matches!(
+        token_type,
+        TokenType::Multiply | TokenType::Minus | TokenType::LessThan | TokenType::GreaterThan | TokenType::DefaultTo
+    )
+}
+
+// Whether a character is (or is part of) an operator
 pub fn is_char_operator(c: char) -> bool
 {
-    is_token_operator(&operator_char_to_token_type(c))
+    c == '?' || is_token_operator(&operator_char_to_token_type(c))
 }
 
-pub fn value_contains_operator(value: &String) -> bool
+pub fn value_contains_operator(value: &str) -> bool
 {
-    for c in value.chars()
+    top_level_operators(value).next().is_some()
+}
+
+/*
+    Yields the (character) positions of every operator that is not inside a string literal
+    or between brackets, as neither "a-b", foo[i-1] nor bar(i-1) should be split apart when
+    evaluating. Only "??" is more than one character long.
+*/
+pub fn top_level_operators(value: &str) -> impl Iterator<Item = (usize, TokenType)>
+{
+    let chars = value.chars().collect::<Vec<char>>();
+    let mut inside_string = false;
+    let mut bracket_depth = 0;
+    let mut skip_next = false;
+
+    (0..chars.len()).filter_map(move |i|
     {
-        if is_char_operator(c) { return true }
-    }
-    false
+        let c = chars[i];
+        if skip_next {
+            skip_next = false;
+            return None
+        }
+
+        match c
+        {
+            '\"' => inside_string = !inside_string,
+            '[' | '(' | '{' if !inside_string => bracket_depth += 1,
+            ']' | ')' | '}' if !inside_string && bracket_depth > 0 => bracket_depth -= 1,
+            _ => {}
+        }
+
+        if inside_string || bracket_depth != 0 { return None }
+
+        if c == '?' && chars.get(i + 1) == Some(&'?')
+        {
+            skip_next = true;
+            return Some((i, TokenType::DefaultTo))
+        }
+
+        let operator = operator_char_to_token_type(c);
+        if is_token_operator(&operator) { Some((i, operator)) } else { None }
+    })
 }
 
-pub fn tokens_contain_valid_operator(tokens: &Vec<Token>) -> bool
+pub fn tokens_contain_valid_operator(tokens: &[Token]) -> bool
 {
     if tokens.len() < 3 { return false }
 
@@ -87,19 +136,13 @@ pub fn collect_operators(tokens: &mut Vec<Token>)
                 let right_value = tokens.remove(i + 1);
                 let left_value = tokens.remove(i - 1);
 
-                let token = |c|
+                let operator = operator_symbol(&tokens[i-1].token_type);
+                tokens[i - 1] = Token
                 {
-                    Token
-                    {
-                        token_type: TokenType::Value,
-                        string: format!("{}{}{}", left_value.string, c, right_value.string)
-                    }
+                    token_type: TokenType::Value,
+                    string: format!("{}{}{}", left_value.string, operator, right_value.string)
                 };
-
-                     if matches!(tokens[i-1].token_type, TokenType::Multiply)    { tokens[i - 1] = token('*'); break; }
-                else if matches!(tokens[i-1].token_type, TokenType::Minus)       { tokens[i - 1] = token('-'); break; }
-                else if matches!(tokens[i-1].token_type, TokenType::LessThan)    { tokens[i - 1] = token('<'); break; }
-                else if matches!(tokens[i-1].token_type, TokenType::GreaterThan) { tokens[i - 1] = token('>'); break; }
+                break;
             }
 
             i += 1;
@@ -111,16 +154,16 @@ pub fn collect_operators(tokens: &mut Vec<Token>)
     }
 }
 
-pub fn evaluate_operator_expression(expression: &Vec<OperatorExpression>) -> Variable
+pub fn evaluate_operator_expression(expression: &[OperatorExpression]) -> Result<Variable, RuntimeError>
 {
     if let OperatorExpression::Variable(mut initial_variable) = expression[0].clone()
     {
         let mut last_was_variable = true;
         let mut last_operator = TokenType::Multiply;
 
-        for i in 1..expression.len()
+        for item in expression.iter().skip(1)
         {
-            match expression[i].clone()
+            match item.clone()
             {
                 OperatorExpression::Operator(token_type) =>
                 {
@@ -138,19 +181,19 @@ pub fn evaluate_operator_expression(expression: &Vec<OperatorExpression>) -> Var
                     {
                         TokenType::Multiply =>
                         {
-                            initial_variable *= variable;
+                            initial_variable = initial_variable.combine(&variable, isize::checked_mul)?;
                         },
 
                         TokenType::Minus =>
                         {
-                            initial_variable -= variable;
+                            initial_variable = initial_variable.combine(&variable, isize::checked_sub)?;
                         },
 
                         TokenType::LessThan =>
                         {
                             initial_variable = Variable {
                                 variable_type: VariableType::Boolean(
-                                    initial_variable < variable
+                                    initial_variable.compare(&variable)? == Ordering::Less
                                 )
                             }
                         },
@@ -159,11 +202,18 @@ pub fn evaluate_operator_expression(expression: &Vec<OperatorExpression>) -> Var
                         {
                             initial_variable = Variable {
                                 variable_type: VariableType::Boolean(
-                                    initial_variable > variable
+                                    initial_variable.compare(&variable)? == Ordering::Greater
                                 )
                             }
                         },
 
+                        TokenType::DefaultTo =>
+                        {
+                            if matches!(initial_variable.variable_type, VariableType::None) {
+                                initial_variable = variable;
+                            }
+                        },
+
                         _ => { todo!(); }
                     }
 
@@ -172,6 +222,6 @@ pub fn evaluate_operator_expression(expression: &Vec<OperatorExpression>) -> Var
             }
         }
 
-        initial_variable
+        Ok(initial_variable)
     } else { panic!(); }
 }
\ No newline at end of file
@@ -1,21 +1,29 @@
 use super::lexer::Token;
 use super::lexer::TokenType;
 use super::lexer::TokenType::*;
+use super::lexer::KEYWORDS;
+use super::common::ParseError;
 use super::variables::VariableType;
 use super::variables::is_token_type_valid_type;
-use super::variables::token_type_to_variable_type;
-use super::common::error;
+use super::variables::token_to_variable_type;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Instruction
 {
     NoOp,
 
     // Loops
-    FromValueToValue { value: String, start: String, end: String },
+    FromValueToValue { value: String, start: String, end: String, last_line: usize },
+    ForEach { key: String, value: Option<String>, collection: String, last_line: usize },
     IfValue { left_value: String, last_line: usize },
     IfValueIsValue { left_value: String, right_value: String, last_line: usize },
     IfValueIsNotValue { left_value: String, right_value: String, last_line: usize },
+    Match { value: String, arms: Vec<MatchArm>, last_line: usize },
+    Case, // Only ever jumped past, as the match decides which case to run
+    Try { catch_line: usize, last_line: usize },
+    Catch { name: Option<String> }, // Reached without an error only when the try block finishes
+    Throw { value: String },
+    Test { name: String, last_line: usize }, // Skipped unless testing
     Done,
 
     // Functions
@@ -23,152 +31,306 @@ pub enum Instruction
     FunctionCall { function: String, values: Vec<String>, target_variable: Option<String> },
     Return { value: String },
 
+    // Structs
+    StructDeclaration { name: String, fields: Vec<(String, VariableType)> },
+    StructInstanceDeclaration { struct_name: String, name: String, value: String, optional: bool },
+    EnumDeclaration { name: String, variants: Vec<(String, Vec<(String, VariableType)>)> },
+
     // Variables
-    IntDeclaration { name: String, value: String },
-    BoolDeclaration { name: String, value: String },
-    StringDeclaration { name: String, value: String },
-    ArrayDeclaration { name: String },
+    IntDeclaration { name: String, value: String, optional: bool }, // Optional if it may also be none (e.g. "int?")
+    BoolDeclaration { name: String, value: String, optional: bool },
+    StringDeclaration { name: String, value: String, optional: bool },
+    ConstDeclaration { name: String, variable_type: VariableType, value: String },
+    LetDeclaration { name: String, value: String }, // Takes its type from its value
+    ArrayDeclaration { name: String, element_type: Option<VariableType>, value: Option<String> },
+    MapDeclaration { name: String, value_type: Option<VariableType>, value: Option<String> },
     Assignment { name: String, value: String }
 }
 
-pub fn parse_lines(lines: &Vec<Vec<Token>>) -> Vec<Instruction>
+// One case of a match statement, binding the values carried by a variant to the given names. A variant of "_"
+// matches anything.
+#[derive(Debug, Clone)]
+pub struct MatchArm
+{
+    pub variant: String,
+    pub bindings: Vec<String>,
+    pub first_line: usize,
+    pub last_line: usize
+}
+
+// Gives the message for the first line that can't be parsed, if any
+pub fn parse_lines(lines: &[Vec<Token>]) -> Result<Vec<Instruction>, ParseError>
 {
     let mut instructions = Vec::<Instruction>::new();
+    let mut enum_end = Option::<usize>::None; // Enum variants are parsed along with the enum itself
 
     for i in 0..lines.len()
     {
         let tokens = &lines[i];
 
-        if tokens.is_empty() {
+        if tokens.is_empty() || enum_end.is_some_and(|end| i <= end) {
             instructions.push(Instruction::NoOp);
         }
 
-        else if tokens_contain_types(&tokens, &vec![For, Value, From, Value, To, Value])
+        else if tokens_contain_types(tokens, &[For, Value, From, Value, To, Value])
         {
             instructions.push(Instruction::FromValueToValue {
                 value: tokens[1].string.clone(),
                 start: tokens[3].string.clone(),
-                end: tokens[5].string.clone()
+                end: tokens[5].string.clone(),
+                last_line: get_corresponding_end_of_frame(lines, i)?
+            });
+        }
+
+        else if tokens_contain_types(tokens, &[For, Value, In, Value])
+        {
+            instructions.push(Instruction::ForEach {
+                key: tokens[1].string.clone(),
+                value: None,
+                collection: tokens[3].string.clone(),
+                last_line: get_corresponding_end_of_frame(lines, i)?
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![If, Value])
+        else if tokens_contain_types(tokens, &[For, Value, Value, In, Value])
+        {
+            instructions.push(Instruction::ForEach {
+                key: tokens[1].string.clone(),
+                value: Some(tokens[2].string.clone()),
+                collection: tokens[4].string.clone(),
+                last_line: get_corresponding_end_of_frame(lines, i)?
+            });
+        }
+
+        else if tokens_contain_types(tokens, &[If, Value])
         {
             instructions.push(Instruction::IfValue {
                 left_value: tokens[1].string.clone(),
-                last_line: get_corresponding_end_of_frame(lines, i)
+                last_line: get_corresponding_end_of_frame(lines, i)?
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![If, Value, Is, Value])
+        else if tokens_contain_types(tokens, &[If, Value, Is, Value])
         {
             instructions.push(Instruction::IfValueIsValue {
                 left_value: tokens[1].string.clone(),
                 right_value: tokens[3].string.clone(),
-                last_line: get_corresponding_end_of_frame(lines, i)
+                last_line: get_corresponding_end_of_frame(lines, i)?
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![If, Value, Is, Not, Value])
+        else if tokens_contain_types(tokens, &[If, Value, Is, Not, Value])
         {
             instructions.push(Instruction::IfValueIsNotValue {
                 left_value: tokens[1].string.clone(),
                 right_value: tokens[4].string.clone(),
-                last_line: get_corresponding_end_of_frame(lines, i)
+                last_line: get_corresponding_end_of_frame(lines, i)?
+            });
+        }
+
+        else if tokens_contain_types(tokens, &[Match, Value])
+        {
+            let last_line = get_corresponding_end_of_frame(lines, i)?;
+            instructions.push(Instruction::Match {
+                value: tokens[1].string.clone(),
+                arms: parse_match_arms(lines, i, last_line)?,
+                last_line
+            });
+        }
+
+        else if tokens_begins_with_types(tokens, &[Case]) {
+            instructions.push(Instruction::Case);
+        }
+
+        else if tokens_contain_types(tokens, &[Try])
+        {
+            let last_line = get_corresponding_end_of_frame(lines, i)?;
+            instructions.push(Instruction::Try {
+                catch_line: find_catch(lines, i, last_line)?,
+                last_line
+            });
+        }
+
+        else if tokens_contain_types(tokens, &[Catch]) {
+            instructions.push(Instruction::Catch { name: None });
+        }
+
+        else if tokens_contain_types(tokens, &[Catch, Value]) {
+            instructions.push(Instruction::Catch { name: Some(tokens[1].string.clone()) });
+        }
+
+        else if tokens_contain_types(tokens, &[Throw, Value]) {
+            instructions.push(Instruction::Throw { value: tokens[1].string.clone() });
+        }
+
+        else if tokens_contain_types(tokens, &[Test, Value])
+        {
+            // Tests are named by a string (e.g. 'test "adds numbers"')
+            let name = &tokens[1].string;
+            if name.len() < 2 || !name.starts_with('"') || !name.ends_with('"') {
+                return Err(ParseError::new(format!("test on line {} must be named by a string", i + 1), i))
+            }
+
+            instructions.push(Instruction::Test {
+                name: name[1..name.len()-1].to_string(),
+                last_line: get_corresponding_end_of_frame(lines, i)?
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Done]) {
+        else if tokens_contain_types(tokens, &[Done]) {
             instructions.push(Instruction::Done);
         }
 
-        else if tokens_begins_with_types(&tokens, &vec![Function, Value])
+        else if tokens_begins_with_types(tokens, &[Function, Value])
         {
             // Parse arguments, if any
-            let mut arguments = Vec::<(String, VariableType)>::new();
-            if tokens_begins_with_types(&tokens, &vec![Function, Value, Colon])
+            let arguments = if tokens_begins_with_types(tokens, &[Function, Value, Colon]) {
+                parse_typed_names(&tokens[3..], "function declaration", i)?
+            } else {
+                Vec::new()
+            };
+
+            instructions.push(Instruction::FunctionDeclaration {
+                name: tokens[1].string.clone(),
+                first_line: i,
+                last_line: get_corresponding_end_of_frame(lines, i)?,
+                arguments
+            });
+        }
+
+        else if tokens_begins_with_types(tokens, &[Struct, Value])
+        {
+            // Fields are declared just like function arguments (e.g. "struct Point: int x || int y")
+            let fields = if tokens_begins_with_types(tokens, &[Struct, Value, Colon]) {
+                parse_typed_names(&tokens[3..], "struct declaration", i)?
+            } else if tokens.len() == 2 {
+                Vec::new()
+            } else {
+                return Err(ParseError::new(format!("malformed struct declaration on line {}", i + 1), i))
+            };
+
+            instructions.push(Instruction::StructDeclaration {
+                name: tokens[1].string.clone(),
+                fields
+            });
+        }
+
+        else if tokens_contain_types(tokens, &[Enum, Value])
+        {
+            // Variants follow on their own lines, each with any values they carry declared like function
+            // arguments (e.g. "Rectangle: int width || int height")
+            let last_line = get_corresponding_end_of_frame(lines, i)?;
+            let mut variants = Vec::<(String, Vec<(String, VariableType)>)>::new();
+
+            for (j, variant) in lines.iter().enumerate().take(last_line).skip(i + 1)
             {
-                // Remove separating pipes
-                let mut arg_tokens = tokens[3..tokens.len()].iter().collect::<Vec<&Token>>();
-                arg_tokens.retain(|token| {
-                    !matches!(token.token_type, TokenType::DoublePipe)
-                });
-
-                // Ensure valid types and non-overlapping variable names
-                let mut variable_types = Vec::<VariableType>::new();
-                let mut variable_names = Vec::<String>::new();
-                for j in 0..arg_tokens.len()
-                {
-                    if j % 2 == 0
-                    {
-                        if !is_token_type_valid_type(&arg_tokens[j].token_type) {
-                            error(format!("unknown variable type in function declaration on line {}", i + 1));
-                        }
-
-                        variable_types.push(token_type_to_variable_type(&arg_tokens[j].token_type));
-                    }
-
-                    else if j % 2 == 1
-                    {
-                        if variable_names.contains(&arg_tokens[j].string) {
-                            error(format!("duplicate variable name in function declaration on line {}", i+1));
-                        }
-
-                        variable_names.push(arg_tokens[j].string.clone());
-                    }
+                if variant.is_empty() { continue }
+
+                let fields = if tokens_contain_types(variant, &[Value]) {
+                    Vec::new()
+                } else if tokens_begins_with_types(variant, &[Value, Colon]) {
+                    parse_typed_names(&variant[2..], "enum variant", j)?
+                } else {
+                    return Err(ParseError::new(format!("malformed enum variant on line {}", j + 1), j))
+                };
+
+                if variants.iter().any(|(name, _)| *name == variant[0].string) {
+                    return Err(ParseError::new(format!("duplicate variant in enum on line {}", j + 1), j))
                 }
+                variants.push((variant[0].string.clone(), fields));
+            }
 
-                if variable_types.len() != variable_names.len() {
-                    error(format!("unbalanced arguments in function declaration on line {}", i+1));
-                }
+            instructions.push(Instruction::EnumDeclaration {
+                name: tokens[1].string.clone(),
+                variants
+            });
+            enum_end = Some(last_line);
+        }
 
-                // Combine into tuple
-                for j in 0..variable_types.len()
-                {
-                    arguments.push((variable_names[j].clone(), variable_types[j].clone()));
-                }
+        else if tokens_begins_with_types(tokens, &[Const])
+        {
+            // Constants are always of a simple type (e.g. "const int limit = 10 * 3")
+            let is_valid = tokens.len() == 5 && is_token_type_valid_type(&tokens[1].token_type) &&
+                tokens_ends_with_type(tokens, &[Value, Equals, Value]);
+            if !is_valid {
+                return Err(ParseError::new(format!("malformed constant declaration on line {}", i + 1), i))
             }
 
-            instructions.push(Instruction::FunctionDeclaration {
+            instructions.push(Instruction::ConstDeclaration {
+                name: tokens[2].string.clone(),
+                variable_type: token_to_variable_type(&tokens[1]).unwrap(),
+                value: tokens[4].string.clone()
+            });
+        }
+
+        else if tokens_contain_types(tokens, &[Let, Value, Equals, Value])
+        {
+            instructions.push(Instruction::LetDeclaration {
                 name: tokens[1].string.clone(),
-                first_line: i,
-                last_line: get_corresponding_end_of_frame(lines, i),
-                arguments
+                value: tokens[3].string.clone()
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Int, Value, Equals, Value])
+        else if tokens_contain_types(tokens, &[Int, Value, Equals, Value])
         {
             instructions.push(Instruction::IntDeclaration {
                 name: tokens[1].string.clone(),
-                value: tokens[3].string.clone()
+                value: tokens[3].string.clone(),
+                optional: tokens[0].string.ends_with('?')
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Bool, Value, Equals, Value])
+        else if tokens_contain_types(tokens, &[Bool, Value, Equals, Value])
         {
             instructions.push(Instruction::BoolDeclaration {
                 name: tokens[1].string.clone(),
-                value: tokens[3].string.clone()
+                value: tokens[3].string.clone(),
+                optional: tokens[0].string.ends_with('?')
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Str, Value, Equals, Value])
+        else if tokens_contain_types(tokens, &[Str, Value, Equals, Value])
         {
             instructions.push(Instruction::StringDeclaration {
                 name: tokens[1].string.clone(),
-                value: tokens[3].string.clone()
+                value: tokens[3].string.clone(),
+                optional: tokens[0].string.ends_with('?')
+            });
+        }
+
+        else if tokens_begins_with_types(tokens, &[Array]) || tokens_begins_with_types(tokens, &[Map])
+        {
+            // Arrays and maps are declared the same way, optionally with the type of what they hold and an
+            // initial value (e.g. "array foo", "array int foo" or "map int foo = {"a": 1}")
+            let is_typed = tokens.len() > 1 && is_token_type_valid_type(&tokens[1].token_type);
+            let rest = &tokens[if is_typed { 2 } else { 1 }..];
+            let types = rest.iter().map(|t| t.token_type.clone()).collect::<Vec<TokenType>>();
+
+            let value = if types == vec![Value] { None }
+                else if types == vec![Value, Equals, Value] { Some(rest[2].string.clone()) }
+                else { return Err(ParseError::new(format!("malformed declaration on line {}", i + 1), i)) };
+
+            let name = rest[0].string.clone();
+            let held_type = if is_typed { token_to_variable_type(&tokens[1]) } else { None };
+
+            instructions.push(if tokens[0].token_type == Array {
+                Instruction::ArrayDeclaration { name, element_type: held_type, value }
+            } else {
+                Instruction::MapDeclaration { name, value_type: held_type, value }
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Array, Value])
+        else if tokens_contain_types(tokens, &[Value, Value, Equals, Value])
         {
-            instructions.push(Instruction::ArrayDeclaration {
-                name: tokens[1].string.clone()
+            instructions.push(Instruction::StructInstanceDeclaration {
+                struct_name: tokens[0].string.trim_end_matches('?').to_string(),
+                name: tokens[1].string.clone(),
+                value: tokens[3].string.clone(),
+                optional: tokens[0].string.ends_with('?')
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Value, Equals, Value])
+        else if tokens_contain_types(tokens, &[Value, Equals, Value])
         {
             instructions.push(Instruction::Assignment {
                 name: tokens[0].string.clone(),
@@ -176,33 +338,35 @@ pub fn parse_lines(lines: &Vec<Vec<Token>>) -> Vec<Instruction>
             });
         }
 
-        else if tokens_begins_with_types(&tokens, &vec![Value, LeftBracket]) &&
-                tokens_ends_with_type(&tokens, &vec![RightBracket])
+        else if tokens_begins_with_types(tokens, &[Value, LeftBracket]) &&
+                tokens_ends_with_type(tokens, &[RightBracket])
         {
             let arguments: Vec<String> = tokens[2..tokens.len()-1].
                                             iter().map(|t| t.string.clone()).collect();
 
+            let (function, values) = split_method_call(&tokens[0].string, arguments);
             instructions.push(Instruction::FunctionCall {
-                function: tokens[0].string.clone(),
-                values: arguments.clone(),
+                function,
+                values,
                 target_variable: None
             });
         }
 
-        else if tokens_begins_with_types(&tokens, &vec![Value, LeftBracket]) &&
-                tokens_ends_with_type(&tokens, &vec![RightBracket, RightArrow, Value])
+        else if tokens_begins_with_types(tokens, &[Value, LeftBracket]) &&
+                tokens_ends_with_type(tokens, &[RightBracket, RightArrow, Value])
         {
             let arguments: Vec<String> = tokens[2..tokens.len()-3].
                 iter().map(|t| t.string.clone()).collect();
 
+            let (function, values) = split_method_call(&tokens[0].string, arguments);
             instructions.push(Instruction::FunctionCall {
-                function: tokens[0].string.clone(),
-                values: arguments.clone(),
+                function,
+                values,
                 target_variable: Some(tokens[tokens.len()-1].string.clone())
             });
         }
 
-        else if tokens_contain_types(&tokens, &vec![Return, Value])
+        else if tokens_contain_types(tokens, &[Return, Value])
         {
             instructions.push(Instruction::Return {
                 value: tokens[1].string.clone()
@@ -210,14 +374,180 @@ pub fn parse_lines(lines: &Vec<Vec<Token>>) -> Vec<Instruction>
         }
 
         else {
-            error(format!("unknown instruction on line {}:\n{:#?}", i + 1, lines[i]));
+            return Err(ParseError::new(format!("unknown instruction on line {}:\n{:#?}", i + 1, lines[i]), i))
+        }
+    }
+
+    // Keywords can't be names, as some (e.g. true and false) are values in their own right, and the rest have
+    // meanings of their own
+    for (i, instruction) in instructions.iter().enumerate()
+    {
+        if let Some((line, name)) = names_in(instruction, i).into_iter().find(|(_, name)| KEYWORDS.contains(name)) {
+            let message = format!("\"{}\" is a keyword, so cannot be used as a name on line {}", name, line + 1);
+            return Err(ParseError::new(message, line))
+        }
+    }
+
+    Ok(instructions)
+}
+
+// Every name an instruction declares, calls or assigns to, along with the line it's on
+fn names_in(instruction: &Instruction, line: usize) -> Vec<(usize, &str)>
+{
+    let names = match instruction
+    {
+        Instruction::FromValueToValue { value, .. } => vec![value.as_str()],
+        Instruction::ForEach { key, value, .. } => [Some(key), value.as_ref()].into_iter().flatten().map(String::as_str).collect(),
+        Instruction::Catch { name } => name.iter().map(String::as_str).collect(),
+        Instruction::FunctionDeclaration { name, arguments, .. } => [vec![name.as_str()], typed_names(arguments)].concat(),
+        Instruction::FunctionCall { function, target_variable, .. } =>
+            [Some(function), target_variable.as_ref()].into_iter().flatten().map(String::as_str).collect(),
+        Instruction::StructDeclaration { name, fields } => [vec![name.as_str()], typed_names(fields)].concat(),
+        Instruction::EnumDeclaration { name, variants } =>
+        {
+            let mut names = vec![name.as_str()];
+            for (variant, fields) in variants
+            {
+                names.push(variant);
+                names.extend(typed_names(fields));
+            }
+            names
+        },
+        Instruction::StructInstanceDeclaration { name, .. } | Instruction::IntDeclaration { name, .. } |
+        Instruction::BoolDeclaration { name, .. } | Instruction::StringDeclaration { name, .. } |
+        Instruction::ConstDeclaration { name, .. } | Instruction::LetDeclaration { name, .. } |
+        Instruction::ArrayDeclaration { name, .. } | Instruction::MapDeclaration { name, .. } => vec![name.as_str()],
+
+        // Only the variable itself, rather than any field or element of it (e.g. "point" in "point.x = 3")
+        Instruction::Assignment { name, .. } => vec![name.split(['.', '[']).next().unwrap_or_default()],
+
+        // Case bindings are on lines of their own
+        Instruction::Match { arms, .. } => return arms.iter()
+            .flat_map(|arm| arm.bindings.iter().map(|binding| (arm.first_line, binding.as_str())))
+            .collect(),
+
+        _ => Vec::new()
+    };
+
+    names.into_iter().map(|name| (line, name)).collect()
+}
+
+fn typed_names(names: &[(String, VariableType)]) -> Vec<&str>
+{
+    names.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+// Each case of a match must be directly inside it, and looks like "case Rectangle width height"
+fn parse_match_arms(lines: &[Vec<Token>], first_line: usize, last_line: usize) -> Result<Vec<MatchArm>, ParseError>
+{
+    let mut arms = Vec::<MatchArm>::new();
+    let mut i = first_line + 1;
+
+    while i < last_line
+    {
+        let tokens = &lines[i];
+        if tokens.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        if tokens.len() < 2 || tokens[0].token_type != Case || tokens[1..].iter().any(|t| t.token_type != Value) {
+            return Err(ParseError::new(format!("expected case in match on line {}", i + 1), i))
+        }
+
+        let variant = tokens[1].string.clone();
+        let bindings = tokens[2..].iter().map(|t| t.string.clone()).collect::<Vec<String>>();
+        if variant == "_" && !bindings.is_empty() {
+            return Err(ParseError::new(format!("wildcard case cannot bind values on line {}", i + 1), i))
+        }
+
+        let arm_end = get_corresponding_end_of_frame(lines, i)?;
+        arms.push(MatchArm { variant, bindings, first_line: i, last_line: arm_end });
+        i = arm_end + 1;
+    }
+
+    Ok(arms)
+}
+
+// A try block is split in two by a catch directly inside it (i.e. not one belonging to a nested try)
+fn find_catch(lines: &[Vec<Token>], first_line: usize, last_line: usize) -> Result<usize, ParseError>
+{
+    let mut i = first_line + 1;
+
+    while i < last_line
+    {
+        match lines[i].first().map(|t| &t.token_type)
+        {
+            Some(Catch) => return Ok(i),
+            Some(token_type) if FRAME_TOKENS.contains(token_type) => i = get_corresponding_end_of_frame(lines, i)? + 1,
+            _ => i += 1
+        }
+    }
+
+    Err(ParseError::new(format!("try on line {} has no catch", first_line + 1), first_line))
+}
+
+// Parses a list of the form "type name || type name", as used by function arguments and struct fields
+fn parse_typed_names(tokens: &[Token], context: &str, line: usize) -> Result<Vec<(String, VariableType)>, ParseError>
+{
+    // Remove separating pipes
+    let mut name_tokens = tokens.iter().collect::<Vec<&Token>>();
+    name_tokens.retain(|token| {
+        !matches!(token.token_type, TokenType::DoublePipe)
+    });
+
+    // Ensure valid types and non-overlapping names
+    let mut variable_types = Vec::<VariableType>::new();
+    let mut variable_names = Vec::<String>::new();
+    for (j, token) in name_tokens.iter().enumerate()
+    {
+        if j % 2 == 0
+        {
+            match token_to_variable_type(token)
+            {
+                Some(variable_type) => variable_types.push(variable_type),
+                None =>
+                {
+                    let message = format!("unknown variable type in {} on line {}", context, line + 1);
+                    return Err(ParseError::new(message, line))
+                }
+            }
+        }
+
+        else if j % 2 == 1
+        {
+            if variable_names.contains(&token.string) {
+                let message = format!("duplicate variable name in {} on line {}", context, line + 1);
+                return Err(ParseError::new(message, line))
+            }
+
+            variable_names.push(token.string.clone());
         }
     }
 
-    instructions
+    if variable_types.len() != variable_names.len() {
+        return Err(ParseError::new(format!("unbalanced arguments in {} on line {}", context, line + 1), line))
+    }
+
+    // Combine into tuples
+    Ok(variable_names.into_iter().zip(variable_types).collect())
 }
 
-fn tokens_contain_types(line: &Vec<Token>, types: &Vec<TokenType>) -> bool
+// Turns method calls like "foo.push(3)" into their plain form, "push(foo 3)"
+pub fn split_method_call(function: &str, mut arguments: Vec<String>) -> (String, Vec<String>)
+{
+    match function.rsplit_once('.')
+    {
+        Some((receiver, method)) if !receiver.is_empty() && !method.is_empty() =>
+        {
+            arguments.insert(0, receiver.to_string());
+            (method.to_string(), arguments)
+        },
+        _ => (function.to_string(), arguments)
+    }
+}
+
+fn tokens_contain_types(line: &[Token], types: &[TokenType]) -> bool
 {
     if line.len() != types.len() { return false }
     for i in 0..types.len()  {
@@ -226,7 +556,7 @@ fn tokens_contain_types(line: &Vec<Token>, types: &Vec<TokenType>) -> bool
     true
 }
 
-fn tokens_begins_with_types(line: &Vec<Token>, types: &Vec<TokenType>) -> bool
+fn tokens_begins_with_types(line: &[Token], types: &[TokenType]) -> bool
 {
     if line.len() < types.len() { return false }
     for i in 0..types.len() {
@@ -235,7 +565,7 @@ fn tokens_begins_with_types(line: &Vec<Token>, types: &Vec<TokenType>) -> bool
     true
 }
 
-fn tokens_ends_with_type(line: &Vec<Token>, types: &Vec<TokenType>) -> bool
+fn tokens_ends_with_type(line: &[Token], types: &[TokenType]) -> bool
 {
     if line.len() < types.len() { return false }
     let first_tested_element = line.len() - types.len();
@@ -250,24 +580,26 @@ fn tokens_ends_with_type(line: &Vec<Token>, types: &Vec<TokenType>) -> bool
     true
 }
 
-fn get_corresponding_end_of_frame(lines: &Vec<Vec<Token>>, line: usize) -> usize
+// Lines beginning with these are each ended by a "done"
+pub const FRAME_TOKENS: [TokenType; 8] = [For, If, Function, Enum, Match, Case, Try, Test];
+
+pub fn get_corresponding_end_of_frame(lines: &[Vec<Token>], line: usize) -> Result<usize, ParseError>
 {
-    let frame_tokens = vec![For, If, Function];
     let mut inner_frames = 1;
 
-    for i in (line+1)..lines.len()
+    for (i, tokens) in lines.iter().enumerate().skip(line + 1)
     {
-        if lines[i].len() != 0
+        if !tokens.is_empty()
         {
-            let first_token = &lines[i][0].token_type;
-            if frame_tokens.contains(&first_token) { inner_frames += 1; }
+            let first_token = &tokens[0].token_type;
+            if FRAME_TOKENS.contains(first_token) { inner_frames += 1; }
             else if matches!(first_token, Done) { inner_frames -= 1; }
 
             if inner_frames == 0 {
-                return i;
+                return Ok(i);
             }
         }
     }
 
-    error(format!("frame declared on line {} does not terminate", line));
+    Err(ParseError::new(format!("frame declared on line {} does not terminate", line + 1), line))
 }
\ No newline at end of file
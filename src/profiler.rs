@@ -0,0 +1,161 @@
+use super::engine::Hook;
+use super::engine::State;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+/*
+    Measures where a script spends its time. The time from one line starting to the next is put down to the first
+    (with whatever's left of a line once a function called from within it returns put back down to that line), and to
+    the functions being run at the time: all of it to the innermost one (exclusive time), and to each of them once
+    (inclusive time, so recursion isn't counted twice). Time is also kept per stack of calls, which is written out
+    folded (e.g. "<script>;outer;inner 120", in microseconds) for flamegraph tools.
+*/
+
+// What the top level is called in folded stacks
+const ROOT: &str = "<script>";
+
+#[derive(Default)]
+struct LineStats
+{
+    runs: u64,
+    time: Duration
+}
+
+#[derive(Default)]
+struct FunctionStats
+{
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration
+}
+
+#[derive(Default)]
+pub struct Profile
+{
+    lines: HashMap<usize, LineStats>,
+    functions: HashMap<String, FunctionStats>,
+    stacks: HashMap<String, Duration>, // By folded stack
+    current: Option<(usize, Vec<String>, Instant)>, // The line being run, the functions it's in, and when it started
+    calls: u64 // How many calls the script had made as of the current line
+}
+
+impl Profile
+{
+    // Puts the time since the current line started down to it
+    fn record(&mut self, now: Instant)
+    {
+        let Some((line, stack, started)) = self.current.take() else { return };
+        let elapsed = now - started;
+
+        self.lines.entry(line).or_default().time += elapsed;
+
+        for i in 0..stack.len()
+        {
+            if !stack[..i].contains(&stack[i]) {
+                self.functions.entry(stack[i].clone()).or_default().inclusive += elapsed;
+            }
+        }
+        if let Some(innermost) = stack.last() {
+            self.functions.entry(innermost.clone()).or_default().exclusive += elapsed;
+        }
+
+        let folded = std::iter::once(ROOT.to_string()).chain(stack).collect::<Vec<String>>().join(";");
+        *self.stacks.entry(folded).or_default() += elapsed;
+    }
+
+    // Puts the time so far down to the last line run, once the script has finished
+    pub fn finish(&mut self)
+    {
+        self.record(Instant::now());
+    }
+
+    // Lines and functions, each with the most time spent first
+    pub fn report(&self, source: &[String]) -> String
+    {
+        let mut lines = self.lines.iter().collect::<Vec<(&usize, &LineStats)>>();
+        lines.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+
+        let mut report = String::from("=== Profile ===\n");
+        report += "Lines, by time spent on them (not counting functions they call)\n";
+        report += &format!("{:>6} {:>8} {:>12}  source\n", "line", "runs", "time");
+        for (line, stats) in lines
+        {
+            let text = source.get(*line).map(|text| text.trim()).unwrap_or_default();
+            report += &format!("{:>6} {:>8} {:>12}  {}\n", line + 1, stats.runs, milliseconds(stats.time), text);
+        }
+
+        let mut functions = self.functions.iter().collect::<Vec<(&String, &FunctionStats)>>();
+        functions.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+
+        let width = functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("function".len());
+        report += "\nFunctions, by time spent within them (inclusive of functions they call)\n";
+        report += &format!("{:<width$} {:>8} {:>12} {:>12}\n", "function", "calls", "inclusive", "exclusive");
+        for (name, stats) in functions
+        {
+            let (inclusive, exclusive) = (milliseconds(stats.inclusive), milliseconds(stats.exclusive));
+            report += &format!("{:<width$} {:>8} {:>12} {:>12}\n", name, stats.calls, inclusive, exclusive);
+        }
+
+        report
+    }
+
+    // A line per stack of calls, with the time spent in it, as read by flamegraph tools
+    pub fn folded(&self) -> String
+    {
+        let mut stacks = self.stacks.iter().collect::<Vec<(&String, &Duration)>>();
+        stacks.sort();
+        stacks.iter().map(|(stack, time)| format!("{} {}\n", stack, time.as_micros())).collect()
+    }
+}
+
+fn milliseconds(time: Duration) -> String
+{
+    format!("{:.3}ms", time.as_secs_f64() * 1000.0)
+}
+
+// Fills in a profile shared with whatever reports it once the script has finished
+pub struct Profiler
+{
+    profile: Rc<RefCell<Profile>>
+}
+
+impl Profiler
+{
+    pub fn new(profile: Rc<RefCell<Profile>>) -> Profiler
+    {
+        Profiler { profile }
+    }
+}
+
+impl Hook for Profiler
+{
+    fn before_line(&mut self, state: &mut State)
+    {
+        let mut profile = self.profile.borrow_mut();
+        profile.record(Instant::now());
+
+        // Every call starts on a line of its own, so any made since the last line are the innermost ones
+        let stack = state.call_stack();
+        let new_calls = state.calls_made().saturating_sub(profile.calls) as usize;
+        for name in &stack[stack.len().saturating_sub(new_calls)..] {
+            profile.functions.entry(name.clone()).or_default().calls += 1;
+        }
+        profile.calls = state.calls_made();
+        profile.lines.entry(state.line()).or_default().runs += 1;
+
+        // Start timing after the bookkeeping, so it isn't put down to the line
+        profile.current = Some((state.line(), stack, Instant::now()));
+    }
+
+    // The rest of the line the function was called from is that line's own time, not the function's
+    fn returned(&mut self, state: &State)
+    {
+        let mut profile = self.profile.borrow_mut();
+        profile.record(Instant::now());
+        profile.current = Some((state.line(), state.call_stack(), Instant::now()));
+    }
+}
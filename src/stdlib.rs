@@ -1,39 +1,533 @@
 use super::variables::Variable;
+use super::common::RuntimeError;
+use super::common::ErrorKind;
+use super::common::fail;
+use super::common::check_size;
 use crate::variables::VariableType;
-use std::io;
-use std::io::Write;
+use crate::variables::Array;
+use crate::variables::Map;
+use crate::variables::MapKey;
 
-// Returns if the function exists, followed by an optional variable returned
-pub fn stdlib_function(function: &str, arguments: &Vec<Variable>) -> (bool, Option<Variable>)
+use std::cmp::Ordering;
+
+// The collection a function's first argument refers to (if it does), for functions that modify it in-place
+pub enum Receiver<'a>
+{
+    Array(&'a mut Array),
+    Map(&'a mut Map)
+}
+
+// The names of every function here, for editors to offer
+pub const STDLIB_FUNCTIONS: [&str; 25] = [
+    "len", "keys", "values", "has_key", "remove", "contains", "slice", "push", "pop", "insert", "sort", "reverse",
+    "upper", "lower", "trim", "split", "join", "starts_with", "ends_with", "find", "replace", "repeat", "assert",
+    "assert_eq", "assert_error"
+];
+
+// Returns if the function exists, followed by an optional variable returned, or an error message. No array or string
+// made may be larger than the maximum size given, if any.
+pub fn stdlib_function(function: &str, arguments: &[Variable], receiver: Option<Receiver>, max_size: Option<usize>)
+    -> Result<(bool, Option<Variable>), RuntimeError>
 {
+    if assert_function(function, arguments)? {
+        return Ok((true, None))
+    }
+
+    let (map_receiver, array_receiver) = match receiver
+    {
+        Some(Receiver::Map(map)) => (Some(map), None),
+        Some(Receiver::Array(array)) => (None, Some(array)),
+        None => (None, None)
+    };
+
+    let map_result = map_function(function, arguments, map_receiver)?;
+    if map_result.0 {
+        return Ok(map_result)
+    }
+
+    let array_result = array_function(function, arguments, array_receiver, max_size)?;
+    if array_result.0 {
+        return Ok(array_result)
+    }
+
+    match string_function(function, arguments, max_size)?
+    {
+        Some(result) => Ok((true, Some(result))),
+        None => Ok((false, None))
+    }
+}
+
+// Map functions, which all take the map to operate on as their first argument. Those shared with other types
+// (e.g. "len") are left for the others if not given a map.
+fn map_function(function: &str, arguments: &[Variable], receiver: Option<&mut Map>) -> Result<(bool, Option<Variable>), RuntimeError>
+{
+    let map = match arguments.first().map(|a| &a.variable_type)
+    {
+        Some(VariableType::Map(map)) => map,
+        _ => return match function
+        {
+            "keys" | "values" | "has_key" =>
+                fail(ErrorKind::Type, format!("{} expects a map as its first argument", function)),
+            _ => Ok((false, None))
+        }
+    };
+
+    let result = match function
+    {
+        "len" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            integer(map.len())
+        },
+
+        "keys" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            array(map.entries().iter().map(|(key, _)| key.to_variable()).collect())
+        },
+
+        "values" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            array(map.entries().iter().map(|(_, value)| value.clone()).collect())
+        },
+
+        "has_key" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            boolean(map.get(&MapKey::from_variable(&arguments[1])?).is_some())
+        },
+
+        "remove" =>
+        {
+            // Modifies the map in-place, so it must be a variable rather than a temporary value
+            expect_arguments(function, arguments, 2)?;
+            let key = MapKey::from_variable(&arguments[1])?;
+            let Some(map) = receiver else {
+                return fail(ErrorKind::Type, "remove expects a map variable as its first argument".to_string())
+            };
+
+            match map.remove(&key)
+            {
+                Some(value) => value,
+                None => return fail(
+                    ErrorKind::Key, format!("key \"{}\" not found in map", key.to_variable().printed_string())
+                )
+            }
+        },
+
+        _ => return Ok((false, None))
+    };
+
+    Ok((true, Some(result)))
+}
+
+// Array functions, which all take the array to operate on as their first argument. Those shared with strings
+// (e.g. "contains") are left for string_function if not given an array.
+fn array_function(function: &str, arguments: &[Variable], receiver: Option<&mut Array>, max_size: Option<usize>)
+    -> Result<(bool, Option<Variable>), RuntimeError>
+{
+    let is_array = matches!(arguments.first().map(|a| &a.variable_type), Some(VariableType::Array(_)));
+
+    match function
+    {
+        "len" | "contains" if !is_array => return Ok((false, None)),
+        "len" | "contains" | "slice" | "push" | "pop" | "insert" | "remove" | "sort" | "reverse" => {},
+        _ => return Ok((false, None))
+    }
+
+    if !is_array {
+        return fail(ErrorKind::Type, format!("{} expects an array as its first argument", function))
+    }
+
+    // Functions that only read from the array can just use the copy
+    let array = match &arguments[0].variable_type
+    {
+        VariableType::Array(array) => array,
+        _ => unreachable!()
+    };
+
     match function
     {
-        "print" =>
+        "len" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            return Ok((true, Some(integer(array.elements.len()))))
+        },
+
+        "contains" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            return Ok((true, Some(boolean(array.elements.contains(&arguments[1])))))
+        },
+
+        "slice" =>
         {
-            for argument in arguments {
-                print!("{}", argument.printed_string());
+            expect_arguments(function, arguments, 3)?;
+            let start = expect_index(function, &arguments[1], array.elements.len(), true)?;
+            let end = expect_index(function, &arguments[2], array.elements.len(), true)?;
+            if start > end {
+                return fail(ErrorKind::Value, "slice start cannot be after its end".to_string())
             }
-            println!();
 
-            (true, None)
+            let mut slice = array.clone();
+            slice.elements = array.elements[start..end].to_vec();
+            return Ok((true, Some(Variable { variable_type: VariableType::Array(slice) })))
         },
 
-        "input" =>
+        _ => {}
+    }
+
+    // Everything else modifies the array in-place, so it must be a variable rather than a temporary value
+    let array = match receiver
+    {
+        Some(array) => array,
+        None => return fail(ErrorKind::Type, format!("{} expects an array variable as its first argument", function))
+    };
+
+    let result = match function
+    {
+        "push" =>
         {
-            // Print then flush to ensure we actually output before we take input
-            for argument in arguments {
-                print!("{}", argument.printed_string());
+            if arguments.len() < 2 {
+                return fail(ErrorKind::Argument, "push expects at least 2 argument(s)".to_string())
             }
-            io::stdout().flush().unwrap();
+            check_size("array", array.elements.len() + arguments.len() - 1, max_size)?;
 
-            // Get input itself, removing newlines at the same time
-            let input = io::stdin().lines().next().unwrap().unwrap();
+            for argument in &arguments[1..] {
+                let element = array.element_from(argument)?;
+                array.elements.push(element);
+            }
+            None
+        },
 
-            (true, Some(Variable {
-                variable_type: VariableType::Str(input)
-            }))
-        }
+        "pop" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            match array.elements.pop()
+            {
+                Some(element) => Some(element),
+                None => return fail(ErrorKind::Index, "cannot pop from an empty array".to_string())
+            }
+        },
 
-        _ => (false, None)
+        "insert" =>
+        {
+            expect_arguments(function, arguments, 3)?;
+            let index = expect_index(function, &arguments[1], array.elements.len(), true)?;
+            check_size("array", array.elements.len() + 1, max_size)?;
+            let element = array.element_from(&arguments[2])?;
+            array.elements.insert(index, element);
+            None
+        },
+
+        "remove" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let index = expect_index(function, &arguments[1], array.elements.len(), false)?;
+            Some(array.elements.remove(index))
+        },
+
+        "sort" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+
+            // Sorting itself can't fail, so remember the first pair that can't be compared, leaving the array as it
+            // was if there is one
+            let mut incomparable = None;
+            let mut sorted = array.elements.clone();
+            sorted.sort_by(|a, b| a.compare(b).unwrap_or_else(|_|
+            {
+                incomparable.get_or_insert_with(|| (a.printed_string(), b.printed_string()));
+                Ordering::Equal
+            }));
+
+            if let Some((a, b)) = incomparable {
+                return fail(ErrorKind::Type, format!("sort cannot compare \"{}\" with \"{}\"", a, b))
+            }
+            array.elements = sorted;
+            None
+        },
+
+        "reverse" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            array.elements.reverse();
+            None
+        },
+
+        _ => unreachable!()
+    };
+
+    Ok((true, result))
+}
+
+// String functions, which all take the string to operate on as their first argument
+fn string_function(function: &str, arguments: &[Variable], max_size: Option<usize>) -> Result<Option<Variable>, RuntimeError>
+{
+    let result = match function
+    {
+        "len" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            integer(expect_string(function, &arguments[0])?.chars().count())
+        },
+
+        "upper" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            string(expect_string(function, &arguments[0])?.to_uppercase())
+        },
+
+        "lower" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            string(expect_string(function, &arguments[0])?.to_lowercase())
+        },
+
+        "trim" =>
+        {
+            expect_arguments(function, arguments, 1)?;
+            string(expect_string(function, &arguments[0])?.trim().to_string())
+        },
+
+        "split" =>
+        {
+            // Splits on whitespace if no separator is given
+            if arguments.len() != 1 { expect_arguments(function, arguments, 2)?; }
+            let value = expect_string(function, &arguments[0])?;
+
+            let parts: Vec<&str> = if arguments.len() == 1 {
+                value.split_whitespace().collect()
+            }
+            else
+            {
+                let separator = expect_string(function, &arguments[1])?;
+                if separator.is_empty() {
+                    return fail(ErrorKind::Value, "split separator cannot be empty".to_string())
+                }
+                value.split(separator.as_str()).collect()
+            };
+
+            Variable {
+                variable_type: VariableType::Array(Array::new(
+                    Some(VariableType::Str(String::new())),
+                    parts.iter().map(|p| string(p.to_string())).collect()
+                )?)
+            }
+        },
+
+        "join" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let separator = expect_string(function, &arguments[1])?;
+            let VariableType::Array(array) = &arguments[0].variable_type else {
+                return fail(ErrorKind::Type, "join expects an array as its first argument".to_string())
+            };
+
+            // Built up bit by bit so that the size can be checked along the way
+            let mut joined = String::new();
+            let mut size = 0;
+            for i in 0..array.elements.len()
+            {
+                let element = array.elements[i].printed_string();
+                size += element.chars().count() + if i > 0 { separator.chars().count() } else { 0 };
+                check_size("string", size, max_size)?;
+
+                if i > 0 { joined.push_str(separator); }
+                joined.push_str(&element);
+            }
+            string(joined)
+        },
+
+        "contains" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let value = expect_string(function, &arguments[0])?;
+            boolean(value.contains(expect_string(function, &arguments[1])?.as_str()))
+        },
+
+        "starts_with" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let value = expect_string(function, &arguments[0])?;
+            boolean(value.starts_with(expect_string(function, &arguments[1])?.as_str()))
+        },
+
+        "ends_with" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let value = expect_string(function, &arguments[0])?;
+            boolean(value.ends_with(expect_string(function, &arguments[1])?.as_str()))
+        },
+
+        "find" =>
+        {
+            // Returns the character (not byte) index of the first match, or -1 if there is none
+            expect_arguments(function, arguments, 2)?;
+            let value = expect_string(function, &arguments[0])?;
+            match value.find(expect_string(function, &arguments[1])?.as_str())
+            {
+                Some(byte_index) => integer(value[..byte_index].chars().count()),
+                None => Variable { variable_type: VariableType::Integer(-1) }
+            }
+        },
+
+        "replace" =>
+        {
+            expect_arguments(function, arguments, 3)?;
+            let value = expect_string(function, &arguments[0])?;
+            let from = expect_string(function, &arguments[1])?;
+            if from.is_empty() {
+                return fail(ErrorKind::Value, "replace cannot search for an empty string".to_string())
+            }
+
+            let to = expect_string(function, &arguments[2])?;
+            let matches = value.matches(from.as_str()).count();
+            let size = value.chars().count() - matches * from.chars().count() + matches * to.chars().count();
+            check_size("string", size, max_size)?;
+            string(value.replace(from.as_str(), to))
+        },
+
+        "repeat" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let value = expect_string(function, &arguments[0])?;
+            match &arguments[1].variable_type
+            {
+                VariableType::Integer(count) if *count >= 0 =>
+                {
+                    let size = value.chars().count().saturating_mul(*count as usize);
+                    check_size("string", size, max_size)?;
+                    string(value.repeat(*count as usize))
+                },
+                _ => return fail(ErrorKind::Value, "repeat expects a non-negative integer count".to_string())
+            }
+        },
+
+        _ => return Ok(None)
+    };
+
+    Ok(Some(result))
+}
+
+// Assertions, for use in tests, which return nothing but raise an error if what they check doesn't hold. Returns if the
+// function is one of them.
+fn assert_function(function: &str, arguments: &[Variable]) -> Result<bool, RuntimeError>
+{
+    match function
+    {
+        "assert" =>
+        {
+            if arguments.is_empty() || arguments.len() > 2 {
+                return fail(ErrorKind::Argument, "assert expects a condition and an optional message".to_string())
+            }
+
+            if arguments[0].as_integer()? == 0
+            {
+                return match arguments.get(1)
+                {
+                    Some(message) => fail(ErrorKind::Assertion, format!("assertion failed: {}", message.printed_string())),
+                    None => fail(ErrorKind::Assertion, "assertion failed".to_string())
+                }
+            }
+        },
+
+        "assert_eq" =>
+        {
+            expect_arguments(function, arguments, 2)?;
+            let (left, right) = (&arguments[0], &arguments[1]);
+
+            if left != right
+            {
+                let message = format!(
+                    "assert_eq failed, left: {}, right: {}{}", left.printed_string(), right.printed_string(),
+                    first_difference(left, right).map(|at| format!(" (first differs at {})", at)).unwrap_or_default()
+                );
+                return fail(ErrorKind::Assertion, message)
+            }
+        },
+
+        _ => return Ok(false)
+    }
+
+    Ok(true)
+}
+
+// Where two arrays or strings of the same type start to differ, if they do
+fn first_difference(left: &Variable, right: &Variable) -> Option<String>
+{
+    match (&left.variable_type, &right.variable_type)
+    {
+        (VariableType::Array(left), VariableType::Array(right)) =>
+        {
+            let index = (0..left.elements.len().max(right.elements.len()))
+                .find(|i| left.elements.get(*i) != right.elements.get(*i))?;
+            Some(format!("index {}", index))
+        },
+
+        (VariableType::Str(left), VariableType::Str(right)) =>
+        {
+            let (left, right) = (left.chars().collect::<Vec<char>>(), right.chars().collect::<Vec<char>>());
+            let index = (0..left.len().max(right.len())).find(|i| left.get(*i) != right.get(*i))?;
+            Some(format!("character {}", index))
+        },
+
+        _ => None
+    }
+}
+
+fn expect_arguments(function: &str, arguments: &[Variable], count: usize) -> Result<(), RuntimeError>
+{
+    if arguments.len() != count {
+        let message = format!("{} expects {} argument(s) but was given {}", function, count, arguments.len());
+        return fail(ErrorKind::Argument, message)
+    }
+    Ok(())
+}
+
+// Indices may go up to and including the length for functions such as "insert" that can append, otherwise they must
+// refer to an element
+fn expect_index(function: &str, argument: &Variable, length: usize, can_append: bool) -> Result<usize, RuntimeError>
+{
+    let end = if can_append { length + 1 } else { length };
+    match &argument.variable_type
+    {
+        VariableType::Integer(index) if *index >= 0 && (*index as usize) < end => Ok(*index as usize),
+        VariableType::Integer(index) => fail(
+            ErrorKind::Index, format!("{} index {} out of range for array of length {}", function, index, length)
+        ),
+        _ => fail(
+            ErrorKind::Type, format!("{} expects an integer index but was given \"{}\"", function, argument.printed_string())
+        )
+    }
+}
+
+fn expect_string<'a>(function: &str, argument: &'a Variable) -> Result<&'a String, RuntimeError>
+{
+    match &argument.variable_type
+    {
+        VariableType::Str(value) => Ok(value),
+        _ => fail(ErrorKind::Type, format!("{} expects a string but was given \"{}\"", function, argument.printed_string()))
     }
-}
\ No newline at end of file
+}
+
+fn array(elements: Vec<Variable>) -> Variable
+{
+    Variable { variable_type: VariableType::Array(Array::untyped(elements)) }
+}
+
+fn string(value: String) -> Variable
+{
+    Variable { variable_type: VariableType::Str(value) }
+}
+
+fn integer(value: usize) -> Variable
+{
+    Variable { variable_type: VariableType::Integer(value as isize) }
+}
+
+fn boolean(value: bool) -> Variable
+{
+    Variable { variable_type: VariableType::Boolean(value) }
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+// Where a script's input comes from and its output goes, so that it needn't be the terminal
+pub trait Io
+{
+    fn read_line(&mut self) -> Option<String>; // None once there's nothing left to read
+    fn write(&mut self, text: &str);
+    fn write_error(&mut self, text: &str);
+}
+
+// The terminal, through standard input, output and error
+pub struct StandardIo;
+
+impl Io for StandardIo
+{
+    fn read_line(&mut self) -> Option<String>
+    {
+        // Flush first to ensure any prompt is actually shown before we take input, though if output has been closed
+        // there's nobody to show it to, and input can still be read
+        let _ = io::stdout().flush();
+        io::stdin().lines().next().and_then(|line| line.ok())
+    }
+
+    // As with the prompt above, output that's been closed (e.g. by piping into "head") has nobody left to read it,
+    // so isn't worth stopping for
+    fn write(&mut self, text: &str)
+    {
+        let _ = io::stdout().lock().write_all(text.as_bytes());
+    }
+
+    fn write_error(&mut self, text: &str)
+    {
+        let _ = io::stderr().lock().write_all(text.as_bytes());
+    }
+}
+
+#[derive(Default)]
+struct Buffers
+{
+    input: VecDeque<String>,
+    output: String,
+    errors: String
+}
+
+// Input given ahead of time, with output kept in memory. Clones share the same buffers, so one can be kept to look at
+// what was written after giving another to an interpreter.
+#[derive(Clone, Default)]
+pub struct BufferIo
+{
+    buffers: Rc<RefCell<Buffers>>
+}
+
+impl BufferIo
+{
+    // Each line of the input is read separately
+    pub fn new(input: &str) -> BufferIo
+    {
+        let buffers = Buffers { input: input.lines().map(String::from).collect(), ..Buffers::default() };
+        BufferIo { buffers: Rc::new(RefCell::new(buffers)) }
+    }
+
+    pub fn push_input(&self, line: &str)
+    {
+        self.buffers.borrow_mut().input.push_back(line.to_string());
+    }
+
+    pub fn clear_output(&self)
+    {
+        self.buffers.borrow_mut().output.clear();
+    }
+
+    pub fn output(&self) -> String
+    {
+        self.buffers.borrow().output.clone()
+    }
+
+    pub fn errors(&self) -> String
+    {
+        self.buffers.borrow().errors.clone()
+    }
+}
+
+impl Io for BufferIo
+{
+    fn read_line(&mut self) -> Option<String>
+    {
+        self.buffers.borrow_mut().input.pop_front()
+    }
+
+    fn write(&mut self, text: &str)
+    {
+        self.buffers.borrow_mut().output.push_str(text);
+    }
+
+    fn write_error(&mut self, text: &str)
+    {
+        self.buffers.borrow_mut().errors.push_str(text);
+    }
+}
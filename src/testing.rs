@@ -0,0 +1,87 @@
+use super::parser::Instruction;
+use super::engine::State;
+use super::engine::Limits;
+use super::streams::BufferIo;
+
+use std::io;
+use std::io::Write;
+
+/*
+    Runs each test in a script on its own. Only what the script declares (its functions, structs, enums and constants)
+    is set up beforehand, afresh for each test, so that no test can affect another, and nothing else the script does
+    at the top level (e.g. reading input) gets in the way. Anything a test prints is kept back, and only shown if it
+    fails.
+*/
+
+// Returns if every test passed
+pub fn run_tests(instructions: &[Instruction], limits: &Limits, output: &mut dyn Write) -> io::Result<bool>
+{
+    let declarations = declarations_only(instructions);
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for (i, instruction) in instructions.iter().enumerate()
+    {
+        let Instruction::Test { name, .. } = instruction else { continue };
+
+        let io = BufferIo::default();
+        let mut state = State::default();
+        state.set_limits(limits.clone());
+        state.set_io(Box::new(io.clone()));
+
+        let result = state.execute(declarations.clone()).and_then(|_| state.execute_test(i));
+        match result
+        {
+            Ok(()) =>
+            {
+                passed += 1;
+                writeln!(output, "PASS {}", name)?;
+            },
+            Err(error) =>
+            {
+                failed += 1;
+                writeln!(output, "FAIL {}", name)?;
+                writeln!(output, "    {}", error.to_string().replace('\n', "\n    "))?;
+
+                let printed = io.output();
+                if !printed.is_empty() {
+                    writeln!(output, "    output:\n        {}", printed.trim_end().replace('\n', "\n        "))?;
+                }
+            }
+        }
+    }
+
+    writeln!(output, "\n{} passed, {} failed", passed, failed)?;
+    Ok(failed == 0)
+}
+
+// The script with everything at the top level that isn't a declaration (or a test) left out, including any block it
+// begins. Each instruction stays on the same line, so that errors and tests are still found where they're written.
+fn declarations_only(instructions: &[Instruction]) -> Vec<Instruction>
+{
+    let mut declarations = vec![Instruction::NoOp; instructions.len()];
+    let mut i = 0;
+
+    while i < instructions.len()
+    {
+        let (keep, last_line) = match &instructions[i]
+        {
+            Instruction::FunctionDeclaration { last_line, .. } | Instruction::Test { last_line, .. } => (true, *last_line),
+            Instruction::StructDeclaration { .. } | Instruction::EnumDeclaration { .. } |
+                Instruction::ConstDeclaration { .. } => (true, i),
+
+            Instruction::FromValueToValue { last_line, .. } | Instruction::ForEach { last_line, .. } |
+                Instruction::IfValue { last_line, .. } | Instruction::IfValueIsValue { last_line, .. } |
+                Instruction::IfValueIsNotValue { last_line, .. } | Instruction::Match { last_line, .. } |
+                Instruction::Try { last_line, .. } => (false, *last_line),
+            _ => (false, i)
+        };
+
+        if keep {
+            declarations[i..=last_line].clone_from_slice(&instructions[i..=last_line]);
+        }
+        i = last_line + 1;
+    }
+
+    declarations
+}
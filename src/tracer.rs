@@ -0,0 +1,143 @@
+use super::engine::Hook;
+use super::engine::State;
+use super::parser::Instruction;
+use super::variables::Variable;
+use super::json::Json;
+
+use std::io::Write;
+
+/*
+    Logs everything the script does as it runs: each line before it runs, each value worked out along the way (other
+    than literals), and each variable written to. Entries can be limited to a range of lines, or to what runs within a
+    function (including anything it calls), and are written either for people to read, indented by how deep the
+    script is, or as a JSON object per line for tools.
+*/
+
+#[derive(Default)]
+pub struct TraceOptions
+{
+    pub function: Option<String>,
+    pub lines: Option<(usize, usize)>, // First and last (0-based) lines to log
+    pub json: bool
+}
+
+// Reads a line number or range of them (e.g. "12" or "12-20") into the first and last (0-based) lines
+pub fn parse_line_range(text: &str) -> Option<(usize, usize)>
+{
+    let (first, last) = text.split_once('-').unwrap_or((text, text));
+    let (first, last) = (first.trim().parse::<usize>().ok()?, last.trim().parse::<usize>().ok()?);
+    if first == 0 || last < first {
+        return None
+    }
+    Some((first - 1, last - 1))
+}
+
+pub struct Tracer
+{
+    source: Vec<String>,
+    instructions: Vec<Instruction>,
+    options: TraceOptions,
+    output: Box<dyn Write>
+}
+
+impl Tracer
+{
+    pub fn new(source: Vec<String>, instructions: Vec<Instruction>, options: TraceOptions, output: Box<dyn Write>)
+        -> Tracer
+    {
+        Tracer { source, instructions, options, output }
+    }
+
+    fn included(&self, state: &State) -> bool
+    {
+        if let Some((first, last)) = self.options.lines
+        {
+            if state.line() < first || state.line() > last {
+                return false
+            }
+        }
+        match &self.options.function
+        {
+            Some(function) => state.call_stack().contains(function),
+            None => true
+        }
+    }
+
+    // The kind of instruction on a line (e.g. "Assignment")
+    fn kind(&self, line: usize) -> String
+    {
+        let debug = self.instructions.get(line).map(|instruction| format!("{:?}", instruction)).unwrap_or_default();
+        debug.chars().take_while(|c| c.is_alphanumeric()).collect()
+    }
+
+    // Writes one entry, with fields shared by every entry first
+    fn log(&mut self, state: &State, event: &str, fields: Vec<(&str, Json)>, text: String)
+    {
+        let entry = if self.options.json
+        {
+            let function = state.call_stack().last().cloned().map(Json::from).unwrap_or(Json::Null);
+            let mut all = vec![
+                ("event", Json::from(event)),
+                ("line", Json::from(state.line() + 1)),
+                ("depth", Json::from(state.frame_depth())),
+                ("function", function)
+            ];
+            all.extend(fields);
+            Json::object(all).to_string()
+        }
+        else {
+            format!("{}{}", "  ".repeat(state.frame_depth()), text)
+        };
+
+        // A trace that can't be written shouldn't stop the script itself
+        let _ = writeln!(self.output, "{}", entry);
+    }
+}
+
+impl Hook for Tracer
+{
+    fn before_line(&mut self, state: &mut State)
+    {
+        if !self.included(state) {
+            return
+        }
+
+        let line = state.line();
+        let kind = self.kind(line);
+        let source = self.source.get(line).map(|text| text.trim().to_string()).unwrap_or_default();
+        let text = format!("line {} {}: {}", line + 1, kind, source);
+        self.log(state, "line", vec![("instruction", Json::from(kind)), ("source", Json::from(source))], text);
+    }
+
+    fn evaluated(&mut self, state: &State, value: &str, result: &Variable)
+    {
+        if !self.included(state) {
+            return
+        }
+
+        let printed = result.printed_string();
+        let text = format!("    {} => {}", value, printed);
+        let fields = vec![
+            ("value", Json::from(value)),
+            ("result", Json::from(printed)),
+            ("type", Json::from(result.type_name()))
+        ];
+        self.log(state, "evaluate", fields, text);
+    }
+
+    fn written(&mut self, state: &State, name: &str, value: &Variable)
+    {
+        if !self.included(state) {
+            return
+        }
+
+        let printed = value.printed_string();
+        let text = format!("    {} := {}", name, printed);
+        let fields = vec![
+            ("name", Json::from(name)),
+            ("value", Json::from(printed)),
+            ("type", Json::from(value.type_name()))
+        ];
+        self.log(state, "write", fields, text);
+    }
+}
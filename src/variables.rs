@@ -1,38 +1,298 @@
-use super::common::error;
+use super::common::RuntimeError;
+use super::common::ErrorKind;
+use super::lexer::Token;
 use super::lexer::TokenType;
 use std::cmp::Ordering;
-use std::ops;
+use std::collections::HashMap;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum VariableType
 {
     Integer(isize),
     Boolean(bool),
+    Str(String),
+    Array(Array),
+    Map(Map),
+    Struct(Struct),
+    Enum(Enum),
+    None,
+    Optional(Box<VariableType>) // Only ever used as a type, for something that may also be none
+}
+
+#[derive(Clone, Debug)]
+pub struct Array
+{
+    pub element_type: Option<Box<VariableType>>, // None if the array may hold anything
+    pub elements: Vec<Variable>
+}
+
+impl Array
+{
+    pub fn new(element_type: Option<VariableType>, elements: Vec<Variable>) -> Result<Array, RuntimeError>
+    {
+        let mut array = Array { element_type: element_type.map(Box::new), elements: Vec::new() };
+        array.elements = elements.iter().map(|e| array.element_from(e)).collect::<Result<Vec<Variable>, RuntimeError>>()?;
+        Ok(array)
+    }
+
+    // Arrays that may hold anything can be made from anything
+    pub fn untyped(elements: Vec<Variable>) -> Array
+    {
+        Array { element_type: None, elements }
+    }
+
+    // Converts a value into one fit for storing in this array, enforcing the element type (if
+    // any) on every non-array value, including those of nested arrays
+    pub fn element_from(&self, value: &Variable) -> Result<Variable, RuntimeError>
+    {
+        convert_element(&self.element_type, value)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MapKey
+{
+    Integer(isize),
     Str(String)
 }
 
-pub fn is_token_type_valid_type(token_type: &TokenType) -> bool
+impl MapKey
 {
-    match token_type
+    pub fn from_variable(variable: &Variable) -> Result<MapKey, RuntimeError>
+    {
+        match &variable.variable_type
+        {
+            VariableType::Integer(value) => Ok(MapKey::Integer(*value)),
+            VariableType::Str(value) => Ok(MapKey::Str(value.clone())),
+            _ => Err(RuntimeError::new(
+                ErrorKind::Type, format!("map keys must be strings or integers, not \"{}\"", variable.printed_string())
+            ))
+        }
+    }
+
+    pub fn to_variable(&self) -> Variable
     {
-        TokenType::Int => true,
-        TokenType::Bool => true,
-        TokenType::Str => true,
-        _ => false
+        match self
+        {
+            MapKey::Integer(value) => Variable { variable_type: VariableType::Integer(*value) },
+            MapKey::Str(value) => Variable { variable_type: VariableType::Str(value.clone()) }
+        }
     }
 }
 
-pub fn is_str_valid_type(string: &str) -> bool
+// Entries are kept in the order they were first inserted, so that iterating over a map is predictable
+#[derive(Clone, Debug)]
+pub struct Map
+{
+    pub value_type: Option<Box<VariableType>>, // None if the map may hold anything
+    entries: Vec<(MapKey, Variable)>,
+    indices: HashMap<MapKey, usize>
+}
+
+impl Map
+{
+    pub fn new(value_type: Option<VariableType>) -> Map
+    {
+        Map { value_type: value_type.map(Box::new), entries: Vec::new(), indices: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[(MapKey, Variable)]
+    {
+        &self.entries
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&Variable>
+    {
+        self.indices.get(key).map(|i| &self.entries[*i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &MapKey) -> Option<&mut Variable>
+    {
+        self.indices.get(key).map(|i| &mut self.entries[*i].1)
+    }
+
+    // Sets the value for a key, adding the key if it isn't already present
+    pub fn insert(&mut self, key: MapKey, value: &Variable) -> Result<(), RuntimeError>
+    {
+        let value = self.value_from(value)?;
+        match self.indices.get(&key)
+        {
+            Some(index) => self.entries[*index].1 = value,
+            None =>
+            {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<Variable>
+    {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(index);
+
+        // Everything after the removed entry has shifted down by one
+        for i in self.indices.values_mut() {
+            if *i > index { *i -= 1; }
+        }
+
+        Some(value)
+    }
+
+    // Converts a value into one fit for storing in this map, just as Array::element_from does
+    pub fn value_from(&self, value: &Variable) -> Result<Variable, RuntimeError>
+    {
+        convert_element(&self.value_type, value)
+    }
+}
+
+// Maps are equal if they have the same keys with the same values, regardless of order or type
+impl PartialEq for Map
+{
+    fn eq(&self, rhs: &Self) -> bool
+    {
+        self.len() == rhs.len() && self.entries.iter().all(|(key, value)| rhs.get(key) == Some(value))
+    }
+}
+
+// An instance of a user-defined struct, whose fields are kept in the order they were declared
+#[derive(Clone, PartialEq, Debug)]
+pub struct Struct
+{
+    pub name: String,
+    pub fields: Vec<(String, Variable)>
+}
+
+impl Struct
+{
+    pub fn get(&self, field: &str) -> Option<&Variable>
+    {
+        self.fields.iter().find(|(name, _)| name == field).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, field: &str) -> Option<&mut Variable>
+    {
+        self.fields.iter_mut().find(|(name, _)| name == field).map(|(_, value)| value)
+    }
+}
+
+// A variant of a user-defined enum, along with whatever values it carries
+#[derive(Clone, PartialEq, Debug)]
+pub struct Enum
+{
+    pub name: String,
+    pub variant: String,
+    pub fields: Vec<(String, Variable)>
+}
+
+// Enforces the element type of a collection (if any) on every non-collection value within another value
+fn convert_element(element_type: &Option<Box<VariableType>>, value: &Variable) -> Result<Variable, RuntimeError>
+{
+    match (element_type, &value.variable_type)
+    {
+        (None, _) => Ok(value.clone()),
+
+        (Some(element_type), VariableType::Array(inner)) => Ok(Variable {
+            variable_type: VariableType::Array(
+                Array::new(Some(*element_type.clone()), inner.elements.clone())?
+            )
+        }),
+
+        (Some(element_type), VariableType::Map(inner)) =>
+        {
+            let mut map = Map::new(Some(*element_type.clone()));
+            for (key, value) in inner.entries() {
+                map.insert(key.clone(), value)?;
+            }
+            Ok(Variable { variable_type: VariableType::Map(map) })
+        },
+
+        (Some(element_type), _) if !holds_type(element_type, value) =>
+        {
+            let expected = Variable { variable_type: *element_type.clone() }.type_name();
+            let given = format!("{} \"{}\"", value.type_name(), value.printed_string());
+            let message = format!("expected an element of type {} but was given {}", expected, given);
+            Err(RuntimeError::new(ErrorKind::Type, message))
+        },
+
+        (Some(element_type), _) => value_of_type(element_type, value)
+    }
+}
+
+// Whether a value already is of a type, without converting it (e.g. "7" is a string, not an int). Structs and enums
+// are left for value_of_type to check by name.
+fn holds_type(variable_type: &VariableType, value: &Variable) -> bool
 {
-    match string
+    match (variable_type, &value.variable_type)
     {
-        "int" => true,
-        "bool" => true,
-        "str" => true,
-        _ => false
+        (VariableType::Optional(_), VariableType::None) => true,
+        (VariableType::Optional(inner), _) => holds_type(inner, value),
+        (VariableType::Struct(_) | VariableType::Enum(_), _) => true,
+        (expected, actual) => std::mem::discriminant(expected) == std::mem::discriminant(actual)
     }
 }
 
+// Converts a value to the given type, which may only be none if the type is optional
+pub fn value_of_type(variable_type: &VariableType, value: &Variable) -> Result<Variable, RuntimeError>
+{
+    match (variable_type, &value.variable_type)
+    {
+        (VariableType::Optional(_), VariableType::None) => Ok(value.clone()),
+        (VariableType::Optional(inner), _) => value_of_type(inner, value),
+        _ =>
+        {
+            let mut variable = Variable { variable_type: variable_type.clone() };
+            variable.set(value)?;
+            Ok(variable)
+        }
+    }
+}
+
+pub fn is_token_type_valid_type(token_type: &TokenType) -> bool
+{
+    matches!(token_type, TokenType::Int | TokenType::Bool | TokenType::Str)
+}
+
+// Function arguments and struct fields may also be collections, structs or enums, the latter two being named by a
+// plain value (e.g. "Point"). Both are represented by a struct without any fields, to be checked against at runtime.
+// Any of them may be followed by a "?" (e.g. "int?") if they may also be none.
+pub fn token_to_variable_type(token: &Token) -> Option<VariableType>
+{
+    let (name, optional) = match token.string.strip_suffix('?')
+    {
+        Some(name) => (name, true),
+        None => (token.string.as_str(), false)
+    };
+
+    let variable_type = match token.token_type
+    {
+        TokenType::Int | TokenType::Bool | TokenType::Str => token_type_to_variable_type(&token.token_type),
+        TokenType::Array => VariableType::Array(Array::untyped(Vec::new())),
+        TokenType::Map => VariableType::Map(Map::new(None)),
+        TokenType::Value => VariableType::Struct(Struct { name: name.to_string(), fields: Vec::new() }),
+        _ => return None
+    };
+
+    Some(if optional { VariableType::Optional(Box::new(variable_type)) } else { variable_type })
+}
+
+pub fn is_str_valid_type(string: &str) -> bool
+{
+    matches!(string, "int" | "bool" | "str")
+}
+
 pub fn token_type_to_variable_type(token_type: &TokenType) -> VariableType
 {
     match token_type
@@ -44,6 +304,15 @@ pub fn token_type_to_variable_type(token_type: &TokenType) -> VariableType
     }
 }
 
+// Arrays are equal if their contents are, regardless of whether either is typed
+impl PartialEq for Array
+{
+    fn eq(&self, rhs: &Self) -> bool
+    {
+        self.elements == rhs.elements
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Variable
 {
@@ -53,16 +322,33 @@ pub struct Variable
 // TODO: allow the comparison of strings, etc. by casting all types to strings
 impl Variable
 {
-    pub fn set(&mut self, variable: &Variable)
+    pub fn set(&mut self, variable: &Variable) -> Result<(), RuntimeError>
     {
-        if self.is_string_and_so_is(variable)
+        // Only something without a type (e.g. a variable that is already none) can become none, or be given anything
+        // after being none
+        match (&self.variable_type, &variable.variable_type)
+        {
+            (VariableType::None, _) =>
+            {
+                self.variable_type = variable.variable_type.clone();
+                return Ok(());
+            },
+            (_, VariableType::None) => return Err(type_error(format!("attempt to cast none to {}", self.type_name()))),
+            _ => {}
+        }
+
+        if self.is_string_and_so_is(variable) || self.is_same_compound_type_as(variable)
         {
             self.variable_type = variable.variable_type.clone();
-            return;
+            return Ok(());
         }
 
-        self.detect_conflicting_string_types(variable);
-        self.set_from_integer(variable.as_integer());
+        if self.is_compound() || variable.is_compound() {
+            return Err(type_error(format!("attempt to cast {} to {}", variable.type_name(), self.type_name())))
+        }
+
+        self.detect_conflicting_string_types(variable)?;
+        self.set_from_integer(variable.as_integer()?)
     }
 
     fn is_string(&self) -> bool
@@ -75,74 +361,135 @@ impl Variable
         self.is_string() && variable.is_string()
     }
 
-    fn detect_conflicting_string_types(&self, variable: &Variable)
+    // Collections, structs and enums, which can't be converted to or from anything else
+    fn is_compound(&self) -> bool
+    {
+        matches!(
+            self.variable_type,
+            VariableType::Array(_) | VariableType::Map(_) | VariableType::Struct(_) | VariableType::Enum(_)
+        )
+    }
+
+    // Structs and enums are only the same type if they share a name
+    fn is_same_compound_type_as(&self, variable: &Variable) -> bool
+    {
+        self.is_compound() && self.type_name() == variable.type_name()
+    }
+
+    pub fn type_name(&self) -> String
     {
-        fn is_numeric(value: &String) -> bool
+        match &self.variable_type
         {
-            for character in value.chars()
-            {
-                if !character.is_numeric() {
-                    return false
-                }
-            }
-            true
+            VariableType::Integer(_) => "int".to_string(),
+            VariableType::Boolean(_) => "bool".to_string(),
+            VariableType::Str(_) => "string".to_string(),
+            VariableType::Array(_) => "array".to_string(),
+            VariableType::Map(_) => "map".to_string(),
+            VariableType::Struct(instance) => instance.name.clone(),
+            VariableType::Enum(instance) => instance.name.clone(),
+            VariableType::None => "none".to_string(),
+            VariableType::Optional(inner) => format!("{}?", Variable { variable_type: *inner.clone() }.type_name())
         }
+    }
 
+    fn detect_conflicting_string_types(&self, variable: &Variable) -> Result<(), RuntimeError>
+    {
         let is_error = match &self.variable_type
         {
             VariableType::Str(a) => match &variable.variable_type
             {
-                VariableType::Str(b) =>
-                {
-                    if is_numeric(&a) && is_numeric(&b) { false }
-                    else { true }
-                },
-                _ =>
-                {
-                    if is_numeric(&a) { false }
-                    else { false }
-                }
+                VariableType::Str(b) => !(is_numeric(a) && is_numeric(b)),
+                _ => false
             },
 
             _ =>
             {
                 match &variable.variable_type
                 {
-                    VariableType::Str(b) =>
-                    {
-                        if is_numeric(&b) { false }
-                        else { true }
-                    },
-                    _ => { false }
+                    VariableType::Str(b) => !is_numeric(b),
+                    _ => false
                 }
             }
         };
 
         if is_error {
-            error("attempt to cast non-numeric string with other type".to_string());
+            return Err(RuntimeError::new(ErrorKind::Value, "attempt to cast non-numeric string with other type".to_string()))
         }
+        Ok(())
     }
 
-    pub fn as_integer(&self) -> isize
+    pub fn as_integer(&self) -> Result<isize, RuntimeError>
     {
         match &self.variable_type
         {
-            VariableType::Integer(value) => *value,
-            VariableType::Boolean(value) => bool_to_int(value),
-            VariableType::Str(value) => string_to_int(&value)
+            VariableType::Integer(value) => Ok(*value),
+            VariableType::Boolean(value) => Ok(bool_to_int(value)),
+            VariableType::Str(value) => string_to_int(value),
+            VariableType::Array(_) | VariableType::Map(_) | VariableType::Struct(_) | VariableType::Enum(_) |
+            VariableType::None | VariableType::Optional(_) =>
+                Err(type_error(format!("attempt to use {} as a number", self.type_name())))
         }
     }
 
-    fn set_from_integer(&mut self, value: isize)
+    fn set_from_integer(&mut self, value: isize) -> Result<(), RuntimeError>
     {
         let variable_type = match &self.variable_type
         {
             VariableType::Integer(_) => VariableType::Integer(value),
             VariableType::Boolean(_) => VariableType::Boolean(int_to_bool(value)),
-            VariableType::Str(_) => VariableType::Str(int_to_string(value))
+            VariableType::Str(_) => VariableType::Str(int_to_string(value)),
+            VariableType::Array(_) | VariableType::Map(_) | VariableType::Struct(_) | VariableType::Enum(_) |
+            VariableType::None | VariableType::Optional(_) =>
+                return Err(type_error(format!("attempt to use {} as a number", self.type_name())))
         };
 
         self.variable_type = variable_type;
+        Ok(())
+    }
+
+    // Applies an arithmetic operation to two values as numbers, keeping the type of the first. The operation gives
+    // None if it overflows.
+    pub fn combine(&self, rhs: &Variable, operation: impl Fn(isize, isize) -> Option<isize>) -> Result<Variable, RuntimeError>
+    {
+        let (a, b) = (self.as_integer()?, rhs.as_integer()?);
+        let Some(result) = operation(a, b) else {
+            return Err(RuntimeError::new(ErrorKind::Value, format!("integer overflow combining {} and {}", a, b)))
+        };
+
+        let mut combined = self.clone();
+        combined.set_from_integer(result)?;
+        Ok(combined)
+    }
+
+    // Like ==, but failing rather than being unequal when two values can't be compared
+    pub fn equals(&self, rhs: &Variable) -> Result<bool, RuntimeError>
+    {
+        if let Some((a, b)) = self.as_comparable_strings(rhs) {
+            return Ok(a == b)
+        }
+
+        match (&self.variable_type, &rhs.variable_type)
+        {
+            (VariableType::None, other) | (other, VariableType::None) => return Ok(matches!(other, VariableType::None)),
+            (VariableType::Array(a), VariableType::Array(b)) => return Ok(a == b),
+            (VariableType::Map(a), VariableType::Map(b)) => return Ok(a == b),
+            (VariableType::Struct(a), VariableType::Struct(b)) => return Ok(a == b),
+            (VariableType::Enum(a), VariableType::Enum(b)) => return Ok(a == b),
+            _ if self.is_compound() || rhs.is_compound() => return Ok(false),
+            _ => {}
+        }
+
+        Ok(self.as_integer()? == rhs.as_integer()?)
+    }
+
+    // Like partial_cmp, but failing when two values can't be ordered
+    pub fn compare(&self, rhs: &Variable) -> Result<Ordering, RuntimeError>
+    {
+        if let Some((a, b)) = self.as_comparable_strings(rhs) {
+            return Ok(a.cmp(b))
+        }
+
+        Ok(self.as_integer()?.cmp(&rhs.as_integer()?))
     }
 
     pub fn printed_string(&self) -> String
@@ -151,79 +498,123 @@ impl Variable
         {
             VariableType::Integer(value) => format!("{}", value),
             VariableType::Boolean(value) => format!("{}", value),
-            VariableType::Str(value) => value.clone()
+            VariableType::Str(value) => value.clone(),
+            VariableType::Array(array) => format!(
+                "[{}]",
+                array.elements.iter().map(|e| e.printed_string()).collect::<Vec<String>>().join(", ")
+            ),
+            VariableType::Map(map) => format!(
+                "{{{}}}",
+                map.entries().iter()
+                    .map(|(key, value)| format!("{}: {}", key.to_variable().printed_string(), value.printed_string()))
+                    .collect::<Vec<String>>().join(", ")
+            ),
+            VariableType::Struct(instance) => format!("{}({})", instance.name, printed_fields(&instance.fields)),
+            VariableType::Enum(instance) if instance.fields.is_empty() => instance.variant.clone(),
+            VariableType::Enum(instance) => format!("{}({})", instance.variant, printed_fields(&instance.fields)),
+            VariableType::None | VariableType::Optional(_) => "none".to_string()
+        }
+    }
+
+    // Strings are compared as text, unless both happen to hold numbers
+    fn as_comparable_strings<'a>(&'a self, rhs: &'a Variable) -> Option<(&'a String, &'a String)>
+    {
+        match (&self.variable_type, &rhs.variable_type)
+        {
+            (VariableType::Str(a), VariableType::Str(b)) if !(is_numeric(a) && is_numeric(b)) => Some((a, b)),
+            _ => None
         }
     }
 }
 
+fn printed_fields(fields: &[(String, Variable)]) -> String
+{
+    fields.iter()
+        .map(|(name, value)| format!("{}: {}", name, value.printed_string()))
+        .collect::<Vec<String>>().join(", ")
+}
+
+fn is_numeric(value: &str) -> bool
+{
+    !value.is_empty() && value.strip_prefix('-').unwrap_or(value).chars().all(|c| c.is_ascii_digit())
+}
+
 fn bool_to_int(value: &bool) -> isize
 {
-    if *value == false { 0 } else { 1 }
+    if *value { 1 } else { 0 }
 }
 fn int_to_bool(value: isize) -> bool
 {
-    if value == 0 { false } else { true }
+    value != 0
 }
 
-fn string_to_int(value: &String) -> isize { value.parse::<isize>().unwrap() }
+fn string_to_int(value: &str) -> Result<isize, RuntimeError>
+{
+    value.parse::<isize>()
+        .map_err(|_| RuntimeError::new(ErrorKind::Value, format!("cannot convert \"{}\" to a number", value)))
+}
 fn int_to_string(value: isize) -> String { value.to_string() }
 
-impl ops::Add<Variable> for Variable
+fn type_error(message: String) -> RuntimeError
 {
-    type Output = Variable;
+    RuntimeError::new(ErrorKind::Type, message)
+}
 
-    fn add(self, rhs: Variable) -> Variable
+// Values that can't be compared (e.g. "abc" and 1) are simply unequal, for when they're compared from within
+// collections
+impl PartialEq<Self> for Variable
+{
+    fn eq(&self, rhs: &Self) -> bool
     {
-        let self_as_int = self.as_integer();
-        let rhs_as_int = rhs.as_integer();
-
-        let mut new = self.clone();
-        new.set_from_integer(self_as_int + rhs_as_int);
-        new
+        self.equals(rhs).unwrap_or(false)
     }
 }
 
-impl ops::AddAssign<Variable> for Variable
+impl PartialOrd for Variable
 {
-    fn add_assign(&mut self, rhs: Variable)
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering>
     {
-        self.set_from_integer(self.as_integer() + rhs.as_integer());
+        self.compare(rhs).ok()
     }
 }
 
-impl ops::MulAssign<Variable> for Variable
+// For making values from Rust, such as the arguments of a function called from it
+impl From<isize> for Variable
 {
-    fn mul_assign(&mut self, rhs: Variable)
+    fn from(value: isize) -> Variable
     {
-        self.set_from_integer(self.as_integer() * rhs.as_integer());
+        Variable { variable_type: VariableType::Integer(value) }
     }
 }
 
-impl ops::SubAssign<Variable> for Variable
+impl From<bool> for Variable
 {
-    fn sub_assign(&mut self, rhs: Variable)
+    fn from(value: bool) -> Variable
     {
-        self.set_from_integer(self.as_integer() - rhs.as_integer());
+        Variable { variable_type: VariableType::Boolean(value) }
     }
 }
 
-impl PartialEq<Self> for Variable
+impl From<&str> for Variable
 {
-    fn eq(&self, rhs: &Self) -> bool
+    fn from(value: &str) -> Variable
     {
-        self.as_integer() == rhs.as_integer()
+        Variable { variable_type: VariableType::Str(value.to_string()) }
     }
 }
 
-impl PartialOrd for Variable
+impl From<String> for Variable
 {
-    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering>
+    fn from(value: String) -> Variable
     {
-        let self_as_int = self.as_integer();
-        let rhs_as_int = rhs.as_integer();
+        Variable { variable_type: VariableType::Str(value) }
+    }
+}
 
-        if self_as_int < rhs_as_int { Some(Ordering::Less) }
-        else if self_as_int > rhs_as_int { Some(Ordering::Greater) }
-        else { Some(Ordering::Equal) }
+impl From<Vec<Variable>> for Variable
+{
+    fn from(elements: Vec<Variable>) -> Variable
+    {
+        Variable { variable_type: VariableType::Array(Array::untyped(elements)) }
     }
 }
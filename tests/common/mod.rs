@@ -0,0 +1,50 @@
+// Fixtures shared between the integration tests, each of which only uses some of them
+#![allow(dead_code)]
+
+use lukascript::Value;
+use lukascript::variables::VariableType;
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+pub fn int(value: isize) -> Value
+{
+    Value { variable_type: VariableType::Integer(value) }
+}
+
+pub fn string(value: &str) -> Value
+{
+    Value { variable_type: VariableType::Str(value.to_string()) }
+}
+
+// Output that can still be read once given away (e.g. to a debugger)
+#[derive(Clone, Default)]
+pub struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Shared
+{
+    pub fn bytes(&self) -> Vec<u8>
+    {
+        self.0.borrow().clone()
+    }
+
+    pub fn text(&self) -> String
+    {
+        String::from_utf8(self.bytes()).unwrap()
+    }
+}
+
+impl Write for Shared
+{
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize>
+    {
+        self.0.borrow_mut().extend_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+}
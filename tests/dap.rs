@@ -0,0 +1,120 @@
+mod common;
+
+use lukascript::dap;
+use lukascript::json::Json;
+use lukascript::lsp::read_message;
+use lukascript::lsp::write_message;
+use lukascript::Limits;
+
+use std::env;
+use std::fs;
+use std::io::Cursor;
+
+use common::Shared;
+
+// Serves the given requests (each command with its arguments) for a program, giving back every message sent back as
+// "event name" or "response command"
+fn session(name: &str, program: &str, requests: Vec<(&str, Json)>) -> Vec<String>
+{
+    let path = env::temp_dir().join(format!("lukascript-dap-{}-{}.ls", name, std::process::id()));
+    fs::write(&path, program).unwrap();
+
+    let path_name = path.to_string_lossy().to_string();
+    let mut input = Vec::<u8>::new();
+    for (i, (command, arguments)) in requests.into_iter().enumerate()
+    {
+        let arguments = match command
+        {
+            "launch" => Json::object(vec![("program", path_name.clone().into()), ("stopOnEntry", arguments)]),
+            _ => arguments
+        };
+        let request = Json::object(vec![
+            ("seq", (i + 1).into()),
+            ("type", "request".into()),
+            ("command", command.into()),
+            ("arguments", arguments)
+        ]);
+        write_message(&mut input, &request).unwrap();
+    }
+
+    let output = Shared::default();
+    dap::serve(Box::new(Cursor::new(input)), Box::new(output.clone()), &Limits::default());
+    fs::remove_file(&path).unwrap();
+
+    let mut sent = Cursor::new(output.bytes());
+    let mut messages = Vec::<String>::new();
+    while let Some(message) = read_message(&mut sent)
+    {
+        let message = message.unwrap();
+        let kind = message.get("type").and_then(Json::as_str).unwrap_or_default().to_string();
+        let name = message.get("event").or(message.get("command")).and_then(Json::as_str).unwrap_or_default();
+        messages.push(format!("{} {}", kind, name));
+    }
+    messages
+}
+
+const PROGRAM: &str = "print(\"first\")\nprint(\"second\")";
+
+#[test]
+fn disconnecting_while_paused_stops_the_program_without_exiting()
+{
+    let messages = session("paused", PROGRAM, vec![
+        ("initialize", Json::Null),
+        ("launch", Json::Bool(true)),
+        ("configurationDone", Json::Null),
+        ("next", Json::Null),
+        ("disconnect", Json::Null),
+        ("threads", Json::Null)
+    ]);
+
+    // Only the first line runs, and nothing is read after disconnecting
+    assert_eq!(messages, vec![
+        "response initialize",
+        "event initialized",
+        "response launch",
+        "response configurationDone",
+        "event stopped",
+        "response next",
+        "event output",
+        "event stopped",
+        "response disconnect"
+    ]);
+}
+
+#[test]
+fn disconnecting_after_the_program_finishes_ends_the_session()
+{
+    let messages = session("finished", PROGRAM, vec![
+        ("initialize", Json::Null),
+        ("launch", Json::Bool(false)),
+        ("configurationDone", Json::Null),
+        ("disconnect", Json::Null),
+        ("threads", Json::Null)
+    ]);
+
+    assert_eq!(messages, vec![
+        "response initialize",
+        "event initialized",
+        "response launch",
+        "response configurationDone",
+        "event output",
+        "event output",
+        "event exited",
+        "event terminated",
+        "response disconnect"
+    ]);
+}
+
+#[test]
+fn each_print_is_sent_as_one_output_event()
+{
+    let messages = session("output", "print(\"a\" 1 true)\nprint()\nprint(\"b\")", vec![
+        ("initialize", Json::Null),
+        ("launch", Json::Bool(false)),
+        ("configurationDone", Json::Null),
+        ("disconnect", Json::Null)
+    ]);
+
+    let outputs = messages.iter().filter(|message| *message == "event output").count();
+    assert_eq!(outputs, 3, "{:?}", messages);
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use lukascript::engine::State;
+use lukascript::lexer::tokenise_lines;
+use lukascript::parser::parse_lines;
+use lukascript::debugger::Debugger;
+use lukascript::common::ErrorKind;
+use lukascript::BufferIo;
+
+use std::io::Cursor;
+
+use common::Shared;
+
+const SCRIPT: &str = "\
+fn double: int n
+    return n * 2
+done
+int a = double(1)
+print(a)
+print(\"after\")";
+
+// Runs the script under the debugger with the given commands, giving back how it ended, what the debugger said and
+// what the script printed
+fn session(commands: &str) -> (Result<(), ErrorKind>, String, String)
+{
+    session_of(SCRIPT, commands)
+}
+
+fn session_of(script: &str, commands: &str) -> (Result<(), ErrorKind>, String, String)
+{
+    let lines = script.lines().map(String::from).collect::<Vec<String>>();
+    let instructions = parse_lines(&tokenise_lines(&lines)).unwrap();
+
+    let (said, printed) = (Shared::default(), BufferIo::default());
+    let debugger = Debugger::new(lines, Box::new(Cursor::new(commands.to_string())), Box::new(said.clone()));
+
+    let mut state = State::default();
+    state.set_io(Box::new(printed.clone()));
+    state.set_hook(Some(Box::new(debugger)));
+    let result = state.execute(instructions).map_err(|error| error.kind);
+
+    (result, said.text(), printed.output())
+}
+
+#[test]
+fn quitting_stops_the_script_without_exiting()
+{
+    let (result, said, printed) = session("break 5\ncontinue\nprint a\nquit\n");
+    assert_eq!(result, Err(ErrorKind::Stopped));
+    assert_eq!(said, "\
+line 1: fn double: int n
+(debug) breakpoint on line 5
+(debug) line 5: print(a)
+(debug) 2
+(debug) ");
+    assert_eq!(printed, "");
+}
+
+#[test]
+fn quitting_inside_a_function_stops_everything()
+{
+    let (result, said, printed) = session("step\nstep\nbacktrace\nq\n");
+    assert_eq!(result, Err(ErrorKind::Stopped));
+    assert!(said.contains("line 2: return n * 2"), "{}", said);
+    assert!(said.contains("in double(n: 1) called on line 4"), "{}", said);
+    assert_eq!(printed, "");
+}
+
+#[test]
+fn running_out_of_commands_finishes_the_script()
+{
+    let (result, said, printed) = session("next\n");
+    assert_eq!(result, Ok(()));
+    assert_eq!(said, "line 1: fn double: int n\n(debug) line 4: int a = double(1)\n(debug) \n");
+    assert_eq!(printed, "2\nafter\n");
+}
+
+// Where one function is called both from the top level and from within another
+const CALLS: &str = "\
+fn scale: int n
+    int doubled = n * 2
+    int tripled = n * 3
+    return doubled * tripled
+done
+fn twice: int n
+    int once = scale(n * 2)
+    return once * 2
+done
+int count = 1
+int result = scale(count)
+count = twice(1) - result
+print(count)";
+
+#[test]
+fn breakpoints_inside_functions_pause_each_call()
+{
+    let commands = "break 3\ncontinue\nprint n\nbacktrace\ncontinue\nprint n\nbt\ncontinue\n";
+    let (result, said, printed) = session_of(CALLS, commands);
+    assert_eq!(result, Ok(()));
+    assert_eq!(said, "\
+line 1: fn scale: int n
+(debug) breakpoint on line 3
+(debug) line 3: int tripled = n * 3
+(debug) 1
+(debug) at line 3
+    in scale(n: 1) called on line 11
+(debug) line 3: int tripled = n * 3
+(debug) 2
+(debug) at line 3
+    in scale(n: 2) called on line 7
+    in twice(n: 1) called on line 12
+(debug) ");
+    assert_eq!(printed, "42\n");
+}
+
+#[test]
+fn watched_variables_pause_the_script_once_they_change()
+{
+    let (result, said, printed) = session_of(CALLS, "watch count\ncontinue\ncontinue\nunwatch count\ncontinue\n");
+    assert_eq!(result, Ok(()));
+    assert_eq!(said, "\
+line 1: fn scale: int n
+(debug) watching count
+(debug) count changed on line 10 from (not declared) to 1
+line 11: int result = scale(count)
+(debug) count changed on line 12 from 1 to 42
+line 13: print(count)
+(debug) (debug) ");
+    assert_eq!(printed, "42\n");
+}
+
+#[test]
+fn watched_variables_inside_functions_come_and_go_with_each_call()
+{
+    // Each change is put down to the line that made it, which for a function returning is the line it was called from
+    let (result, said, _) = session_of(CALLS, "watch doubled\ncontinue\ncontinue\ncontinue\ncontinue\ncontinue\n");
+    assert_eq!(result, Ok(()));
+    assert_eq!(said, "\
+line 1: fn scale: int n
+(debug) watching doubled
+(debug) doubled changed on line 2 from (not declared) to 2
+line 3: int tripled = n * 3
+(debug) doubled changed on line 11 from 2 to (not declared)
+line 12: count = twice(1) - result
+(debug) doubled changed on line 2 from (not declared) to 4
+line 3: int tripled = n * 3
+(debug) doubled changed on line 7 from 4 to (not declared)
+line 8: return once * 2
+(debug) ");
+}
+
+#[test]
+fn stepping_out_pauses_back_in_the_caller()
+{
+    let (result, said, printed) = session_of(CALLS, "break 2\ncontinue\nout\ncontinue\nout\nbt\nout\nout\ncontinue\n");
+    assert_eq!(result, Ok(()));
+    assert_eq!(said, "\
+line 1: fn scale: int n
+(debug) breakpoint on line 2
+(debug) line 2: int doubled = n * 2
+(debug) line 12: count = twice(1) - result
+(debug) line 2: int doubled = n * 2
+(debug) line 8: return once * 2
+(debug) at line 8
+    in twice(n: 1) called on line 12
+(debug) line 13: print(count)
+(debug) not inside a function
+(debug) ");
+    assert_eq!(printed, "42\n");
+}
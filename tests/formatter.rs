@@ -0,0 +1,118 @@
+use lukascript::formatter::format_source;
+use lukascript::formatter::first_unformatted_line;
+use lukascript::lexer::tokenise_lines_with_trivia;
+
+use std::fs;
+
+fn format(source: &str) -> String
+{
+    format_source(&source.lines().map(String::from).collect::<Vec<String>>())
+}
+
+// Every script in the repository, each along with its name
+fn scripts() -> Vec<(String, String)>
+{
+    let mut scripts = vec![("src.txt".to_string(), fs::read_to_string("src.txt").unwrap())];
+    for entry in fs::read_dir("tests/scripts").unwrap()
+    {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "ls") {
+            scripts.push((path.display().to_string(), fs::read_to_string(&path).unwrap()));
+        }
+    }
+    scripts
+}
+
+#[test]
+fn blocks_are_indented_by_how_deeply_they_are_nested()
+{
+    let source = "\
+fn count: int n
+for i from 0 to n
+  if i is 2
+          print(i)
+    done
+ done
+done
+try
+throw \"x\"
+    catch e
+print(e.message)
+done";
+
+    assert_eq!(format(source), "\
+fn count: int n
+    for i from 0 to n
+        if i is 2
+            print(i)
+        done
+    done
+done
+try
+    throw \"x\"
+catch e
+    print(e.message)
+done
+");
+}
+
+#[test]
+fn tokens_and_operators_are_spaced_evenly()
+{
+    assert_eq!(format("int   total =  3*4"), "int total = 3 * 4\n");
+    assert_eq!(format("print( total   )"), "print(total)\n");
+    assert_eq!(format("struct  Point:int x  ||   int y"), "struct Point: int x || int y\n");
+
+    // Operators within strings and brackets are left alone
+    assert_eq!(format("print(\"3*4\" xs[i-1])"), "print(\"3*4\" xs[i-1])\n");
+}
+
+#[test]
+fn comments_and_blank_lines_are_kept()
+{
+    let source = "\n\n// Counts up\nint a = 1   // the start\n\n\n\nprint(a)\n    // indented\n\n\n";
+    assert_eq!(format(source), "// Counts up\nint a = 1 // the start\n\nprint(a)\n// indented\n");
+}
+
+#[test]
+fn formatting_twice_changes_nothing_more()
+{
+    for (name, source) in scripts()
+    {
+        let formatted = format(&source);
+        assert_eq!(format(&formatted), formatted, "{}", name);
+        assert_eq!(first_unformatted_line(&formatted), None, "{}", name);
+    }
+}
+
+#[test]
+fn check_gives_the_first_line_to_change()
+{
+    assert_eq!(first_unformatted_line("int a = 1\nprint(a)\n"), None);
+    assert_eq!(first_unformatted_line("int a = 1\nprint( a )\nprint(a)\n"), Some(2));
+    assert_eq!(first_unformatted_line("if 1\nprint(1)\ndone\n"), Some(2));
+    assert_eq!(first_unformatted_line("int a = 1\n\n\n"), Some(2));
+
+    // Where only the newline ending the last line is missing, that's the line to change
+    assert_eq!(first_unformatted_line("int a = 1\nprint(a)"), Some(2));
+}
+
+#[test]
+fn trivia_keeps_everything_written()
+{
+    for (name, source) in scripts()
+    {
+        let lines = source.lines().map(String::from).collect::<Vec<String>>();
+        for (line, trivia) in lines.iter().zip(tokenise_lines_with_trivia(&lines)) {
+            assert_eq!(&trivia.line(), line, "{}", name);
+        }
+    }
+
+    let lines = vec!["    int  a =  b *  2   // one  ".to_string(), "   ".to_string()];
+    let trivia = tokenise_lines_with_trivia(&lines);
+    assert_eq!(trivia[0].indent, "    ");
+    assert_eq!(trivia[0].written, vec!["int", "a", "=", "b *  2"]);
+    assert_eq!(trivia[0].spacing, vec!["  ", " ", "  ", "   "]);
+    assert_eq!(trivia[0].comment.as_deref(), Some("// one  "));
+    assert_eq!((trivia[1].indent.as_str(), trivia[1].tokens.len()), ("   ", 0));
+}
@@ -0,0 +1,8 @@
+use lukascript::golden;
+use lukascript::Limits;
+
+#[test]
+fn golden_scripts_pass()
+{
+    assert!(golden::run_directory("tests/scripts", &Limits::default()));
+}
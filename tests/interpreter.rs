@@ -0,0 +1,116 @@
+mod common;
+
+use lukascript::Interpreter;
+use lukascript::Error;
+use lukascript::Value;
+use lukascript::common::ErrorKind;
+
+use std::thread;
+
+use common::int;
+
+fn printed(result: Result<Value, Error>) -> String
+{
+    result.expect("expected a value").printed_string()
+}
+
+#[test]
+fn eval_gives_back_the_last_expression()
+{
+    let mut interpreter = Interpreter::new();
+    assert_eq!(printed(interpreter.eval("int x = 6\nx * 7")), "42");
+    assert_eq!(printed(interpreter.eval("string s = \"hi\"")), "none");
+}
+
+#[test]
+fn eval_keeps_what_was_declared_before()
+{
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("int base = 10\nfn double: int n\n    return n * 2\ndone").unwrap();
+    assert_eq!(printed(interpreter.eval("int doubled = double(base)\ndoubled")), "20");
+}
+
+#[test]
+fn eval_reports_parse_and_runtime_errors()
+{
+    let mut interpreter = Interpreter::new();
+    assert!(matches!(interpreter.eval("if 1"), Err(Error::Parse(_))));
+    assert!(matches!(interpreter.eval("print(missing)"), Err(Error::Runtime(_))));
+}
+
+#[test]
+fn parse_errors_keep_the_line_they_were_found_on()
+{
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("int a = 1").unwrap();
+
+    let Err(Error::Parse(errors)) = interpreter.eval("\nif a") else { panic!("expected a parse error") };
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].line, errors[0].message.as_str()), (2, "frame declared on line 3 does not terminate"));
+
+    let Err(Error::Parse(errors)) = interpreter.eval("let b = none") else { panic!("expected a parse error") };
+    assert_eq!(errors[0].line, 1);
+}
+
+#[test]
+fn call_passes_arguments_and_gives_back_the_result()
+{
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("fn scale: int n || int factor\n    return n * factor\ndone\nfn nothing\ndone").unwrap();
+
+    assert_eq!(printed(interpreter.call("scale", vec![int(6), int(7)])), "42");
+    assert_eq!(printed(interpreter.call("nothing", Vec::new())), "none");
+    assert!(matches!(interpreter.call("scale", vec![int(6)]), Err(Error::Runtime(_))));
+    assert!(matches!(interpreter.call("missing", Vec::new()), Err(Error::Runtime(_))));
+}
+
+#[test]
+fn globals_can_be_read_and_written()
+{
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("int count = 1").unwrap();
+    assert_eq!(interpreter.global("count").map(|value| value.printed_string()), Some("1".to_string()));
+    assert!(interpreter.global("missing").is_none());
+
+    interpreter.set_global("count", int(5)).unwrap();
+    assert_eq!(printed(interpreter.eval("count * 2")), "10");
+
+    // Globals that don't exist yet are declared
+    interpreter.set_global("limit", int(3)).unwrap();
+    assert_eq!(printed(interpreter.eval("limit")), "3");
+}
+
+#[test]
+fn interpreters_do_not_share_state()
+{
+    let mut first = Interpreter::new();
+    let mut second = Interpreter::new();
+
+    first.eval("int x = 1\nfn only_first\ndone").unwrap();
+    assert!(second.global("x").is_none());
+    assert!(matches!(second.eval("x"), Err(Error::Runtime(_))));
+    assert!(matches!(second.call("only_first", Vec::new()), Err(Error::Runtime(_))));
+
+    second.eval("int x = 2").unwrap();
+    second.set_global("x", int(20)).unwrap();
+    assert_eq!(printed(first.eval("x")), "1");
+    assert_eq!(printed(second.eval("x")), "20");
+}
+
+#[test]
+fn recursing_within_expressions_ends_with_an_error_on_a_normal_thread()
+{
+    // Calls from within expressions recurse within the engine, which mustn't overflow a thread of the default size
+    let recursed = thread::spawn(||
+    {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("fn down: int n\n    if n is 0\n        return 0\n    done\n    return down(n - 1)\ndone").unwrap();
+        let result = interpreter.eval("int reached = down(1000)");
+
+        // The interpreter carries on as normal afterwards
+        (result, printed(interpreter.eval("int again = down(3)\nagain")))
+    }).join().unwrap();
+
+    assert!(matches!(recursed.0, Err(Error::Runtime(error)) if error.kind == ErrorKind::DepthLimit));
+    assert_eq!(recursed.1, "0");
+}
@@ -0,0 +1,96 @@
+use lukascript::lexer::tokenise_lines;
+use lukascript::parser::parse_lines;
+use lukascript::lint::lint;
+
+// The codes of every warning for the source, with the line each is on
+fn warnings(source: &str) -> Vec<(&'static str, usize)>
+{
+    let lines = source.lines().map(String::from).collect::<Vec<String>>();
+    let tokens = tokenise_lines(&lines);
+    let instructions = parse_lines(&tokens).unwrap();
+    lint(&lines, &tokens, &instructions).into_iter().map(|warning| (warning.code, warning.line + 1)).collect()
+}
+
+#[test]
+fn comments_silence_warnings()
+{
+    assert_eq!(warnings("int unused = 1"), vec![("W001", 1)]);
+    assert_eq!(warnings("int unused = 1 // lint: allow"), Vec::new());
+    assert_eq!(warnings("int unused = 1 // lint: allow W001"), Vec::new());
+    assert_eq!(warnings("int unused = 1 // lint: allow W002"), vec![("W001", 1)]);
+}
+
+#[test]
+fn strings_do_not_silence_warnings()
+{
+    assert_eq!(warnings("string unused = \"// lint: allow W001 \""), vec![("W001", 1)]);
+}
+
+#[test]
+fn unused_variables_are_found()
+{
+    assert_eq!(warnings("int unused = 1\nint used = 2\nprint(used)"), vec![("W001", 1)]);
+    assert_eq!(warnings("int _ignored = 1\nfor i from 0 to 3\n    print(\"again\")\ndone"), Vec::new());
+}
+
+#[test]
+fn unreachable_code_is_found()
+{
+    assert_eq!(warnings("fn f: int n\n    return n\n    print(n)\ndone\nf(1)"), vec![("W002", 3)]);
+    assert_eq!(warnings("throw \"stop\"\n\nprint(\"never\")"), vec![("W002", 3)]);
+
+    // Blocks can end straight after, whether at a "done" (of an if, a case or a function) or a catch
+    let sign = "fn sign: int n\n    if n is 0\n        throw \"zero\"\n    done\n    return n\ndone\nsign(1)";
+    assert_eq!(warnings(sign), Vec::new());
+    assert_eq!(warnings("try\n    throw \"x\"\ncatch\n    print(\"caught\")\ndone"), Vec::new());
+    assert_eq!(warnings("\
+enum Kind
+    Some: int n
+    Empty
+done
+fn value: Kind kind
+    match kind
+        case Some n
+            return n
+        done
+        case Empty
+            return 0
+        done
+    done
+done
+print(value(Empty))"), Vec::new());
+}
+
+#[test]
+fn functions_never_called_are_found()
+{
+    assert_eq!(warnings("fn unused\n    return 1\ndone"), vec![("W003", 1)]);
+
+    // Calling itself doesn't count, as it could never get that far
+    assert_eq!(warnings("fn again: int n\n    return again(n)\ndone"), vec![("W003", 1)]);
+
+    assert_eq!(warnings("fn used\n    return 1\ndone\nprint(used())"), Vec::new());
+    assert_eq!(warnings("fn _unused\n    return 1\ndone"), Vec::new());
+}
+
+#[test]
+fn parameters_hiding_variables_are_found()
+{
+    assert_eq!(warnings("int n = 1\nprint(n)\nfn f: int n\n    return n\ndone\nf(2)"), vec![("W004", 3)]);
+    assert_eq!(warnings("fn f: int n\n    int n = 2\n    return n\ndone\nf(1)"), vec![("W004", 2)]);
+
+    // Only variables outside of any block can be hidden
+    let in_loop = "for i from 0 to 2\n    int n = i\n    print(n)\ndone\nfn f: int n\n    return n\ndone\nf(1)";
+    assert_eq!(warnings(in_loop), Vec::new());
+    assert_eq!(warnings("int m = 1\nprint(m)\nfn f: int n\n    return n\ndone\nf(m)"), Vec::new());
+}
+
+#[test]
+fn constant_conditions_are_found()
+{
+    assert_eq!(warnings("if true\n    print(1)\ndone"), vec![("W005", 1)]);
+    assert_eq!(warnings("const int LIMIT = 3\nif LIMIT is 3\n    print(1)\ndone"), vec![("W005", 2)]);
+
+    assert_eq!(warnings("int x = 1\nif x is 1\n    print(x)\ndone"), Vec::new());
+    assert_eq!(warnings("const int LIMIT = 3\nint x = 1\nif x is not LIMIT\n    print(x)\ndone"), Vec::new());
+}
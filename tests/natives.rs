@@ -0,0 +1,103 @@
+mod common;
+
+use lukascript::Interpreter;
+use lukascript::Error;
+use lukascript::Natives;
+use lukascript::Parameters;
+use lukascript::BufferIo;
+use lukascript::common::ErrorKind;
+use lukascript::variables::VariableType;
+
+use common::int;
+use common::string;
+
+// A native adding two integers together
+fn natives() -> Natives
+{
+    let mut natives = Natives::empty();
+    let parameters = Parameters::Exactly(vec![Some(VariableType::Integer(0)), Some(VariableType::Integer(0))]);
+    natives.register("add", parameters, Some(VariableType::Integer(0)), |_, arguments|
+    {
+        match (&arguments[0].variable_type, &arguments[1].variable_type)
+        {
+            (VariableType::Integer(a), VariableType::Integer(b)) => Ok(Some(int(a + b))),
+            _ => unreachable!()
+        }
+    });
+    natives
+}
+
+#[test]
+fn call_checks_how_many_arguments_are_given()
+{
+    let natives = natives();
+    let add = natives.get("add").unwrap();
+    let mut io = BufferIo::default();
+
+    assert_eq!(add.call("add", &[int(2), int(3)], &mut io).unwrap().unwrap().printed_string(), "5");
+
+    let error = add.call("add", &[int(2)], &mut io).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Argument);
+    assert_eq!(error.message, "add expects 2 argument(s) but was given 1");
+    assert_eq!(add.call("add", &[int(1), int(2), int(3)], &mut io).unwrap_err().kind, ErrorKind::Argument);
+}
+
+#[test]
+fn call_rejects_arguments_of_the_wrong_type()
+{
+    let natives = natives();
+    let add = natives.get("add").unwrap();
+    let mut io = BufferIo::default();
+
+    let error = add.call("add", &[int(2), string("three")], &mut io).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert!(error.message.starts_with("argument 2 of add is invalid"), "{}", error.message);
+}
+
+#[test]
+fn call_rejects_functions_not_returning_what_they_said()
+{
+    let mut natives = Natives::empty();
+    natives.register("nothing", Parameters::AnyNumber(None), Some(VariableType::Integer(0)), |_, _| Ok(None));
+    let error = natives.get("nothing").unwrap().call("nothing", &[], &mut BufferIo::default()).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+}
+
+#[test]
+fn standard_library_functions_are_registered()
+{
+    let mut natives = Natives::default();
+    assert!(natives.names().contains(&"len".to_string()));
+    assert!(natives.has_builtin("len"));
+
+    assert!(natives.remove("len"));
+    assert!(!natives.has_builtin("len"));
+    assert!(!natives.names().contains(&"len".to_string()));
+    assert!(!natives.remove("len"));
+}
+
+#[test]
+fn standard_library_functions_can_be_taken_away()
+{
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.eval("int n = len(\"abc\")\nn").unwrap().printed_string(), "3");
+
+    interpreter.natives().remove("len");
+    interpreter.natives().remove("assert_error");
+    assert!(matches!(interpreter.eval("int m = len(\"abc\")"), Err(Error::Runtime(e)) if e.kind == ErrorKind::Name));
+    assert!(matches!(interpreter.eval("assert_error(1)"), Err(Error::Runtime(e)) if e.kind == ErrorKind::Name));
+
+    // Another untouched interpreter still has them
+    assert_eq!(Interpreter::new().eval("int n = len(\"ab\")\nn").unwrap().printed_string(), "2");
+}
+
+#[test]
+fn natives_can_take_the_place_of_standard_library_functions()
+{
+    let mut interpreter = Interpreter::new();
+    interpreter.natives().register("len", Parameters::AnyNumber(None), Some(VariableType::Integer(0)), |_, _|
+    {
+        Ok(Some(int(99)))
+    });
+    assert_eq!(interpreter.eval("int n = len(\"abc\")\nn").unwrap().printed_string(), "99");
+}
@@ -0,0 +1,153 @@
+use lukascript::engine::State;
+use lukascript::lexer::tokenise_lines;
+use lukascript::parser::parse_lines;
+use lukascript::profiler::Profile;
+use lukascript::profiler::Profiler;
+use lukascript::variables::Variable;
+use lukascript::variables::VariableType;
+use lukascript::Parameters;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+// How long the "pause" native takes
+const PAUSE: Duration = Duration::from_millis(30);
+
+// Profiles the script, which can call "pause" to take a while without running any lines
+fn profile(source: &str) -> (Profile, Vec<String>)
+{
+    let lines = source.lines().map(String::from).collect::<Vec<String>>();
+    let instructions = parse_lines(&tokenise_lines(&lines)).unwrap();
+    let profile = Rc::new(RefCell::new(Profile::default()));
+
+    let mut state = State::default();
+    state.natives().register("pause", Parameters::Exactly(Vec::new()), Some(VariableType::Integer(0)), |_, _|
+    {
+        thread::sleep(PAUSE);
+        Ok(Some(Variable { variable_type: VariableType::Integer(1) }))
+    });
+    state.set_hook(Some(Box::new(Profiler::new(profile.clone()))));
+    state.execute(instructions).unwrap();
+
+    profile.borrow_mut().finish();
+    let profile = profile.take();
+    (profile, lines)
+}
+
+// The columns of the report's row for a line (by its number) or function (by its name), after the first
+fn row(report: &str, first: &str) -> Vec<String>
+{
+    let row = report.lines().find(|row| row.split_whitespace().next() == Some(first));
+    row.unwrap_or_else(|| panic!("no row for {} in:\n{}", first, report)).split_whitespace().skip(1).map(String::from).collect()
+}
+
+fn milliseconds(column: &str) -> f64
+{
+    column.strip_suffix("ms").unwrap().parse::<f64>().unwrap()
+}
+
+// Each folded stack along with its time, in microseconds
+fn stacks(folded: &str) -> Vec<(String, u128)>
+{
+    folded.lines().map(|line| {
+        let (stack, time) = line.rsplit_once(' ').unwrap();
+        (stack.to_string(), time.parse::<u128>().unwrap())
+    }).collect()
+}
+
+#[test]
+fn report_counts_how_often_each_line_and_function_runs()
+{
+    let (profile, lines) = profile("\
+fn square: int n
+    return n * n
+done
+for i from 0 to 3
+    int s = square(i)
+done
+print(\"done\")");
+    let report = profile.report(&lines);
+
+    assert!(report.starts_with("=== Profile ===\nLines, by time spent on them (not counting functions they call)\n"));
+    assert_eq!(row(&report, "2")[..1], ["3"]);
+    assert_eq!(row(&report, "5")[..1], ["3"]);
+    assert_eq!(row(&report, "7")[..1], ["1"]);
+    assert_eq!(row(&report, "7")[2..], ["print(\"done\")"]);
+    assert_eq!(row(&report, "square")[..1], ["3"]);
+
+    // A loop starts once, and goes back round from its end
+    assert_eq!(row(&report, "4")[..1], ["1"]);
+    assert_eq!(row(&report, "6")[..1], ["3"]);
+}
+
+#[test]
+fn the_rest_of_a_line_is_its_own_once_a_function_it_calls_returns()
+{
+    let (profile, lines) = profile("\
+fn one
+    return 1
+done
+int total = one() * pause()");
+    let report = profile.report(&lines);
+    let pause = PAUSE.as_secs_f64() * 1000.0;
+
+    assert!(milliseconds(&row(&report, "4")[1]) >= pause, "{}", report);
+    assert!(milliseconds(&row(&report, "2")[1]) < pause, "{}", report);
+
+    let one = row(&report, "one");
+    assert_eq!(one[0], "1");
+    assert!(milliseconds(&one[1]) < pause && milliseconds(&one[2]) < pause, "{}", report);
+
+    let stacks = stacks(&profile.folded());
+    assert!(stacks.iter().any(|(stack, time)| stack == "<script>" && *time >= PAUSE.as_micros()), "{:?}", stacks);
+    assert!(stacks.iter().any(|(stack, time)| stack == "<script>;one" && *time < PAUSE.as_micros()), "{:?}", stacks);
+}
+
+#[test]
+fn functions_are_timed_with_and_without_those_they_call()
+{
+    let (profile, lines) = profile("\
+fn inner
+    int waited = pause()
+    return waited
+done
+fn outer
+    int got = inner()
+    return got
+done
+int result = outer()");
+    let report = profile.report(&lines);
+    let pause = PAUSE.as_secs_f64() * 1000.0;
+
+    let (outer, inner) = (row(&report, "outer"), row(&report, "inner"));
+    assert!(milliseconds(&outer[1]) >= pause && milliseconds(&outer[2]) < pause, "{}", report);
+    assert!(milliseconds(&inner[1]) >= pause && milliseconds(&inner[2]) >= pause, "{}", report);
+
+    // Most time spent first
+    assert!(report.find("\nouter").unwrap() < report.find("\ninner").unwrap(), "{}", report);
+    assert!(report.find("\n     2 ").unwrap() < report.find("\n     6 ").unwrap(), "{}", report);
+}
+
+#[test]
+fn folded_gives_each_stack_of_calls_once()
+{
+    let (profile, _) = profile("\
+fn inner
+    int waited = pause()
+    return waited
+done
+fn outer
+    int got = inner()
+    return got
+done
+int first = outer()
+int second = inner()");
+    let stacks = stacks(&profile.folded());
+
+    let names = stacks.iter().map(|(stack, _)| stack.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, ["<script>", "<script>;inner", "<script>;outer", "<script>;outer;inner"]);
+    assert!(stacks[1].1 >= PAUSE.as_micros() && stacks[3].1 >= PAUSE.as_micros(), "{:?}", stacks);
+    assert!(stacks[2].1 < PAUSE.as_micros(), "{:?}", stacks);
+}
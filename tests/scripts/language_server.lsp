@@ -0,0 +1,25 @@
+// A whole session: opening a document, asking about it, breaking it, then shutting down
+{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}
+// expect: {"jsonrpc":"2.0","id":1,"result":{"capabilities":{"textDocumentSync":1,"definitionProvider":true,"hoverProvider":true,"completionProvider":{},"documentSymbolProvider":true},"serverInfo":{"name":"lukascript"}}}
+{"jsonrpc": "2.0", "method": "initialized", "params": {}}
+{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {"uri": "file:///area.ls", "languageId": "lukascript", "version": 1, "text": "fn area: int width || int height\n    return width * height\ndone\n\narea(2 3) -> result\nprint(result)\nint unused = 1\n"}}}
+// expect: {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///area.ls","diagnostics":[{"range":{"start":{"line":6,"character":0},"end":{"line":6,"character":14}},"severity":2,"code":"W001","source":"lukascript","message":"variable \"unused\" is never used"}]}}
+{"jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {"textDocument": {"uri": "file:///area.ls"}, "position": {"line": 4, "character": 1}}}
+// expect: {"jsonrpc":"2.0","id":2,"result":{"uri":"file:///area.ls","range":{"start":{"line":0,"character":3},"end":{"line":0,"character":7}}}}
+{"jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": {"textDocument": {"uri": "file:///area.ls"}, "position": {"line": 1, "character": 20}}}
+// expect: {"jsonrpc":"2.0","id":3,"result":{"contents":{"kind":"markdown","value":"```lukascript\nint height\n```"}}}
+{"jsonrpc": "2.0", "id": 4, "method": "textDocument/hover", "params": {"textDocument": {"uri": "file:///area.ls"}, "position": {"line": 5, "character": 8}}}
+// expect: {"jsonrpc":"2.0","id":4,"result":{"contents":{"kind":"markdown","value":"```lukascript\nresult (returned by area)\n```"}}}
+{"jsonrpc": "2.0", "id": 5, "method": "textDocument/documentSymbol", "params": {"textDocument": {"uri": "file:///area.ls"}}}
+// expect: {"jsonrpc":"2.0","id":5,"result":[{"name":"area","detail":"fn area: int width || int height","kind":12,"range":{"start":{"line":0,"character":0},"end":{"line":2,"character":4}},"selectionRange":{"start":{"line":0,"character":0},"end":{"line":0,"character":32}}}]}
+{"jsonrpc": "2.0", "id": 6, "method": "textDocument/hover", "params": {"textDocument": {"uri": "file:///area.ls"}, "position": {"line": 3, "character": 0}}}
+// expect: {"jsonrpc":"2.0","id":6,"result":null}
+{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {"textDocument": {"uri": "file:///area.ls", "version": 2}, "contentChanges": [{"text": "if 1\nprint(2)\n"}]}}
+// expect: {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///area.ls","diagnostics":[{"range":{"start":{"line":0,"character":0},"end":{"line":0,"character":4}},"severity":1,"source":"lukascript","message":"frame declared on line 1 does not terminate"}]}}
+{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {"textDocument": {"uri": "file:///area.ls", "version": 3}, "contentChanges": [{"text": "int a = 1\n\nif a\nprint(a)\n"}]}}
+// expect: {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///area.ls","diagnostics":[{"range":{"start":{"line":2,"character":0},"end":{"line":2,"character":4}},"severity":1,"source":"lukascript","message":"frame declared on line 3 does not terminate"}]}}
+{"jsonrpc": "2.0", "id": 7, "method": "textDocument/unknown", "params": {}}
+// expect: {"jsonrpc":"2.0","id":7,"error":{"code":-32601,"message":"unknown method \"textDocument/unknown\""}}
+{"jsonrpc": "2.0", "id": 8, "method": "shutdown"}
+// expect: {"jsonrpc":"2.0","id":8,"result":null}
+{"jsonrpc": "2.0", "method": "exit"}
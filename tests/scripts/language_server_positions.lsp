@@ -0,0 +1,13 @@
+// Columns are counted in UTF-16 code units, so each emoji (outside the basic plane) takes up two
+{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}
+// expect: {"jsonrpc":"2.0","id":1,"result":{"capabilities":{"textDocumentSync":1,"definitionProvider":true,"hoverProvider":true,"completionProvider":{},"documentSymbolProvider":true},"serverInfo":{"name":"lukascript"}}}
+{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {"uri": "file:///faces.ls", "languageId": "lukascript", "version": 1, "text": "string face = \"😀\"\nprint(\"😀\" face)\nstring unused = \"😀😀\"\n"}}}
+// expect: {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///faces.ls","diagnostics":[{"range":{"start":{"line":2,"character":0},"end":{"line":2,"character":22}},"severity":2,"code":"W001","source":"lukascript","message":"variable \"unused\" is never used"}]}}
+// The end of "face", just past the emoji before it, and then the space before it
+{"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {"textDocument": {"uri": "file:///faces.ls"}, "position": {"line": 1, "character": 15}}}
+// expect: {"jsonrpc":"2.0","id":2,"result":{"contents":{"kind":"markdown","value":"```lukascript\nstring face\n```"}}}
+{"jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": {"textDocument": {"uri": "file:///faces.ls"}, "position": {"line": 1, "character": 10}}}
+// expect: {"jsonrpc":"2.0","id":3,"result":null}
+{"jsonrpc": "2.0", "id": 4, "method": "shutdown"}
+// expect: {"jsonrpc":"2.0","id":4,"result":null}
+{"jsonrpc": "2.0", "method": "exit"}
@@ -0,0 +1,54 @@
+use lukascript::Interpreter;
+use lukascript::Error;
+use lukascript::BufferIo;
+use lukascript::common::ErrorKind;
+
+#[test]
+fn buffer_io_captures_output()
+{
+    let io = BufferIo::new("");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(Box::new(io.clone()));
+
+    interpreter.eval("print(\"hello\")\nprint(1 2)").unwrap();
+    assert_eq!(io.output(), "hello\n12\n");
+
+    io.clear_output();
+    interpreter.eval("print(\"again\")").unwrap();
+    assert_eq!(io.output(), "again\n");
+    assert_eq!(io.errors(), "");
+}
+
+#[test]
+fn input_reads_each_line_given()
+{
+    let io = BufferIo::new("first\nsecond");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(Box::new(io.clone()));
+
+    interpreter.eval("input(\"name? \") -> a\ninput() -> b\nprint(b a)").unwrap();
+    assert_eq!(io.output(), "name? secondfirst\n");
+
+    io.push_input("third");
+    interpreter.eval("input() -> c\nprint(c)").unwrap();
+    assert!(io.output().ends_with("third\n"));
+}
+
+#[test]
+fn input_fails_once_there_is_nothing_left_to_read()
+{
+    let io = BufferIo::new("only");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(Box::new(io.clone()));
+
+    interpreter.eval("input() -> a").unwrap();
+    match interpreter.eval("input() -> b")
+    {
+        Err(Error::Runtime(error)) =>
+        {
+            assert_eq!(error.kind, ErrorKind::EndOfInput);
+            assert_eq!(error.message, "no more input to read");
+        },
+        _ => panic!("expected reading past the end of input to fail")
+    }
+}
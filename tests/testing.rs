@@ -0,0 +1,101 @@
+use lukascript::lexer::tokenise_lines;
+use lukascript::parser::parse_lines;
+use lukascript::testing::run_tests;
+use lukascript::Limits;
+
+// Runs the tests in the source, giving back if they all passed and what was reported
+fn tests(source: &str) -> (bool, String)
+{
+    let lines = source.lines().map(String::from).collect::<Vec<String>>();
+    let instructions = parse_lines(&tokenise_lines(&lines)).unwrap();
+
+    let mut output = Vec::<u8>::new();
+    let passed = run_tests(&instructions, &Limits::default(), &mut output).unwrap();
+    (passed, String::from_utf8(output).unwrap())
+}
+
+const SCRIPT: &str = "\
+const int BASE = 10
+struct Pair: int a || int b
+fn double: int n
+    return n * 2
+done
+enum Shape
+    Circle: int radius
+    Square
+done
+
+test \"passes\"
+    Pair pair = Pair(1 2)
+    print(\"kept back\")
+    assert_eq(double(pair.b) 4)
+    assert_eq(BASE 10)
+    Shape shape = Circle(3)
+    match shape
+        case Circle radius
+            assert_eq(radius 3)
+        done
+        case _
+            assert(false)
+        done
+    done
+done
+
+test \"fails\"
+    print(\"shown\")
+    assert_eq(double(1) 3)
+done
+
+test \"still runs after a failure\"
+    assert(true)
+done";
+
+#[test]
+fn each_test_passes_or_fails_on_its_own()
+{
+    let (passed, output) = tests(SCRIPT);
+    assert!(!passed);
+    assert_eq!(output, "\
+PASS passes
+FAIL fails
+    assert_eq failed, left: 2, right: 3 - line 29
+    output:
+        shown
+PASS still runs after a failure
+
+2 passed, 1 failed
+");
+}
+
+#[test]
+fn only_declarations_are_run_before_each_test()
+{
+    // Were the rest of the top level run, every test would fail reading input, or see what it left behind
+    let script = "\
+string name = input()
+array seen = []
+for i from 0 to 3
+    print(i)
+done
+fn greeting
+    return \"hello\"
+done
+
+test \"first\"
+    array seen = [1]
+    assert_eq(greeting() \"hello\")
+done
+
+test \"second\"
+    array seen = []
+    assert_eq(len(seen) 0)
+done";
+
+    assert_eq!(tests(script), (true, "PASS first\nPASS second\n\n2 passed, 0 failed\n".to_string()));
+}
+
+#[test]
+fn scripts_without_tests_pass()
+{
+    assert_eq!(tests("print(\"not a test\")"), (true, "\n0 passed, 0 failed\n".to_string()));
+}
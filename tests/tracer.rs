@@ -0,0 +1,147 @@
+mod common;
+
+use lukascript::engine::State;
+use lukascript::lexer::tokenise_lines;
+use lukascript::parser::parse_lines;
+use lukascript::tracer::parse_line_range;
+use lukascript::tracer::Tracer;
+use lukascript::tracer::TraceOptions;
+use lukascript::json::Json;
+use lukascript::BufferIo;
+
+use common::Shared;
+
+const SCRIPT: &str = "\
+fn double: int n
+    int result = n * 2
+    return result
+done
+double(4) -> a
+int b = double(a)
+print(b)";
+
+fn trace(options: TraceOptions) -> String
+{
+    let lines = SCRIPT.lines().map(String::from).collect::<Vec<String>>();
+    let instructions = parse_lines(&tokenise_lines(&lines)).unwrap();
+    let output = Shared::default();
+
+    let mut state = State::default();
+    state.set_io(Box::new(BufferIo::default()));
+    state.set_hook(Some(Box::new(Tracer::new(lines, instructions.clone(), options, Box::new(output.clone())))));
+    state.execute(instructions).unwrap();
+    output.text()
+}
+
+// The event, line and name or value of each entry
+fn entries(trace: &str) -> Vec<(String, usize, String)>
+{
+    trace.lines().map(|line| {
+        let entry = Json::parse(line).unwrap();
+        let field = |name: &str| entry.get(name).and_then(Json::as_str).unwrap_or_default().to_string();
+        let what = match field("event").as_str()
+        {
+            "line" => field("instruction"),
+            "write" => field("name"),
+            _ => field("value")
+        };
+        (field("event"), entry.get("line").and_then(Json::as_usize).unwrap(), what)
+    }).collect()
+}
+
+fn entry(event: &str, line: usize, what: &str) -> (String, usize, String)
+{
+    (event.to_string(), line, what.to_string())
+}
+
+#[test]
+fn lines_values_and_writes_are_logged_as_they_happen()
+{
+    assert_eq!(trace(TraceOptions::default()), "\
+line 1 FunctionDeclaration: fn double: int n
+line 5 FunctionCall: double(4) -> a
+      n := 4
+  line 2 IntDeclaration: int result = n * 2
+      n*2 => 8
+      result := 8
+  line 3 Return: return result
+      result => 8
+    a := 8
+line 6 IntDeclaration: int b = double(a)
+    a => 8
+      n := 8
+  line 2 IntDeclaration: int result = n * 2
+      n*2 => 16
+      result := 16
+  line 3 Return: return result
+      result => 16
+    double(a) => 16
+    b := 16
+line 7 FunctionCall: print(b)
+    b => 16
+");
+}
+
+#[test]
+fn json_entries_say_where_each_happened()
+{
+    let trace = trace(TraceOptions { json: true, ..Default::default() });
+    let lines = trace.lines().collect::<Vec<&str>>();
+
+    assert_eq!(lines[0], "{\"event\":\"line\",\"line\":1,\"depth\":0,\"function\":null,\
+        \"instruction\":\"FunctionDeclaration\",\"source\":\"fn double: int n\"}");
+    assert_eq!(lines[4], "{\"event\":\"evaluate\",\"line\":2,\"depth\":1,\"function\":\"double\",\
+        \"value\":\"n*2\",\"result\":\"8\",\"type\":\"int\"}");
+
+    // Arguments are written where the function is declared, and what it returns where it was called from
+    assert_eq!(lines[2], "{\"event\":\"write\",\"line\":1,\"depth\":1,\"function\":\"double\",\
+        \"name\":\"n\",\"value\":\"4\",\"type\":\"int\"}");
+    assert_eq!(lines[8], "{\"event\":\"write\",\"line\":5,\"depth\":0,\"function\":null,\
+        \"name\":\"a\",\"value\":\"8\",\"type\":\"int\"}");
+    assert_eq!(entries(&trace)[11], entry("write", 1, "n"));
+}
+
+#[test]
+fn only_what_runs_within_the_function_is_logged()
+{
+    let options = TraceOptions { function: Some("double".to_string()), json: true, ..Default::default() };
+    let entries = entries(&trace(options));
+
+    assert_eq!(entries[..6], [
+        entry("write", 1, "n"),
+        entry("line", 2, "IntDeclaration"),
+        entry("evaluate", 2, "n*2"),
+        entry("write", 2, "result"),
+        entry("line", 3, "Return"),
+        entry("evaluate", 3, "result")
+    ]);
+    assert_eq!(entries.len(), 12);
+    assert_eq!(trace(TraceOptions { function: Some("missing".to_string()), ..Default::default() }), "");
+}
+
+#[test]
+fn only_the_lines_given_are_logged()
+{
+    let options = TraceOptions { lines: parse_line_range("5-6"), json: true, ..Default::default() };
+    assert_eq!(entries(&trace(options)), [
+        entry("line", 5, "FunctionCall"),
+        entry("write", 5, "a"),
+        entry("line", 6, "IntDeclaration"),
+        entry("evaluate", 6, "a"),
+        entry("evaluate", 6, "double(a)"),
+        entry("write", 6, "b")
+    ]);
+
+    let options = TraceOptions { lines: parse_line_range("1"), ..Default::default() };
+    assert_eq!(trace(options), "line 1 FunctionDeclaration: fn double: int n\n      n := 4\n      n := 8\n");
+}
+
+#[test]
+fn line_ranges_are_read_from_one_or_two_numbers()
+{
+    assert_eq!(parse_line_range("12"), Some((11, 11)));
+    assert_eq!(parse_line_range("12-20"), Some((11, 19)));
+    assert_eq!(parse_line_range("0-3"), None);
+    assert_eq!(parse_line_range("5-2"), None);
+    assert_eq!(parse_line_range("five"), None);
+}